@@ -1,73 +1,275 @@
-use api_dependence::{mongodb::mongodbapi, mysql::mysqlapi, sqlite::sqliteapi};
-use axum::{Router, routing::get};
+use api_dependence::{
+    access::{self, AccessControl},
+    admin::{self, AdminState},
+    avatar::{self, AvatarConfig},
+    cache::{self, ResponseCache},
+    health,
+    metrics::{self, MetricsConfig},
+    mongodb::mongodbapi,
+    mysql::mysqlapi,
+    sqlite::sqliteapi,
+};
+use axum::{
+    Extension, Router,
+    http::HeaderValue,
+    middleware,
+    routing::{get, post},
+};
+use data_structures::config::{ApiSettings, Settings};
 use db::{mongo, mysql, sqlite};
+use std::net::SocketAddr;
 use tools::init_tracing;
 use tower::ServiceBuilder;
-use tower_http::cors::{Any, CorsLayer};
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 use tower_http::trace::TraceLayer;
-use tracing::error;
+use tracing::{error, info, warn};
 
-// 创建 SQLite 应用
-pub async fn create_sqlite_app(db_path: &str) -> Router {
-    let cors = CorsLayer::new()
+// 按`API.allowed_origins`限制跨域来源
+fn cors_layer(fc_settings: &Settings) -> CorsLayer {
+    let api = &fc_settings.api;
+    let allow_origin = if api.allow_any_origin() {
+        AllowOrigin::any()
+    } else {
+        let origins: Vec<HeaderValue> = api
+            .allowed_origins
+            .iter()
+            .filter_map(
+                |origin| match HeaderValue::from_str(origin.trim_end_matches('/')) {
+                    Ok(v) => Some(v),
+                    Err(e) => {
+                        warn!("跨域来源 {} 无效，已忽略: {}", origin, e);
+                        None
+                    }
+                },
+            )
+            .collect();
+        AllowOrigin::list(origins)
+    };
+    CorsLayer::new()
         .allow_methods(Any)
-        .allow_origin(Any)
-        .allow_headers(Any);
+        .allow_origin(allow_origin)
+        .allow_headers(Any)
+}
+
+// 设置了`API.path_prefix`时将所有路由挂载到该前缀下
+fn with_path_prefix(router: Router, api: &ApiSettings) -> Router {
+    match api.path_prefix() {
+        Some(prefix) => Router::new().nest(&prefix, router),
+        None => router,
+    }
+}
+
+// 开启指标时提供`/metrics`
+fn metrics_router<S: Clone + Send + Sync + 'static>(fc_settings: &Settings) -> Router<S> {
+    if fc_settings.metrics.enable {
+        Router::new().route("/metrics", get(metrics::get_metrics))
+    } else {
+        Router::new()
+    }
+}
+
+// 设置了`ADMIN.token`时提供`/admin`下的路由，`routes`为各数据库的友链和屏蔽站点路由
+fn admin_router<S: Clone + Send + Sync + 'static>(
+    fc_settings: &Settings,
+    routes: Router<S>,
+) -> Router<S> {
+    match AdminState::from_settings(fc_settings) {
+        Some(admin_state) => routes
+            .route("/admin/crawl", post(admin::post_crawl))
+            .route_layer(middleware::from_fn_with_state(
+                admin_state.clone(),
+                admin::require_admin_token,
+            ))
+            .layer(Extension(admin_state)),
+        None => Router::new(),
+    }
+}
+
+// 挂载路径前缀，并添加各数据库共用的中间件，`fetch_version`为响应缓存查询最近一次运行时间的方法
+fn with_layers<F, Fut>(router: Router, fc_settings: &Settings, fetch_version: F) -> Router
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Option<String>> + Send + 'static,
+{
+    let response_cache = ResponseCache::new(&fc_settings.http_cache, fetch_version);
+    let access_control = AccessControl::from(fc_settings);
     let service = ServiceBuilder::new()
         .layer(TraceLayer::new_for_http())
-        .layer(cors);
+        .layer(CompressionLayer::new())
+        .option_layer(
+            fc_settings
+                .metrics
+                .enable
+                .then(|| middleware::from_fn(metrics::track_metrics)),
+        )
+        .layer(cors_layer(fc_settings))
+        .option_layer(access_control.is_enabled().then(|| {
+            middleware::from_fn_with_state(access_control.clone(), access::access_control)
+        }))
+        .layer(Extension(fc_settings.tag_aliases()))
+        .layer(Extension(AvatarConfig::from(fc_settings)))
+        .layer(Extension(fc_settings.friend_activity()))
+        .layer(Extension(fc_settings.readiness()))
+        .option_layer(
+            fc_settings
+                .metrics
+                .enable
+                .then(|| Extension(MetricsConfig::from(fc_settings))),
+        )
+        .option_layer(
+            fc_settings
+                .http_cache
+                .enable
+                .then(|| middleware::from_fn_with_state(response_cache, cache::cache_responses)),
+        );
+    with_path_prefix(router, &fc_settings.api).layer(service)
+}
 
+// 创建 SQLite 应用
+pub async fn create_sqlite_app(db_path: &str, fc_settings: &Settings) -> Router {
     let dbpool = sqlite::connect_sqlite_dbpool(db_path).await.unwrap();
-    Router::new()
+    let router = Router::new()
         .route("/all", get(sqliteapi::get_all))
         .route("/friend", get(sqliteapi::get_friend))
         .route("/post", get(sqliteapi::get_post))
         .route("/randomfriend", get(sqliteapi::get_randomfriend))
         .route("/randompost", get(sqliteapi::get_randompost))
-        .with_state(dbpool)
-        .layer(service)
+        .route("/search", get(sqliteapi::get_search))
+        .route("/tags", get(sqliteapi::get_tags))
+        .route("/tag/{name}", get(sqliteapi::get_tag))
+        .route("/new", get(sqliteapi::get_new))
+        .route("/healthz", get(health::get_healthz))
+        .route("/readyz", get(sqliteapi::get_readyz))
+        .route("/avatar/{hash}", get(avatar::get_avatar))
+        .merge(metrics_router(fc_settings))
+        .merge(admin_router(
+            fc_settings,
+            Router::new()
+                .route(
+                    "/admin/friends",
+                    get(sqliteapi::get_admin_friends)
+                        .post(sqliteapi::post_admin_friends)
+                        .put(sqliteapi::put_admin_friends)
+                        .delete(sqliteapi::delete_admin_friends),
+                )
+                .route(
+                    "/admin/block",
+                    get(sqliteapi::get_admin_block)
+                        .post(sqliteapi::post_admin_block)
+                        .delete(sqliteapi::delete_admin_block),
+                )
+                .route(
+                    "/admin/overrides",
+                    get(sqliteapi::get_admin_overrides)
+                        .put(sqliteapi::put_admin_overrides)
+                        .delete(sqliteapi::delete_admin_overrides),
+                ),
+        ))
+        .with_state(dbpool.clone());
+    with_layers(router, fc_settings, move || {
+        let pool = dbpool.clone();
+        async move { sqlite::select_latest_time_from_posts(&pool).await.ok() }
+    })
 }
 
 // 创建 MySQL 应用
-pub async fn create_mysql_app(conn_str: &str) -> Router {
-    let cors = CorsLayer::new()
-        .allow_methods(Any)
-        .allow_origin(Any)
-        .allow_headers(Any);
-    let service = ServiceBuilder::new()
-        .layer(TraceLayer::new_for_http())
-        .layer(cors);
-
-    let dbpool = mysql::connect_mysql_dbpool(conn_str).await.unwrap();
-    Router::new()
+pub async fn create_mysql_app(conn_str: &str, fc_settings: &Settings) -> Router {
+    let dbpool = mysql::connect_mysql_dbpool_lazy(conn_str).unwrap();
+    let router = Router::new()
         .route("/all", get(mysqlapi::get_all))
         .route("/friend", get(mysqlapi::get_friend))
         .route("/post", get(mysqlapi::get_post))
         .route("/randomfriend", get(mysqlapi::get_randomfriend))
         .route("/randompost", get(mysqlapi::get_randompost))
-        .with_state(dbpool)
-        .layer(service)
+        .route("/search", get(mysqlapi::get_search))
+        .route("/tags", get(mysqlapi::get_tags))
+        .route("/tag/{name}", get(mysqlapi::get_tag))
+        .route("/new", get(mysqlapi::get_new))
+        .route("/healthz", get(health::get_healthz))
+        .route("/readyz", get(mysqlapi::get_readyz))
+        .route("/avatar/{hash}", get(avatar::get_avatar))
+        .merge(metrics_router(fc_settings))
+        .merge(admin_router(
+            fc_settings,
+            Router::new()
+                .route(
+                    "/admin/friends",
+                    get(mysqlapi::get_admin_friends)
+                        .post(mysqlapi::post_admin_friends)
+                        .put(mysqlapi::put_admin_friends)
+                        .delete(mysqlapi::delete_admin_friends),
+                )
+                .route(
+                    "/admin/block",
+                    get(mysqlapi::get_admin_block)
+                        .post(mysqlapi::post_admin_block)
+                        .delete(mysqlapi::delete_admin_block),
+                )
+                .route(
+                    "/admin/overrides",
+                    get(mysqlapi::get_admin_overrides)
+                        .put(mysqlapi::put_admin_overrides)
+                        .delete(mysqlapi::delete_admin_overrides),
+                ),
+        ))
+        .with_state(dbpool.clone());
+    with_layers(router, fc_settings, move || {
+        let pool = dbpool.clone();
+        async move { mysql::select_latest_time_from_posts(&pool).await.ok() }
+    })
 }
 
-async fn create_mongodb_app(mongodburi: &str) -> Router {
-    let cors = CorsLayer::new()
-        .allow_methods(Any)
-        .allow_origin(Any)
-        .allow_headers(Any);
-    let service = ServiceBuilder::new()
-        .layer(TraceLayer::new_for_http())
-        .layer(cors);
-
+async fn create_mongodb_app(mongodburi: &str, fc_settings: &Settings) -> Router {
     let clientdb = mongo::connect_mongodb_clientdb(mongodburi).await.unwrap();
-
-    Router::new()
+    if let Err(e) = mongo::create_posts_text_index(&clientdb).await {
+        error!("创建全文索引失败: {}", e);
+    }
+    if let Err(e) = mongo::create_admin_indexes(&clientdb).await {
+        error!("创建唯一索引失败: {}", e);
+    }
+    let router = Router::new()
         .route("/all", get(mongodbapi::get_all))
         .route("/friend", get(mongodbapi::get_friend))
         .route("/post", get(mongodbapi::get_post))
         .route("/randomfriend", get(mongodbapi::get_randomfriend))
         .route("/randompost", get(mongodbapi::get_randompost))
-        .with_state(clientdb)
-        .layer(service)
+        .route("/search", get(mongodbapi::get_search))
+        .route("/tags", get(mongodbapi::get_tags))
+        .route("/tag/{name}", get(mongodbapi::get_tag))
+        .route("/new", get(mongodbapi::get_new))
+        .route("/healthz", get(health::get_healthz))
+        .route("/readyz", get(mongodbapi::get_readyz))
+        .route("/avatar/{hash}", get(avatar::get_avatar))
+        .merge(metrics_router(fc_settings))
+        .merge(admin_router(
+            fc_settings,
+            Router::new()
+                .route(
+                    "/admin/friends",
+                    get(mongodbapi::get_admin_friends)
+                        .post(mongodbapi::post_admin_friends)
+                        .put(mongodbapi::put_admin_friends)
+                        .delete(mongodbapi::delete_admin_friends),
+                )
+                .route(
+                    "/admin/block",
+                    get(mongodbapi::get_admin_block)
+                        .post(mongodbapi::post_admin_block)
+                        .delete(mongodbapi::delete_admin_block),
+                )
+                .route(
+                    "/admin/overrides",
+                    get(mongodbapi::get_admin_overrides)
+                        .put(mongodbapi::put_admin_overrides)
+                        .delete(mongodbapi::delete_admin_overrides),
+                ),
+        ))
+        .with_state(clientdb.clone());
+    with_layers(router, fc_settings, move || {
+        let pool = clientdb.clone();
+        async move { mongo::select_latest_time_from_posts(&pool).await.ok() }
+    })
 }
 
 #[tokio::main]
@@ -76,7 +278,7 @@ async fn main() {
     let _guard = init_tracing("api", None);
 
     let app = match fc_settings.database.as_str() {
-        "sqlite" => create_sqlite_app("data.db").await,
+        "sqlite" => create_sqlite_app("data.db", &fc_settings).await,
         "mysql" => {
             // get mysql conn pool
             let mysqlconnstr = match tools::get_env_var("MYSQL_URI") {
@@ -86,7 +288,7 @@ async fn main() {
                     return;
                 }
             };
-            create_mysql_app(&mysqlconnstr).await
+            create_mysql_app(&mysqlconnstr, &fc_settings).await
         }
         "mongodb" => {
             let mongodburi = match tools::get_env_var("MONGODB_URI") {
@@ -96,12 +298,119 @@ async fn main() {
                     return;
                 }
             };
-            create_mongodb_app(&mongodburi).await
+            create_mongodb_app(&mongodburi, &fc_settings).await
         }
         _ => return,
     };
 
-    // run our app with hyper, listening globally on port 3000
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:8000").await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    let api = &fc_settings.api;
+    #[cfg(unix)]
+    if !api.unix_socket.is_empty() {
+        // 上次运行留下的socket文件会导致绑定失败
+        let _ = std::fs::remove_file(&api.unix_socket);
+        let listener = match tokio::net::UnixListener::bind(&api.unix_socket) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("监听 {} 失败: {}", api.unix_socket, e);
+                return;
+            }
+        };
+        info!("api监听 {}", api.unix_socket);
+        axum::serve(listener, app).await.unwrap();
+        return;
+    }
+    let listener = match tokio::net::TcpListener::bind(api.listen_addr()).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("监听 {} 失败: {}", api.listen_addr(), e);
+            return;
+        }
+    };
+    info!("api监听 {}", api.listen_addr());
+    // 频率限制需要客户端地址
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::header::{ACCESS_CONTROL_ALLOW_ORIGIN, ORIGIN};
+    use axum_test::TestServer;
+    use data_structures::testing::{settings, temp_dir};
+
+    async fn allowed_origin(fc_settings: &Settings, origin: &str) -> Option<HeaderValue> {
+        let app = Router::new()
+            .route("/healthz", get(health::get_healthz))
+            .layer(cors_layer(fc_settings));
+        let server = TestServer::new(app).unwrap();
+        server
+            .get("/healthz")
+            .add_header(ORIGIN, HeaderValue::from_str(origin).unwrap())
+            .await
+            .maybe_header(ACCESS_CONTROL_ALLOW_ORIGIN)
+    }
+
+    // 测试跨域来源白名单和`*`
+    #[tokio::test]
+    async fn test_cors_layer() {
+        let mut fc_settings = settings();
+        fc_settings.api.allowed_origins = vec![String::from("https://blog.example.com/")];
+        assert_eq!(
+            allowed_origin(&fc_settings, "https://blog.example.com").await,
+            Some(HeaderValue::from_static("https://blog.example.com"))
+        );
+        assert_eq!(
+            allowed_origin(&fc_settings, "https://evil.example.com").await,
+            None
+        );
+
+        fc_settings.api.allowed_origins.push(String::from("*"));
+        assert_eq!(
+            allowed_origin(&fc_settings, "https://evil.example.com").await,
+            Some(HeaderValue::from_static("*"))
+        );
+    }
+
+    // 测试设置路径前缀后只有带前缀的路由可以访问
+    #[tokio::test]
+    async fn test_path_prefix() {
+        let db_path = std::env::temp_dir().join(format!(
+            "fcircle_test_api_path_prefix_{}.db",
+            std::process::id()
+        ));
+        let mut fc_settings = settings();
+        fc_settings.api.path_prefix = String::from("fcircle/");
+        let app = create_sqlite_app(db_path.to_str().unwrap(), &fc_settings).await;
+        let server = TestServer::new(app).unwrap();
+
+        let response = server.get("/fcircle/healthz").await;
+        response.assert_status_ok();
+        response.assert_text("ok");
+        server.get("/healthz").await.assert_status_not_found();
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    // 测试`/all`的排序规则不在白名单中时返回400
+    #[tokio::test]
+    async fn test_all_sort_rule() {
+        let db_path = temp_dir("api_all_sort_rule").join("data.db");
+        let app = create_sqlite_app(db_path.to_str().unwrap(), &settings()).await;
+        let server = TestServer::new(app).unwrap();
+
+        server
+            .get("/all")
+            .add_query_param("rule", "created")
+            .await
+            .assert_status_ok();
+        server
+            .get("/all")
+            .add_query_param("rule", "updated DESC, (SELECT 1)")
+            .await
+            .assert_status_bad_request();
+    }
 }
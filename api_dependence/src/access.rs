@@ -0,0 +1,166 @@
+use crate::format_response::ErrorResponse;
+use axum::{
+    Json,
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use data_structures::config::{RateLimitSettings, Settings};
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::warn;
+
+/// 不受频率限制的路由，供容器探针使用
+const EXEMPT_ROUTES: [&str; 2] = ["/healthz", "/readyz"];
+
+/// 记录的IP数超过该值时，清理已经恢复满额的记录
+const MAX_TRACKED_IPS: usize = 10_000;
+
+/// 令牌桶
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// 频率限制和API密钥检查
+#[derive(Clone)]
+pub struct AccessControl {
+    rate_limit: RateLimitSettings,
+    keys: Arc<HashSet<String>>,
+    protected_routes: Arc<Vec<String>>,
+    buckets: Arc<Mutex<HashMap<IpAddr, Bucket>>>,
+}
+
+impl From<&Settings> for AccessControl {
+    fn from(settings: &Settings) -> Self {
+        let keys = settings
+            .api_keys
+            .keys
+            .iter()
+            .filter_map(|key| match tools::resolve_secret(key) {
+                Ok(key) if !key.is_empty() => Some(key),
+                Ok(_) => None,
+                Err(e) => {
+                    warn!("读取API密钥失败，已忽略: {}", e);
+                    None
+                }
+            })
+            .collect();
+        AccessControl {
+            rate_limit: settings.rate_limit.clone(),
+            keys: Arc::new(keys),
+            protected_routes: Arc::new(settings.api_keys.routes.clone()),
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl AccessControl {
+    /// 是否需要加载该中间件
+    pub fn is_enabled(&self) -> bool {
+        self.rate_limit.enable || !self.protected_routes.is_empty()
+    }
+
+    fn has_valid_key(&self, headers: &HeaderMap) -> bool {
+        let key = headers
+            .get("x-api-key")
+            .and_then(|v| v.to_str().ok())
+            .or_else(|| {
+                headers
+                    .get(header::AUTHORIZATION)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.strip_prefix("Bearer "))
+            });
+        key.is_some_and(|key| self.keys.contains(key.trim()))
+    }
+
+    fn client_ip(&self, req: &Request) -> IpAddr {
+        if self.rate_limit.trust_forwarded {
+            let forwarded = req
+                .headers()
+                .get("x-forwarded-for")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.split(',').next())
+                .or_else(|| req.headers().get("x-real-ip").and_then(|v| v.to_str().ok()))
+                .and_then(|v| v.trim().parse().ok());
+            if let Some(ip) = forwarded {
+                return ip;
+            }
+        }
+        // 通过unix socket连接时没有客户端地址，所有请求共用一个令牌桶
+        req.extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|info| info.0.ip())
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+    }
+
+    /// 取出一个令牌，令牌不足时返回需要等待的秒数
+    fn acquire(&self, ip: IpAddr) -> Result<(), u64> {
+        let rate = f64::from(self.rate_limit.per_minute.max(1)) / 60.0;
+        let capacity = f64::from(self.rate_limit.burst.max(1));
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > MAX_TRACKED_IPS {
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate < capacity
+            });
+        }
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        bucket.tokens =
+            (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate).min(capacity);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - bucket.tokens) / rate).ceil() as u64)
+        }
+    }
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (status, Json(ErrorResponse::new(message))).into_response()
+}
+
+/// 检查API密钥和请求频率，路由按注册时的路径模板匹配
+pub async fn access_control(
+    State(access): State<AccessControl>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_default();
+    let valid_key = access.has_valid_key(req.headers());
+
+    if !valid_key
+        && access
+            .protected_routes
+            .iter()
+            .any(|protected| route.ends_with(protected.as_str()))
+    {
+        return error_response(StatusCode::UNAUTHORIZED, "缺少有效的API密钥");
+    }
+
+    if access.rate_limit.enable
+        && !valid_key
+        && !EXEMPT_ROUTES.iter().any(|exempt| route.ends_with(exempt))
+        && let Err(retry_after) = access.acquire(access.client_ip(&req))
+    {
+        let mut response =
+            error_response(StatusCode::TOO_MANY_REQUESTS, "请求过于频繁，请稍后再试");
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        return response;
+    }
+    next.run(req).await
+}
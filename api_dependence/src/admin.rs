@@ -0,0 +1,183 @@
+use crate::format_response::{ErrorResponse, PYQError};
+use axum::{
+    Extension, Json,
+    extract::{Request, State},
+    http::{StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{FixedOffset, Utc};
+use data_structures::{
+    config::Settings,
+    metadata::{BlockedSite, FriendOverride, ManualFriend},
+    response::CrawlData,
+};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use subtle::ConstantTimeEq;
+use tokio::process::Command;
+use tracing::{error, info, warn};
+use url::Url;
+
+/// 管理接口的令牌和core的运行状态
+#[derive(Clone)]
+pub struct AdminState {
+    token: Arc<String>,
+    core_command: Arc<String>,
+    /// 通过管理接口触发的core是否仍在运行
+    running: Arc<AtomicBool>,
+}
+
+impl AdminState {
+    /// 令牌为空或读取失败时返回None，不启用管理接口
+    pub fn from_settings(settings: &Settings) -> Option<AdminState> {
+        let token = match tools::resolve_secret(&settings.admin.token) {
+            Ok(token) => token,
+            Err(e) => {
+                warn!("读取管理令牌失败，不启用管理接口: {}", e);
+                return None;
+            }
+        };
+        if token.is_empty() {
+            return None;
+        }
+        Some(AdminState {
+            token: Arc::new(token),
+            core_command: Arc::new(settings.admin.core_command.clone()),
+            running: Arc::new(AtomicBool::new(false)),
+        })
+    }
+}
+
+/// 检查`Authorization: Bearer`请求头中的管理令牌
+pub async fn require_admin_token(
+    State(admin): State<AdminState>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let authorized = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        // 按常数时间比较，避免通过响应时间猜测令牌
+        .is_some_and(|token| bool::from(token.trim().as_bytes().ct_eq(admin.token.as_bytes())));
+    if !authorized {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(ErrorResponse::new("缺少有效的管理令牌")),
+        )
+            .into_response();
+    }
+    next.run(req).await
+}
+
+fn now_string() -> String {
+    let offset = FixedOffset::east_opt(8 * 60 * 60).unwrap();
+    tools::strptime_to_string_ymdhms(Utc::now().with_timezone(&offset))
+}
+
+/// 检查并整理请求中的友链，设置添加时间
+pub fn validate_manual_friend(mut friend: ManualFriend) -> Result<ManualFriend, PYQError> {
+    friend.name = friend.name.trim().to_string();
+    friend.link = friend.link.trim().to_string();
+    friend.avatar = friend.avatar.trim().to_string();
+    friend.suffix = friend.suffix.trim().to_string();
+    if friend.name.is_empty() {
+        return Err(PYQError::QueryParamsError(String::from(
+            "缺少友链名称`name`",
+        )));
+    }
+    match Url::parse(&friend.link) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => (),
+        _ => {
+            return Err(PYQError::QueryParamsError(format!(
+                "友链地址无效: {}",
+                friend.link
+            )));
+        }
+    }
+    friend.created_at = now_string();
+    Ok(friend)
+}
+
+/// 检查请求中的友链覆盖设置，设置添加时间
+pub fn validate_friend_override(
+    mut friend_override: FriendOverride,
+) -> Result<FriendOverride, PYQError> {
+    friend_override.link = friend_override.link.trim().to_string();
+    friend_override.name = friend_override.name.trim().to_string();
+    friend_override.feed = friend_override.feed.trim().to_string();
+    friend_override.theme = friend_override.theme.trim().to_string();
+    if Url::parse(&friend_override.link).is_err() {
+        return Err(PYQError::QueryParamsError(format!(
+            "友链地址无效: {}",
+            friend_override.link
+        )));
+    }
+    if friend_override.max_posts < 0 {
+        return Err(PYQError::QueryParamsError(String::from(
+            "`max_posts`不能为负数",
+        )));
+    }
+    friend_override.created_at = now_string();
+    Ok(friend_override)
+}
+
+/// 检查请求中的屏蔽站点，设置添加时间
+pub fn validate_blocked_site(mut site: BlockedSite) -> Result<BlockedSite, PYQError> {
+    site.pattern = site.pattern.trim().to_string();
+    if site.pattern.is_empty() {
+        return Err(PYQError::QueryParamsError(String::from(
+            "缺少屏蔽规则`pattern`",
+        )));
+    }
+    if let Err(e) = regex::Regex::new(&tools::block_site_regex(&site.pattern)) {
+        return Err(PYQError::QueryParamsError(format!("屏蔽规则无效: {e}")));
+    }
+    site.created_at = now_string();
+    Ok(site)
+}
+
+/// 立即运行一次core，上一次触发的运行尚未结束时返回409
+///
+/// 后台等待进程结束并记录退出状态，避免留下僵尸进程
+pub async fn post_crawl(Extension(admin): Extension<AdminState>) -> Response {
+    if admin.running.swap(true, Ordering::SeqCst) {
+        return (
+            StatusCode::CONFLICT,
+            Json(ErrorResponse::new("上一次运行尚未结束")),
+        )
+            .into_response();
+    }
+    match Command::new(admin.core_command.as_str())
+        .kill_on_drop(false)
+        .spawn()
+    {
+        Ok(mut child) => {
+            let data = CrawlData {
+                pid: child.id(),
+                started_at: now_string(),
+            };
+            info!("通过管理接口开始运行core, pid: {:?}", data.pid);
+            let running = admin.running.clone();
+            tokio::spawn(async move {
+                match child.wait().await {
+                    Ok(status) => info!("通过管理接口触发的运行已结束: {}", status),
+                    Err(e) => error!("无法获取core的运行状态: {}", e),
+                }
+                running.store(false, Ordering::SeqCst);
+            });
+            (StatusCode::ACCEPTED, Json(data)).into_response()
+        }
+        Err(e) => {
+            admin.running.store(false, Ordering::SeqCst);
+            error!("运行{}失败: {}", admin.core_command, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse::new(&format!("运行core失败: {e}"))),
+            )
+                .into_response()
+        }
+    }
+}
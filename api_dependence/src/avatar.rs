@@ -0,0 +1,87 @@
+use axum::{
+    extract::{Extension, Path},
+    http::{StatusCode, header},
+    response::{IntoResponse, Redirect, Response},
+};
+use data_structures::config::{AvatarCacheSettings, Settings};
+use std::path::Path as FsPath;
+
+/// 镜像头像的文件名为内容哈希，内容不会变化，可以长期缓存
+const AVATAR_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+/// 默认头像可能随配置变化，只缓存一小时
+const DEFAULT_AVATAR_CACHE_CONTROL: &str = "public, max-age=3600";
+
+#[derive(Debug, Clone)]
+pub struct AvatarConfig {
+    pub cache: AvatarCacheSettings,
+    pub default_avatar: String,
+}
+
+impl From<&Settings> for AvatarConfig {
+    fn from(settings: &Settings) -> Self {
+        AvatarConfig {
+            cache: settings.avatar_cache.clone(),
+            default_avatar: settings.default_avatar.clone(),
+        }
+    }
+}
+
+fn content_type_of(path: &FsPath) -> &'static str {
+    let ext = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    match ext.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        _ => "image/png",
+    }
+}
+
+async fn default_avatar(config: &AvatarConfig) -> Response {
+    let default_image = FsPath::new(&config.cache.default_image);
+    if !config.cache.default_image.is_empty()
+        && let Ok(bytes) = tokio::fs::read(default_image).await
+    {
+        return (
+            [
+                (header::CONTENT_TYPE, content_type_of(default_image)),
+                (header::CACHE_CONTROL, DEFAULT_AVATAR_CACHE_CONTROL),
+            ],
+            bytes,
+        )
+            .into_response();
+    }
+    (
+        [(header::CACHE_CONTROL, DEFAULT_AVATAR_CACHE_CONTROL)],
+        Redirect::temporary(&config.default_avatar),
+    )
+        .into_response()
+}
+
+pub async fn get_avatar(
+    Extension(config): Extension<AvatarConfig>,
+    Path(hash): Path<String>,
+) -> Response {
+    let path = match config.cache.path_of(&hash) {
+        Some(path) => path,
+        None => return default_avatar(&config).await,
+    };
+    match tokio::fs::read(&path).await {
+        Ok(bytes) => (
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, String::from("image/png")),
+                (header::CACHE_CONTROL, String::from(AVATAR_CACHE_CONTROL)),
+                (header::ETAG, format!("\"{hash}\"")),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(_) => default_avatar(&config).await,
+    }
+}
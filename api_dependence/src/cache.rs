@@ -0,0 +1,325 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::{MatchedPath, Request, State},
+    http::{HeaderMap, HeaderValue, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc};
+use data_structures::config::HttpCacheSettings;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// 可以缓存的路由，随机接口和头像、指标等不缓存
+const CACHEABLE_ROUTES: [&str; 7] = [
+    "/all",
+    "/friend",
+    "/post",
+    "/search",
+    "/tags",
+    "/tag/{name}",
+    "/new",
+];
+
+/// 可缓存路由的响应大小上限
+const MAX_BODY_BYTES: usize = 64 * 1024 * 1024;
+
+type FetchVersion =
+    Arc<dyn Fn() -> Pin<Box<dyn Future<Output = Option<String>> + Send>> + Send + Sync>;
+
+#[derive(Clone)]
+struct CachedResponse {
+    content_type: Option<HeaderValue>,
+    body: Bytes,
+}
+
+#[derive(Default)]
+struct CacheInner {
+    /// 最近一次运行的时间
+    version: Option<String>,
+    checked_at: Option<Instant>,
+    /// 请求路径和参数 -> 响应
+    entries: HashMap<String, CachedResponse>,
+}
+
+/// 内存中的响应缓存，最近一次运行的时间变化时清空
+#[derive(Clone)]
+pub struct ResponseCache {
+    settings: HttpCacheSettings,
+    fetch_version: FetchVersion,
+    inner: Arc<Mutex<CacheInner>>,
+}
+
+impl ResponseCache {
+    /// `fetch_version`查询最近一次运行的时间，即`select_latest_time_from_posts`
+    pub fn new<F, Fut>(settings: &HttpCacheSettings, fetch_version: F) -> ResponseCache
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Option<String>> + Send + 'static,
+    {
+        ResponseCache {
+            settings: settings.clone(),
+            fetch_version: Arc::new(move || Box::pin(fetch_version())),
+            inner: Arc::new(Mutex::new(CacheInner::default())),
+        }
+    }
+
+    /// 返回当前版本，超过`check_interval`时重新查询，版本变化时清空缓存
+    async fn version(&self) -> Option<String> {
+        {
+            let inner = self.inner.lock().unwrap();
+            if let Some(checked_at) = inner.checked_at
+                && checked_at.elapsed() < Duration::from_secs(self.settings.check_interval)
+            {
+                return inner.version.clone();
+            }
+        }
+        let version = (self.fetch_version)().await;
+        let mut inner = self.inner.lock().unwrap();
+        if inner.version != version {
+            inner.entries.clear();
+            inner.version = version.clone();
+        }
+        inner.checked_at = Some(Instant::now());
+        version
+    }
+
+    fn get(&self, key: &str, version: &str) -> Option<CachedResponse> {
+        let inner = self.inner.lock().unwrap();
+        if inner.version.as_deref() != Some(version) {
+            return None;
+        }
+        inner.entries.get(key).cloned()
+    }
+
+    fn insert(&self, key: String, version: &str, response: CachedResponse) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.version.as_deref() != Some(version) {
+            return;
+        }
+        if inner.entries.len() >= self.settings.max_entries {
+            inner.entries.clear();
+        }
+        inner.entries.insert(key, response);
+    }
+}
+
+/// 将北京时间`%Y-%m-%d %H:%M:%S`转换为UTC，无法解析时返回None
+fn parse_version_time(version: &str) -> Option<DateTime<Utc>> {
+    let naive = NaiveDateTime::parse_from_str(version, "%Y-%m-%d %H:%M:%S").ok()?;
+    let offset = FixedOffset::east_opt(8 * 60 * 60)?;
+    Some(
+        naive
+            .and_local_timezone(offset)
+            .single()?
+            .with_timezone(&Utc),
+    )
+}
+
+/// 版本只包含数字，作为ETag
+fn etag_of(version: &str) -> String {
+    let digits: String = version.chars().filter(|c| c.is_ascii_digit()).collect();
+    format!("\"{digits}\"")
+}
+
+/// 根据`If-None-Match`和`If-Modified-Since`判断客户端的缓存是否仍然有效
+fn is_not_modified(headers: &HeaderMap, etag: &str, last_modified: Option<DateTime<Utc>>) -> bool {
+    if let Some(if_none_match) = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+    {
+        return if_none_match
+            .split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == etag || tag == "*");
+    }
+    match (
+        headers
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| DateTime::parse_from_rfc2822(v).ok()),
+        last_modified,
+    ) {
+        (Some(since), Some(last_modified)) => last_modified.timestamp() <= since.timestamp(),
+        _ => false,
+    }
+}
+
+fn with_cache_headers(
+    mut response: Response,
+    settings: &HttpCacheSettings,
+    etag: &str,
+    last_modified: Option<DateTime<Utc>>,
+) -> Response {
+    let headers = response.headers_mut();
+    if let Ok(v) = HeaderValue::from_str(&format!("public, max-age={}", settings.max_age)) {
+        headers.insert(header::CACHE_CONTROL, v);
+    }
+    if let Ok(v) = HeaderValue::from_str(etag) {
+        headers.insert(header::ETAG, v);
+    }
+    if let Some(last_modified) = last_modified
+        && let Ok(v) = HeaderValue::from_str(
+            &last_modified
+                .format("%a, %d %b %Y %H:%M:%S GMT")
+                .to_string(),
+        )
+    {
+        headers.insert(header::LAST_MODIFIED, v);
+    }
+    response
+}
+
+/// 为可缓存的路由添加缓存头，处理条件请求，并在内存中缓存响应
+pub async fn cache_responses(
+    State(cache): State<ResponseCache>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let cacheable = req.method() == Method::GET
+        && req.extensions().get::<MatchedPath>().is_some_and(|path| {
+            CACHEABLE_ROUTES
+                .iter()
+                .any(|route| path.as_str().ends_with(route))
+        });
+    if !cacheable {
+        return next.run(req).await;
+    }
+    let Some(version) = cache.version().await else {
+        return next.run(req).await;
+    };
+    let etag = etag_of(&version);
+    let last_modified = parse_version_time(&version);
+    if is_not_modified(req.headers(), &etag, last_modified) {
+        return with_cache_headers(
+            StatusCode::NOT_MODIFIED.into_response(),
+            &cache.settings,
+            &etag,
+            last_modified,
+        );
+    }
+
+    let key = req.uri().to_string();
+    if let Some(cached) = cache.get(&key, &version) {
+        let mut response = Response::new(Body::from(cached.body));
+        if let Some(content_type) = cached.content_type {
+            response
+                .headers_mut()
+                .insert(header::CONTENT_TYPE, content_type);
+        }
+        return with_cache_headers(response, &cache.settings, &etag, last_modified);
+    }
+
+    let mut response = next.run(req).await;
+    if response.status() != StatusCode::OK {
+        // 错误响应不应被浏览器或CDN缓存
+        response
+            .headers_mut()
+            .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let body = match axum::body::to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("读取响应失败: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    cache.insert(
+        key,
+        &version,
+        CachedResponse {
+            content_type: parts.headers.get(header::CONTENT_TYPE).cloned(),
+            body: body.clone(),
+        },
+    );
+    with_cache_headers(
+        Response::from_parts(parts, Body::from(body)),
+        &cache.settings,
+        &etag,
+        last_modified,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, extract::Query, middleware, routing::get};
+    use axum_test::TestServer;
+
+    /// `/all`返回当前数据，带`fail`参数时返回400；返回的版本可以在测试中修改
+    fn test_server(version: Arc<Mutex<String>>, data: Arc<Mutex<String>>) -> TestServer {
+        let settings = HttpCacheSettings {
+            enable: true,
+            check_interval: 0,
+            ..Default::default()
+        };
+        let cache = ResponseCache::new(&settings, move || {
+            let version = version.lock().unwrap().clone();
+            async move { Some(version) }
+        });
+        let app = Router::new()
+            .route(
+                "/all",
+                get(
+                    move |Query(params): Query<HashMap<String, String>>| async move {
+                        if params.contains_key("fail") {
+                            return (StatusCode::BAD_REQUEST, String::from("error"));
+                        }
+                        (StatusCode::OK, data.lock().unwrap().clone())
+                    },
+                ),
+            )
+            .layer(middleware::from_fn_with_state(cache, cache_responses));
+        TestServer::new(app).unwrap()
+    }
+
+    // 测试ETag、304、运行后缓存失效，以及错误响应的缓存头
+    #[tokio::test]
+    async fn test_cache_responses() {
+        let version = Arc::new(Mutex::new(String::from("2024-06-01 08:00:00")));
+        let data = Arc::new(Mutex::new(String::from("v1")));
+        let server = test_server(version.clone(), data.clone());
+
+        let response = server.get("/all").await;
+        response.assert_status_ok();
+        response.assert_text("v1");
+        let etag = response.header(header::ETAG);
+        assert_eq!(etag, "\"20240601080000\"");
+        assert_eq!(response.header(header::CACHE_CONTROL), "public, max-age=60");
+        assert_eq!(
+            response.header(header::LAST_MODIFIED),
+            "Sat, 01 Jun 2024 00:00:00 GMT"
+        );
+
+        let response = server
+            .get("/all")
+            .add_header(header::IF_NONE_MATCH, etag.clone())
+            .await;
+        response.assert_status(StatusCode::NOT_MODIFIED);
+
+        // 版本不变时返回缓存的响应
+        *data.lock().unwrap() = String::from("v2");
+        server.get("/all").await.assert_text("v1");
+
+        // 再次运行后版本变化，旧的ETag失效，缓存的响应也被清空
+        *version.lock().unwrap() = String::from("2024-06-02 08:00:00");
+        let response = server
+            .get("/all")
+            .add_header(header::IF_NONE_MATCH, etag)
+            .await;
+        response.assert_status_ok();
+        response.assert_text("v2");
+        assert_eq!(response.header(header::ETAG), "\"20240602080000\"");
+
+        let response = server.get("/all").add_query_param("fail", "1").await;
+        response.assert_status(StatusCode::BAD_REQUEST);
+        assert_eq!(response.header(header::CACHE_CONTROL), "no-store");
+        assert!(response.maybe_header(header::ETAG).is_none());
+    }
+}
@@ -1,5 +1,5 @@
 use axum::{
-    http::header,
+    http::{StatusCode, header},
     response::{IntoResponse, Response},
 };
 use serde::{Deserialize, Serialize};
@@ -24,6 +24,7 @@ impl ErrorResponse {
 
 impl IntoResponse for PYQError {
     fn into_response(self) -> Response {
+        let is_params_error = matches!(self, PYQError::QueryParamsError(_));
         let body = match self {
             PYQError::QueryDataBaseError(e) => {
                 serde_json::to_string(&ErrorResponse::new(&format!(
@@ -44,6 +45,11 @@ impl IntoResponse for PYQError {
                 .unwrap()
             }
         };
-        ([(header::CONTENT_TYPE, "application/json")], body).into_response()
+        let mut response = ([(header::CONTENT_TYPE, "application/json")], body).into_response();
+        // 请求参数错误返回400，其余错误沿用原有的200
+        if is_params_error {
+            *response.status_mut() = StatusCode::BAD_REQUEST;
+        }
+        response
     }
 }
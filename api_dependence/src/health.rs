@@ -0,0 +1,38 @@
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use data_structures::{config::Readiness, response::ReadyData};
+
+/// 存活检查，进程可以响应请求即可
+pub async fn get_healthz() -> &'static str {
+    "ok"
+}
+
+/// 检查最近一次运行的时间，没有数据或数据过旧时记录到`errors`
+pub fn check_data_age(
+    readiness: &Readiness,
+    last_updated_time: Option<&str>,
+    errors: &mut Vec<String>,
+) {
+    let Some(before) = readiness.stale_before() else {
+        return;
+    };
+    match last_updated_time {
+        Some(time) if time >= before.as_str() => (),
+        Some(time) => errors.push(format!("数据过旧，最近一次运行时间为{time}")),
+        None => errors.push(String::from("数据库中没有数据")),
+    }
+}
+
+/// 所有检查都通过时返回200，否则返回503
+pub fn ready_response(last_updated_time: Option<String>, errors: Vec<String>) -> Response {
+    let data = ReadyData::new(last_updated_time, errors);
+    let status = if data.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(data)).into_response()
+}
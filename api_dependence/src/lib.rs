@@ -1,4 +1,10 @@
+pub mod access;
+pub mod admin;
+pub mod avatar;
+pub mod cache;
 pub mod format_response;
+pub mod health;
+pub mod metrics;
 pub mod mongodb;
 pub mod mysql;
 pub mod sqlite;
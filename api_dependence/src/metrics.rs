@@ -0,0 +1,98 @@
+use axum::{
+    extract::{Extension, MatchedPath, Request},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use data_structures::config::Settings;
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::Instant;
+
+/// 请求和查询耗时的分桶（秒）
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+static PROMETHEUS_HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+/// 安装全局的Prometheus recorder，多次调用返回同一个handle
+pub fn prometheus_handle() -> PrometheusHandle {
+    PROMETHEUS_HANDLE
+        .get_or_init(|| {
+            PrometheusBuilder::new()
+                .set_buckets_for_metric(Matcher::Suffix(String::from("_seconds")), &LATENCY_BUCKETS)
+                .unwrap()
+                .install_recorder()
+                .unwrap()
+        })
+        .clone()
+}
+
+#[derive(Debug, Clone)]
+pub struct MetricsConfig {
+    pub handle: PrometheusHandle,
+    /// core写入的抓取指标文件
+    pub crawl_record: String,
+}
+
+impl From<&Settings> for MetricsConfig {
+    fn from(settings: &Settings) -> Self {
+        MetricsConfig {
+            handle: prometheus_handle(),
+            crawl_record: settings.metrics.crawl_record.clone(),
+        }
+    }
+}
+
+/// 记录每个路由的请求数和耗时，路由为注册时的路径模板，避免标签值过多
+pub async fn track_metrics(req: Request, next: Next) -> Response {
+    let route = match req.extensions().get::<MatchedPath>() {
+        Some(path) => path.as_str().to_string(),
+        None => String::from("unmatched"),
+    };
+    let method = req.method().to_string();
+    let start = Instant::now();
+    let response = next.run(req).await;
+    let status = response.status().as_u16().to_string();
+
+    metrics::counter!(
+        "fcircle_http_requests_total",
+        "method" => method.clone(),
+        "route" => route.clone(),
+        "status" => status,
+    )
+    .increment(1);
+    metrics::histogram!(
+        "fcircle_http_request_duration_seconds",
+        "method" => method,
+        "route" => route,
+    )
+    .record(start.elapsed().as_secs_f64());
+    response
+}
+
+/// 记录一次数据库查询的耗时，`query`为db中的函数名
+pub async fn timed_query<F: Future>(query: &'static str, fut: F) -> F::Output {
+    let start = Instant::now();
+    let output = fut.await;
+    metrics::histogram!("fcircle_db_query_duration_seconds", "query" => query)
+        .record(start.elapsed().as_secs_f64());
+    output
+}
+
+/// 返回api的指标，以及core最近一次运行写入的抓取指标
+pub async fn get_metrics(Extension(config): Extension<MetricsConfig>) -> impl IntoResponse {
+    let mut body = config.handle.render();
+    if let Ok(crawl_record) = tokio::fs::read_to_string(&config.crawl_record).await {
+        body.push_str(&crawl_record);
+    }
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        body,
+    )
+}
@@ -1,12 +1,23 @@
+use crate::admin;
 use crate::format_response::PYQError;
+use crate::health;
+use crate::metrics::timed_query;
 use axum::{
-    Json,
-    extract::{Query, State},
+    Extension, Json,
+    extract::{Path, Query, State},
+    response::Response,
+};
+use data_structures::query_params::{
+    AdminBlockParams, AdminFriendParams, AllQueryParams, NewQueryParams, PostParams,
+    RandomQueryParams, SearchQueryParams, TagParams,
 };
-use data_structures::query_params::{AllQueryParams, PostParams, RandomQueryParams};
 use data_structures::{
-    metadata::{Friends, Posts},
-    response::{AllPostData, AllPostDataSomeFriend},
+    config::{FriendActivity, Readiness, TagAliases},
+    metadata::{BlockedSite, FriendOverride, Friends, ManualFriend, Posts},
+    response::{
+        AllPostData, AllPostDataSomeFriend, AllPostDataSomeTag, NewPostData, Pagination,
+        SearchData, TagData,
+    },
 };
 use db::{MySqlPool, mysql};
 use rand::prelude::*;
@@ -15,13 +26,41 @@ use url::Url;
 pub async fn get_all(
     State(pool): State<MySqlPool>,
     Query(params): Query<AllQueryParams>,
+    Extension(activity): Extension<FriendActivity>,
 ) -> Result<Json<AllPostData>, PYQError> {
     // println!("{:?}",params);
-    let posts = match mysql::select_all_from_posts(
-        &pool,
-        params.start.unwrap_or(0),
-        params.end.unwrap_or(0),
-        &params.sort_rule.unwrap_or(String::from("updated")),
+    let (offset, limit) = match params.pagination() {
+        Ok(v) => v,
+        Err(e) => return Err(PYQError::QueryParamsError(e)),
+    };
+    let sort_rule = match params.checked_sort_rule() {
+        Ok(v) => v,
+        Err(e) => return Err(PYQError::QueryParamsError(e)),
+    };
+    let end = match limit {
+        0 => 0,
+        _ => match offset.checked_add(limit) {
+            Some(end) => end,
+            None => {
+                return Err(PYQError::QueryParamsError(format!(
+                    "offset({offset})超出范围"
+                )));
+            }
+        },
+    };
+    let filter = params.filter();
+    let posts = match timed_query(
+        "select_all_from_posts_with_filter",
+        mysql::select_all_from_posts_with_filter(&pool, &filter, offset, end, sort_rule),
+    )
+    .await
+    {
+        Ok(v) => v,
+        Err(e) => return Err(PYQError::QueryDataBaseError(e.to_string())),
+    };
+    let article_num = match timed_query(
+        "select_count_from_posts_with_filter",
+        mysql::select_count_from_posts_with_filter(&pool, &filter),
     )
     .await
     {
@@ -29,39 +68,59 @@ pub async fn get_all(
         Err(e) => return Err(PYQError::QueryDataBaseError(e.to_string())),
     };
 
-    let last_updated_time = match mysql::select_latest_time_from_posts(&pool).await {
+    let last_updated_time = match timed_query(
+        "select_latest_time_from_posts",
+        mysql::select_latest_time_from_posts(&pool),
+    )
+    .await
+    {
         Ok(v) => v,
         Err(_e) => "1970-01-01 00:00:00".to_string(),
     };
 
-    let friends = match mysql::select_all_from_friends(&pool).await {
+    let (friends_num, lost_num) = match timed_query(
+        "select_count_from_friends",
+        mysql::select_count_from_friends(&pool),
+    )
+    .await
+    {
         Ok(v) => v,
         Err(e) => return Err(PYQError::QueryDataBaseError(e.to_string())),
     };
-    let friends_num = friends.len();
-    let mut active_num = 0;
-    let mut lost_num = 0;
-    for friend in friends {
-        if friend.error {
-            lost_num += 1;
-        } else {
-            active_num += 1;
-        }
-    }
+    let dormant_num = match activity.dormant_before() {
+        Some(before) => match timed_query(
+            "select_dormant_count_from_friends",
+            mysql::select_dormant_count_from_friends(&pool, &before),
+        )
+        .await
+        {
+            Ok(v) => v,
+            Err(e) => return Err(PYQError::QueryDataBaseError(e.to_string())),
+        },
+        None => 0,
+    };
     let data = AllPostData::new(
         friends_num,
-        active_num,
+        // 三次查询之间core可能重写了友链表
+        friends_num
+            .saturating_sub(lost_num)
+            .saturating_sub(dormant_num),
+        dormant_num,
         lost_num,
-        posts.len(),
         last_updated_time,
         posts,
-        params.start.unwrap_or(0),
+        Pagination::new(article_num, offset, limit),
     );
     Ok(Json(data))
 }
 
 pub async fn get_friend(State(pool): State<MySqlPool>) -> Result<Json<Vec<Friends>>, PYQError> {
-    let friends = match mysql::select_all_from_friends(&pool).await {
+    let friends = match timed_query(
+        "select_all_from_friends",
+        mysql::select_all_from_friends(&pool),
+    )
+    .await
+    {
         Ok(v) => v,
         Err(e) => return Err(PYQError::QueryDataBaseError(e.to_string())),
     };
@@ -84,14 +143,24 @@ pub async fn get_post(
             };
             // println!("{}", domain_str);
 
-            match mysql::select_one_from_friends_with_linklike(&pool, &domain_str).await {
+            match timed_query(
+                "select_one_from_friends_with_linklike",
+                mysql::select_one_from_friends_with_linklike(&pool, &domain_str),
+            )
+            .await
+            {
                 Ok(v) => v,
                 Err(e) => return Err(PYQError::QueryDataBaseError(e.to_string())),
             }
         }
         None => {
             // 没有提供link，则随机获取一个friend
-            let friends = match mysql::select_all_from_friends(&pool).await {
+            let friends = match timed_query(
+                "select_all_from_friends",
+                mysql::select_all_from_friends(&pool),
+            )
+            .await
+            {
                 Ok(v) => v,
                 Err(e) => return Err(PYQError::QueryDataBaseError(e.to_string())),
             };
@@ -106,11 +175,14 @@ pub async fn get_post(
             }
         }
     };
-    let posts = match mysql::select_all_from_posts_with_linklike(
-        &pool,
-        &friend.link,
-        params.num.unwrap_or(-1),
-        &params.sort_rule.unwrap_or(String::from("created")),
+    let posts = match timed_query(
+        "select_all_from_posts_with_linklike",
+        mysql::select_all_from_posts_with_linklike(
+            &pool,
+            &friend.link,
+            params.num.unwrap_or(-1),
+            &params.sort_rule.unwrap_or(String::from("created")),
+        ),
     )
     .await
     {
@@ -132,7 +204,12 @@ pub async fn get_randomfriend(
     State(pool): State<MySqlPool>,
     Query(params): Query<RandomQueryParams>,
 ) -> Result<Json<Vec<Friends>>, PYQError> {
-    let friends = match mysql::select_all_from_friends(&pool).await {
+    let friends = match timed_query(
+        "select_all_from_friends",
+        mysql::select_all_from_friends(&pool),
+    )
+    .await
+    {
         Ok(v) => v,
         Err(e) => return Err(PYQError::QueryDataBaseError(e.to_string())),
     };
@@ -149,14 +226,267 @@ pub async fn get_randompost(
     State(pool): State<MySqlPool>,
     Query(params): Query<RandomQueryParams>,
 ) -> Result<Json<Vec<Posts>>, PYQError> {
-    let posts = match mysql::select_all_from_posts(&pool, 0, 0, "updated").await {
+    let num = match params.posts_num() {
+        Ok(v) => v,
+        Err(e) => return Err(PYQError::QueryParamsError(e)),
+    };
+    let posts = match timed_query(
+        "select_random_posts",
+        mysql::select_random_posts(&pool, num),
+    )
+    .await
+    {
         Ok(v) => v,
         Err(e) => return Err(PYQError::QueryDataBaseError(e.to_string())),
     };
-    let mut rng = rand::rng();
-    let result: Vec<Posts> = posts
-        .choose_multiple(&mut rng, params.num.unwrap_or(1))
-        .cloned()
-        .collect();
-    Ok(Json(result))
+    Ok(Json(posts))
+}
+
+pub async fn get_search(
+    State(pool): State<MySqlPool>,
+    Query(params): Query<SearchQueryParams>,
+) -> Result<Json<SearchData>, PYQError> {
+    let num = match params.posts_num() {
+        Ok(v) => v,
+        Err(e) => return Err(PYQError::QueryParamsError(e)),
+    };
+    let keyword = match params.q {
+        Some(q) if !q.trim().is_empty() => q.trim().to_string(),
+        _ => return Err(PYQError::QueryParamsError(String::from("缺少检索词`q`"))),
+    };
+    let posts = match timed_query("search_posts", mysql::search_posts(&pool, &keyword, num)).await {
+        Ok(v) => v,
+        Err(e) => return Err(PYQError::QueryDataBaseError(e.to_string())),
+    };
+    Ok(Json(SearchData::new(keyword, posts)))
+}
+
+pub async fn get_tags(
+    State(pool): State<MySqlPool>,
+    Extension(tag_aliases): Extension<TagAliases>,
+) -> Result<Json<Vec<TagData>>, PYQError> {
+    let counts = match timed_query(
+        "select_tag_counts_from_posts",
+        mysql::select_tag_counts_from_posts(&pool),
+    )
+    .await
+    {
+        Ok(v) => v,
+        Err(e) => return Err(PYQError::QueryDataBaseError(e.to_string())),
+    };
+    Ok(Json(TagData::merge(counts, &tag_aliases)))
+}
+
+pub async fn get_tag(
+    State(pool): State<MySqlPool>,
+    Extension(tag_aliases): Extension<TagAliases>,
+    Path(name): Path<String>,
+    Query(params): Query<TagParams>,
+) -> Result<Json<AllPostDataSomeTag>, PYQError> {
+    if name.trim().is_empty() {
+        return Err(PYQError::QueryParamsError(String::from("标签不能为空")));
+    }
+    let sort_rule = match params.checked_sort_rule() {
+        Ok(v) => v,
+        Err(e) => return Err(PYQError::QueryParamsError(e)),
+    };
+    let posts = match timed_query(
+        "select_all_from_posts_with_tags",
+        mysql::select_all_from_posts_with_tags(
+            &pool,
+            &tag_aliases.variants(&name),
+            params.num.unwrap_or(-1),
+            sort_rule,
+        ),
+    )
+    .await
+    {
+        Ok(v) => v,
+        Err(e) => return Err(PYQError::QueryDataBaseError(e.to_string())),
+    };
+    Ok(Json(AllPostDataSomeTag::new(
+        tag_aliases.normalize(&name),
+        posts,
+    )))
+}
+
+pub async fn get_new(
+    State(pool): State<MySqlPool>,
+    Query(params): Query<NewQueryParams>,
+) -> Result<Json<NewPostData>, PYQError> {
+    // 缺省时返回最近一次运行中新增的文章
+    let since = match params.since.map(|since| since.trim().to_string()) {
+        Some(since) if !since.is_empty() => since,
+        _ => match timed_query(
+            "select_latest_time_from_posts",
+            mysql::select_latest_time_from_posts(&pool),
+        )
+        .await
+        {
+            Ok(v) => v,
+            Err(e) => return Err(PYQError::QueryDataBaseError(e.to_string())),
+        },
+    };
+    let posts = match timed_query(
+        "select_new_posts",
+        mysql::select_new_posts(&pool, &since, params.num.unwrap_or(-1)),
+    )
+    .await
+    {
+        Ok(v) => v,
+        Err(e) => return Err(PYQError::QueryDataBaseError(e.to_string())),
+    };
+    Ok(Json(NewPostData::new(since, posts)))
+}
+
+pub async fn get_readyz(
+    State(pool): State<MySqlPool>,
+    Extension(readiness): Extension<Readiness>,
+) -> Response {
+    let mut errors = Vec::new();
+    if let Err(e) = mysql::ping(&pool).await {
+        errors.push(format!("数据库无法连接: {e}"));
+        return health::ready_response(None, errors);
+    }
+    match mysql::select_pending_migrations(&pool).await {
+        Ok(pending) if pending.is_empty() => (),
+        Ok(pending) => errors.push(format!("数据库迁移未执行: {pending:?}")),
+        Err(e) => errors.push(format!("无法查询数据库迁移: {e}")),
+    }
+    let last_updated_time = mysql::select_latest_time_from_posts(&pool).await.ok();
+    health::check_data_age(&readiness, last_updated_time.as_deref(), &mut errors);
+    health::ready_response(last_updated_time, errors)
+}
+
+pub async fn get_admin_friends(
+    State(pool): State<MySqlPool>,
+) -> Result<Json<Vec<ManualFriend>>, PYQError> {
+    match mysql::select_all_from_manual_friends(&pool).await {
+        Ok(v) => Ok(Json(v)),
+        Err(e) => Err(PYQError::QueryDataBaseError(e.to_string())),
+    }
+}
+
+/// 添加友链，返回添加后的所有手动友链
+pub async fn post_admin_friends(
+    State(pool): State<MySqlPool>,
+    Json(friend): Json<ManualFriend>,
+) -> Result<Json<Vec<ManualFriend>>, PYQError> {
+    let friend = admin::validate_manual_friend(friend)?;
+    if let Err(e) = mysql::insert_manual_friend(&friend, &pool).await {
+        return Err(PYQError::InsertDataBaseError(e.to_string()));
+    }
+    get_admin_friends(State(pool)).await
+}
+
+/// 按`link`修改友链的名称、头像和订阅后缀
+pub async fn put_admin_friends(
+    State(pool): State<MySqlPool>,
+    Json(friend): Json<ManualFriend>,
+) -> Result<Json<Vec<ManualFriend>>, PYQError> {
+    let friend = admin::validate_manual_friend(friend)?;
+    match mysql::update_manual_friend(&friend, &pool).await {
+        Ok(0) => {
+            return Err(PYQError::QueryParamsError(format!(
+                "友链不存在: {}",
+                friend.link
+            )));
+        }
+        Ok(_) => (),
+        Err(e) => return Err(PYQError::InsertDataBaseError(e.to_string())),
+    }
+    get_admin_friends(State(pool)).await
+}
+
+pub async fn delete_admin_friends(
+    State(pool): State<MySqlPool>,
+    Query(params): Query<AdminFriendParams>,
+) -> Result<Json<Vec<ManualFriend>>, PYQError> {
+    match mysql::delete_manual_friend(params.link.trim(), &pool).await {
+        Ok(0) => {
+            return Err(PYQError::QueryParamsError(format!(
+                "友链不存在: {}",
+                params.link
+            )));
+        }
+        Ok(_) => (),
+        Err(e) => return Err(PYQError::InsertDataBaseError(e.to_string())),
+    }
+    get_admin_friends(State(pool)).await
+}
+
+pub async fn get_admin_block(
+    State(pool): State<MySqlPool>,
+) -> Result<Json<Vec<BlockedSite>>, PYQError> {
+    match mysql::select_all_from_blocked_sites(&pool).await {
+        Ok(v) => Ok(Json(v)),
+        Err(e) => Err(PYQError::QueryDataBaseError(e.to_string())),
+    }
+}
+
+/// 添加屏蔽站点，返回添加后的所有屏蔽站点
+pub async fn post_admin_block(
+    State(pool): State<MySqlPool>,
+    Json(site): Json<BlockedSite>,
+) -> Result<Json<Vec<BlockedSite>>, PYQError> {
+    let site = admin::validate_blocked_site(site)?;
+    if let Err(e) = mysql::insert_blocked_site(&site, &pool).await {
+        return Err(PYQError::InsertDataBaseError(e.to_string()));
+    }
+    get_admin_block(State(pool)).await
+}
+
+pub async fn delete_admin_block(
+    State(pool): State<MySqlPool>,
+    Query(params): Query<AdminBlockParams>,
+) -> Result<Json<Vec<BlockedSite>>, PYQError> {
+    match mysql::delete_blocked_site(params.pattern.trim(), &pool).await {
+        Ok(0) => {
+            return Err(PYQError::QueryParamsError(format!(
+                "屏蔽站点不存在: {}",
+                params.pattern
+            )));
+        }
+        Ok(_) => (),
+        Err(e) => return Err(PYQError::InsertDataBaseError(e.to_string())),
+    }
+    get_admin_block(State(pool)).await
+}
+
+pub async fn get_admin_overrides(
+    State(pool): State<MySqlPool>,
+) -> Result<Json<Vec<FriendOverride>>, PYQError> {
+    match mysql::select_all_from_friend_overrides(&pool).await {
+        Ok(v) => Ok(Json(v)),
+        Err(e) => Err(PYQError::QueryDataBaseError(e.to_string())),
+    }
+}
+
+/// 添加或修改友链的覆盖设置，下次运行时生效
+pub async fn put_admin_overrides(
+    State(pool): State<MySqlPool>,
+    Json(friend_override): Json<FriendOverride>,
+) -> Result<Json<Vec<FriendOverride>>, PYQError> {
+    let friend_override = admin::validate_friend_override(friend_override)?;
+    if let Err(e) = mysql::upsert_friend_override(&friend_override, &pool).await {
+        return Err(PYQError::InsertDataBaseError(e.to_string()));
+    }
+    get_admin_overrides(State(pool)).await
+}
+
+pub async fn delete_admin_overrides(
+    State(pool): State<MySqlPool>,
+    Query(params): Query<AdminFriendParams>,
+) -> Result<Json<Vec<FriendOverride>>, PYQError> {
+    match mysql::delete_friend_override(params.link.trim(), &pool).await {
+        Ok(0) => {
+            return Err(PYQError::QueryParamsError(format!(
+                "友链覆盖设置不存在: {}",
+                params.link
+            )));
+        }
+        Ok(_) => (),
+        Err(e) => return Err(PYQError::InsertDataBaseError(e.to_string())),
+    }
+    get_admin_overrides(State(pool)).await
 }
@@ -0,0 +1,92 @@
+//! 抓取指标
+//!
+//! core不常驻运行，指标在运行结束时以Prometheus文本格式写入`METRICS.crawl_record`，
+//! 由api的`/metrics`一并返回
+
+use data_structures::metadata::Friends;
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// 安装全局recorder，需在发起请求前调用，下载器中记录的HTTP状态码才会被统计
+pub fn install_recorder() -> Result<PrometheusHandle, Box<dyn Error>> {
+    Ok(PrometheusBuilder::new().install_recorder()?)
+}
+
+/// 记录本次运行的结果
+pub fn record_run(
+    duration: Duration,
+    success_friends: &[Friends],
+    failed_friends: &[Friends],
+    posts_num: usize,
+    new_posts_num: usize,
+) {
+    metrics::gauge!("fcircle_crawl_duration_seconds").set(duration.as_secs_f64());
+    metrics::gauge!("fcircle_crawl_last_run_timestamp_seconds")
+        .set(chrono::Utc::now().timestamp() as f64);
+    metrics::gauge!("fcircle_crawl_friends", "result" => "success")
+        .set(success_friends.len() as f64);
+    metrics::gauge!("fcircle_crawl_friends", "result" => "failed").set(failed_friends.len() as f64);
+    for (friends, up) in [(success_friends, 1.0), (failed_friends, 0.0)] {
+        for friend in friends {
+            metrics::gauge!(
+                "fcircle_crawl_friend_up",
+                "name" => friend.name.clone(),
+                "link" => friend.link.clone(),
+            )
+            .set(up);
+        }
+    }
+    metrics::gauge!("fcircle_crawl_posts_fetched").set(posts_num as f64);
+    metrics::gauge!("fcircle_crawl_new_posts").set(new_posts_num as f64);
+}
+
+/// 写入指标文件，先写临时文件再重命名，避免api读到写了一半的文件
+pub fn write_crawl_record(handle: &PrometheusHandle, path: &str) -> Result<(), Box<dyn Error>> {
+    let path = Path::new(path);
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, handle.render())?;
+    fs::rename(tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data_structures::testing::{self, friend};
+
+    // 测试抓取指标的写入
+    #[test]
+    fn test_write_crawl_record() {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+        metrics::with_local_recorder(&recorder, || {
+            record_run(
+                Duration::from_secs(3),
+                &[friend("a")],
+                &[friend("b")],
+                10,
+                2,
+            );
+        });
+
+        let dir = testing::temp_dir("crawl_metrics");
+        let path = dir.join("crawl.prom");
+        write_crawl_record(&handle, path.to_str().unwrap()).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("fcircle_crawl_duration_seconds 3"));
+        assert!(
+            content
+                .contains(r#"fcircle_crawl_friend_up{name="b",link="https://b.example.com/"} 0"#)
+        );
+        assert!(content.contains("fcircle_crawl_posts_fetched 10"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
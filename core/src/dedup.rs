@@ -0,0 +1,157 @@
+//! 抓取结果去重
+//!
+//! 同一个友链可能在友链页中以不同名称出现多次，同一篇文章也可能以不同形式的地址
+//! （http/https、末尾的`/`、`index.html`、`utm_`参数）被多次获取
+
+use data_structures::config::DedupStrategy;
+use data_structures::metadata::{BasePosts, Friends};
+use downloader::dedup_key;
+use std::collections::{HashMap, HashSet};
+
+/// 获取失败时使用的占位标题，与downloader中保持一致
+const TITLE_PLACEHOLDER: &str = "文章标题获取失败";
+
+/// 按友链地址去重，`reserved`中的地址（如配置项友链）优先，保留先出现的友链
+///
+/// 返回去重后的友链和被去除的数量
+pub fn dedup_friends(friends: Vec<Friends>, reserved: &HashSet<String>) -> (Vec<Friends>, usize) {
+    let mut seen: HashSet<String> = reserved.clone();
+    let total = friends.len();
+    let friends: Vec<Friends> = friends
+        .into_iter()
+        .filter(|friend| seen.insert(dedup_key(&friend.link)))
+        .collect();
+    let removed = total - friends.len();
+    (friends, removed)
+}
+
+/// `candidate`是否应该替换`current`
+fn should_replace(current: &BasePosts, candidate: &BasePosts, strategy: DedupStrategy) -> bool {
+    match strategy {
+        DedupStrategy::First => false,
+        DedupStrategy::Latest => candidate.updated > current.updated,
+        DedupStrategy::Earliest => candidate.created < current.created,
+    }
+}
+
+/// 对所有友链的文章去重，返回被去除的文章数
+///
+/// 冲突时按`strategy`选择保留的文章；保留的文章标题获取失败时，使用被去除文章的标题
+pub fn dedup_posts(all_res: &mut [(Friends, Vec<BasePosts>)], strategy: DedupStrategy) -> usize {
+    // 去重键 -> 保留的文章位置
+    let mut kept: HashMap<String, (usize, usize)> = HashMap::new();
+    let mut removed: HashSet<(usize, usize)> = HashSet::new();
+    for friend_idx in 0..all_res.len() {
+        for post_idx in 0..all_res[friend_idx].1.len() {
+            let key = dedup_key(&all_res[friend_idx].1[post_idx].link);
+            let Some(&current_pos) = kept.get(&key) else {
+                kept.insert(key, (friend_idx, post_idx));
+                continue;
+            };
+            let candidate_pos = (friend_idx, post_idx);
+            let current = &all_res[current_pos.0].1[current_pos.1];
+            let candidate = &all_res[candidate_pos.0].1[candidate_pos.1];
+            let (winner_pos, loser_pos) = if should_replace(current, candidate, strategy) {
+                (candidate_pos, current_pos)
+            } else {
+                (current_pos, candidate_pos)
+            };
+            kept.insert(key, winner_pos);
+            removed.insert(loser_pos);
+            let loser_title = all_res[loser_pos.0].1[loser_pos.1].title.clone();
+            let winner = &mut all_res[winner_pos.0].1[winner_pos.1];
+            if winner.title == TITLE_PLACEHOLDER && loser_title != TITLE_PLACEHOLDER {
+                winner.title = loser_title;
+            }
+        }
+    }
+    for (friend_idx, (_, posts)) in all_res.iter_mut().enumerate() {
+        let mut post_idx = 0;
+        posts.retain(|_| {
+            let keep = !removed.contains(&(friend_idx, post_idx));
+            post_idx += 1;
+            keep
+        });
+    }
+    removed.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data_structures::testing::{base_post, friend};
+
+    // 测试友链去重
+    #[test]
+    fn test_dedup_friends() {
+        let friends = vec![
+            Friends {
+                link: String::from("https://example.com/"),
+                ..friend("用户1")
+            },
+            Friends {
+                link: String::from("http://www.example.com"),
+                ..friend("用户1的别名")
+            },
+            Friends {
+                link: String::from("https://example.org/"),
+                ..friend("用户2")
+            },
+        ];
+        let reserved = HashSet::from([dedup_key("https://example.org")]);
+        let (friends, removed) = dedup_friends(friends, &reserved);
+        assert_eq!(removed, 2);
+        assert_eq!(friends.len(), 1);
+        assert_eq!(friends[0].name, "用户1");
+    }
+
+    // 测试文章去重策略
+    #[test]
+    fn test_dedup_posts() {
+        let build = || {
+            vec![
+                (
+                    Friends {
+                        link: String::from("https://example.com/"),
+                        ..friend("用户1")
+                    },
+                    vec![
+                        base_post(TITLE_PLACEHOLDER, "https://example.com/a/", "2023-01-01"),
+                        base_post("文章B", "https://example.com/b", "2023-01-01"),
+                    ],
+                ),
+                (
+                    Friends {
+                        link: String::from("http://example.com/"),
+                        ..friend("用户1的别名")
+                    },
+                    vec![BasePosts {
+                        updated: String::from("2023-01-03"),
+                        ..base_post(
+                            "文章A",
+                            "http://example.com/a/index.html?utm_source=rss",
+                            "2023-01-02",
+                        )
+                    }],
+                ),
+            ]
+        };
+
+        let mut all_res = build();
+        assert_eq!(dedup_posts(&mut all_res, DedupStrategy::First), 1);
+        assert_eq!(all_res[0].1.len(), 2);
+        assert!(all_res[1].1.is_empty());
+        // 占位标题被补全
+        assert_eq!(all_res[0].1[0].title, "文章A");
+
+        let mut all_res = build();
+        assert_eq!(dedup_posts(&mut all_res, DedupStrategy::Latest), 1);
+        assert_eq!(all_res[0].1.len(), 1);
+        assert_eq!(all_res[1].1[0].updated, "2023-01-03");
+
+        let mut all_res = build();
+        assert_eq!(dedup_posts(&mut all_res, DedupStrategy::Earliest), 1);
+        assert_eq!(all_res[0].1[0].created, "2023-01-01");
+        assert_eq!(all_res[0].1[0].title, "文章A");
+    }
+}
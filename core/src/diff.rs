@@ -0,0 +1,160 @@
+//! 与上次运行结果的对比
+//!
+//! 每次运行都会删除并重新写入文章，写入前先与数据库中的文章对比，
+//! 得到新增、移除和标题变化的文章，并保留已有文章的`first_seen`
+
+use data_structures::metadata::{BasePosts, Friends, Posts};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PostBrief {
+    pub title: String,
+    pub link: String,
+    pub author: String,
+    pub created: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TitleChange {
+    pub link: String,
+    pub author: String,
+    pub old_title: String,
+    pub new_title: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PostDiff {
+    /// 数据库中不存在的文章
+    pub new_posts: Vec<PostBrief>,
+    /// 本次获取成功的友链中，在本次获取的时间范围内但订阅里已经没有的文章
+    pub removed_posts: Vec<PostBrief>,
+    /// 链接相同但标题变化的文章
+    pub title_changes: Vec<TitleChange>,
+    /// 文章链接 -> 第一次获取到的时间
+    #[serde(skip)]
+    first_seen: HashMap<String, String>,
+}
+
+impl PostDiff {
+    /// `previous_posts`为写入数据库前查询到的所有文章，`all_res`为本次获取的结果
+    pub fn new(previous_posts: &[Posts], all_res: &[(Friends, Vec<BasePosts>)]) -> PostDiff {
+        let previous: HashMap<&str, &Posts> = previous_posts
+            .iter()
+            .map(|post| (post.meta.link.as_str(), post))
+            .collect();
+        let mut diff = PostDiff::default();
+        let mut current_links = HashSet::new();
+        // 获取成功的友链 -> 本次获取到的最早的文章日期
+        let mut oldest_created: HashMap<&str, &str> = HashMap::new();
+
+        for (friend, posts) in all_res {
+            if let Some(oldest) = posts.iter().map(|post| post.created.as_str()).min() {
+                oldest_created.insert(friend.name.as_str(), oldest);
+            }
+            for post in posts {
+                current_links.insert(post.link.as_str());
+                match previous.get(post.link.as_str()) {
+                    Some(old) => {
+                        if !old.first_seen.is_empty() {
+                            diff.first_seen
+                                .insert(post.link.clone(), old.first_seen.clone());
+                        }
+                        if old.meta.title != post.title {
+                            diff.title_changes.push(TitleChange {
+                                link: post.link.clone(),
+                                author: friend.name.clone(),
+                                old_title: old.meta.title.clone(),
+                                new_title: post.title.clone(),
+                            });
+                        }
+                    }
+                    None => diff.new_posts.push(PostBrief {
+                        title: post.title.clone(),
+                        link: post.link.clone(),
+                        author: friend.name.clone(),
+                        created: post.created.clone(),
+                    }),
+                }
+            }
+        }
+        // 获取失败的友链无法判断文章是否被移除；早于本次最早文章的，
+        // 可能只是超出了订阅或`MAX_POSTS_NUM`的范围，也不算移除
+        diff.removed_posts = previous_posts
+            .iter()
+            .filter(|post| {
+                oldest_created
+                    .get(post.author.as_str())
+                    .is_some_and(|&oldest| post.meta.created.as_str() >= oldest)
+                    && !current_links.contains(post.meta.link.as_str())
+            })
+            .map(|post| PostBrief {
+                title: post.meta.title.clone(),
+                link: post.meta.link.clone(),
+                author: post.author.clone(),
+                created: post.meta.created.clone(),
+            })
+            .collect();
+        diff
+    }
+
+    /// 为即将写入的文章设置`first_seen`，已有文章沿用上次的值
+    pub fn apply_first_seen(&self, post: &mut Posts) {
+        if let Some(first_seen) = self.first_seen.get(&post.meta.link) {
+            post.first_seen = first_seen.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data_structures::testing::{base_post, friend, post};
+
+    // 测试新增、移除、标题变化和first_seen的保留
+    #[test]
+    fn test_post_diff() {
+        let previous_posts: Vec<Posts> = [
+            ("a", "旧标题", "https://a.example.com/1/"),
+            ("a", "被删除", "https://a.example.com/2/"),
+            ("b", "获取失败", "https://b.example.com/1/"),
+        ]
+        .iter()
+        .map(|(author, title, link)| post(base_post(title, link, "2024-06-01"), author))
+        .collect();
+        // 早于本次获取范围的旧文章
+        let outside = post(
+            base_post("范围之外", "https://a.example.com/0/", "2023-01-01"),
+            "a",
+        );
+        let previous_posts = [previous_posts, vec![outside]].concat();
+        let all_res = vec![
+            (
+                friend("a"),
+                vec![
+                    base_post("新标题", "https://a.example.com/1/", "2024-06-01"),
+                    base_post("新文章", "https://a.example.com/3/", "2024-06-01"),
+                ],
+            ),
+            (friend("b"), vec![]),
+        ];
+
+        let diff = PostDiff::new(&previous_posts, &all_res);
+        assert_eq!(diff.new_posts.len(), 1);
+        assert_eq!(diff.new_posts[0].link, "https://a.example.com/3/");
+        // b获取失败，它的文章不算移除；a中早于本次获取范围的文章也不算
+        assert_eq!(diff.removed_posts.len(), 1);
+        assert_eq!(diff.removed_posts[0].link, "https://a.example.com/2/");
+        assert_eq!(diff.title_changes.len(), 1);
+        assert_eq!(diff.title_changes[0].old_title, "旧标题");
+
+        let mut current = Posts::new(
+            base_post("新标题", "https://a.example.com/1/", "2024-06-01"),
+            String::from("a"),
+            String::new(),
+            String::from("2024-06-02 00:00:00"),
+        );
+        diff.apply_first_seen(&mut current);
+        assert_eq!(current.first_seen, "2024-06-01 00:00:00");
+    }
+}
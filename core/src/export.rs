@@ -0,0 +1,181 @@
+//! 极简模式的数据导出
+
+use crate::static_site;
+use data_structures::config::{ExportFormat, Settings};
+use data_structures::metadata::{Friends, Posts};
+use data_structures::response::{AllPostData, Pagination};
+use std::error::Error;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// csv的列，与`Posts`的json字段名一致
+const CSV_HEADER: [&str; 11] = [
+    "title",
+    "created",
+    "updated",
+    "link",
+    "author",
+    "avatar",
+    "summary",
+    "cover",
+    "tags",
+    "rule",
+    "createdAt",
+];
+
+/// 按RFC 4180转义csv字段
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn csv_row(post: &Posts) -> String {
+    [
+        post.meta.title.as_str(),
+        &post.meta.created,
+        &post.meta.updated,
+        &post.meta.link,
+        &post.author,
+        &post.avatar,
+        post.meta.summary.as_deref().unwrap_or_default(),
+        post.meta.cover.as_deref().unwrap_or_default(),
+        &post.meta.tags.to_db_string(),
+        &post.meta.rule,
+        &post.created_at,
+    ]
+    .iter()
+    .map(|field| escape_csv(field))
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+/// 按`SIMPLE_MODE_OUTPUT`中的路径和格式导出数据
+///
+/// json格式与`/all`的返回格式相同，ndjson和csv格式每行一篇文章
+pub fn write_export(
+    fc_settings: &Settings,
+    friends: &[Friends],
+    posts: &[Posts],
+    last_updated_time: &str,
+) -> Result<(), Box<dyn Error>> {
+    let settings = &fc_settings.simple_mode_output;
+    let path = Path::new(&settings.export_path);
+    if let Some(parent) = path.parent()
+        && !parent.as_os_str().is_empty()
+    {
+        fs::create_dir_all(parent)?;
+    }
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+
+    match settings.export_format {
+        ExportFormat::Json | ExportFormat::CompactJson => {
+            let dormant_before = fc_settings.friend_activity().dormant_before();
+            let (active_num, dormant_num, lost_num) =
+                static_site::count_friends(friends, dormant_before.as_deref());
+            let data = AllPostData::new(
+                friends.len(),
+                active_num,
+                dormant_num,
+                lost_num,
+                last_updated_time.to_string(),
+                posts.to_vec(),
+                Pagination::new(posts.len(), 0, 0),
+            );
+            if settings.export_format == ExportFormat::Json {
+                serde_json::to_writer_pretty(&mut writer, &data)?;
+            } else {
+                serde_json::to_writer(&mut writer, &data)?;
+            }
+        }
+        ExportFormat::Ndjson => {
+            for post in posts {
+                serde_json::to_writer(&mut writer, post)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+        ExportFormat::Csv => {
+            writeln!(writer, "{}", CSV_HEADER.join(","))?;
+            for post in posts {
+                writeln!(writer, "{}", csv_row(post))?;
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data_structures::metadata::{BasePosts, Tags};
+    use data_structures::testing;
+
+    // 测试各个导出格式
+    #[test]
+    fn test_write_export() {
+        let export_dir = testing::temp_dir("export");
+        let mut fc_settings = testing::settings();
+
+        let mut meta = BasePosts::new(
+            String::from("标题, \"引号\""),
+            String::from("2024-06-01"),
+            String::from("2024-06-02"),
+            String::from("https://example.com/a/"),
+            String::from("feed"),
+        );
+        meta.tags = Tags::new([String::from("Rust"), String::from("博客")]);
+        let posts = vec![
+            Posts::new(
+                meta.clone(),
+                String::from("alice"),
+                String::new(),
+                String::from("2024-06-02 00:00:00"),
+            ),
+            Posts::new(
+                meta,
+                String::from("bob"),
+                String::new(),
+                String::from("2024-06-02 00:00:00"),
+            ),
+        ];
+
+        let cases = [
+            (ExportFormat::Json, "data.json"),
+            (ExportFormat::CompactJson, "data.min.json"),
+            (ExportFormat::Ndjson, "data.ndjson"),
+            (ExportFormat::Csv, "data.csv"),
+        ];
+        for (format, name) in cases {
+            let path = export_dir.join(name);
+            fc_settings.simple_mode_output.export_path = path.to_string_lossy().to_string();
+            fc_settings.simple_mode_output.export_format = format;
+            write_export(&fc_settings, &[], &posts, "2024-06-02 00:00:00").unwrap();
+
+            let content = fs::read_to_string(&path).unwrap();
+            match format {
+                ExportFormat::Json | ExportFormat::CompactJson => {
+                    let data: serde_json::Value = serde_json::from_str(&content).unwrap();
+                    assert_eq!(data["statistical_data"]["article_num"], 2);
+                    assert_eq!(content.contains('\n'), format == ExportFormat::Json);
+                }
+                ExportFormat::Ndjson => {
+                    let lines: Vec<&str> = content.lines().collect();
+                    assert_eq!(lines.len(), 2);
+                    let post: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+                    assert_eq!(post["author"], "bob");
+                }
+                ExportFormat::Csv => {
+                    let lines: Vec<&str> = content.lines().collect();
+                    assert_eq!(lines.len(), 3);
+                    assert!(lines[1].starts_with("\"标题, \"\"引号\"\"\",2024-06-01,"));
+                    assert!(lines[1].contains(",\"Rust,博客\","));
+                }
+            }
+        }
+        fs::remove_dir_all(&export_dir).unwrap();
+    }
+}
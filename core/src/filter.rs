@@ -0,0 +1,194 @@
+//! 文章过滤
+//!
+//! 按标题、链接、关键词和创建时间过滤获取到的文章，在去重和写入数据库前执行；
+//! 数据库中已保存的文章也按同样的规则清理
+
+use chrono::{DateTime, FixedOffset, TimeDelta};
+use data_structures::config::{PostFilterMode, PostFilterSettings};
+use data_structures::metadata::{BasePosts, Posts};
+use regex::RegexSet;
+
+pub struct PostFilters {
+    mode: PostFilterMode,
+    titles: RegexSet,
+    links: RegexSet,
+    /// 已转换为小写
+    keywords: Vec<String>,
+    /// 早于该日期（`%Y-%m-%d`）创建的文章被去除
+    created_after: Option<String>,
+}
+
+impl PostFilters {
+    /// 编译`POST_FILTERS`中的正则，`now`用于计算`max_age_days`
+    pub fn new(
+        settings: &PostFilterSettings,
+        now: DateTime<FixedOffset>,
+    ) -> Result<PostFilters, regex::Error> {
+        let created_after = (settings.max_age_days > 0).then(|| {
+            tools::strptime_to_string_ymd(now - TimeDelta::days(settings.max_age_days as i64))
+        });
+        Ok(PostFilters {
+            mode: settings.mode,
+            titles: RegexSet::new(&settings.titles)?,
+            links: RegexSet::new(&settings.links)?,
+            keywords: settings
+                .keywords
+                .iter()
+                .filter(|keyword| !keyword.is_empty())
+                .map(|keyword| keyword.to_lowercase())
+                .collect(),
+            created_after,
+        })
+    }
+
+    fn has_rules(&self) -> bool {
+        !self.titles.is_empty() || !self.links.is_empty() || !self.keywords.is_empty()
+    }
+
+    fn matches(&self, post: &BasePosts) -> bool {
+        if self.titles.is_match(&post.title) || self.links.is_match(&post.link) {
+            return true;
+        }
+        if self.keywords.is_empty() {
+            return false;
+        }
+        let title = post.title.to_lowercase();
+        let summary = post.summary.as_deref().unwrap_or_default().to_lowercase();
+        self.keywords
+            .iter()
+            .any(|keyword| title.contains(keyword) || summary.contains(keyword))
+    }
+
+    fn keep(&self, post: &BasePosts) -> bool {
+        if let Some(created_after) = &self.created_after
+            && post.created.as_str() < created_after.as_str()
+        {
+            return false;
+        }
+        match self.mode {
+            PostFilterMode::Block => !self.matches(post),
+            PostFilterMode::Allow => !self.has_rules() || self.matches(post),
+        }
+    }
+
+    /// 去除不需要的文章，返回去除的数量
+    pub fn apply(&self, posts: &mut Vec<BasePosts>) -> usize {
+        let before = posts.len();
+        posts.retain(|post| self.keep(post));
+        before - posts.len()
+    }
+
+    /// 从已保存的文章中取出不符合当前规则的文章，用于从数据库中删除
+    pub fn take_rejected(&self, posts: &mut Vec<Posts>) -> Vec<Posts> {
+        let (kept, rejected) = std::mem::take(posts)
+            .into_iter()
+            .partition(|post| self.keep(&post.meta));
+        *posts = kept;
+        rejected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use data_structures::testing::{base_post, post};
+    use db::sqlite;
+
+    // 测试屏蔽和白名单两种模式，以及按创建时间过滤
+    #[test]
+    fn test_post_filters() {
+        let now = FixedOffset::east_opt(8 * 60 * 60)
+            .unwrap()
+            .with_ymd_and_hms(2024, 6, 30, 12, 0, 0)
+            .unwrap();
+        let posts = vec![
+            base_post("Hello World", "https://a.com/hello/", "2024-06-01"),
+            base_post("关于", "https://a.com/about/", "2024-06-01"),
+            BasePosts {
+                summary: Some(String::from("学习RUST")),
+                ..base_post("Rust笔记", "https://a.com/rust/", "2024-06-02")
+            },
+            base_post("旧文章", "https://a.com/old/", "2023-01-01"),
+        ];
+        let mut settings = PostFilterSettings {
+            mode: PostFilterMode::Block,
+            titles: vec![String::from("^Hello World$")],
+            links: vec![String::from("/about/?$")],
+            keywords: vec![],
+            max_age_days: 365,
+        };
+
+        let mut blocked = posts.clone();
+        let removed = PostFilters::new(&settings, now)
+            .unwrap()
+            .apply(&mut blocked);
+        assert_eq!(removed, 3);
+        assert_eq!(blocked.len(), 1);
+        assert_eq!(blocked[0].title, "Rust笔记");
+
+        // 白名单模式下只保留标题或摘要中包含关键词的文章
+        settings.mode = PostFilterMode::Allow;
+        settings.titles.clear();
+        settings.links.clear();
+        settings.keywords = vec![String::from("rust")];
+        settings.max_age_days = 0;
+        let mut allowed = posts.clone();
+        PostFilters::new(&settings, now)
+            .unwrap()
+            .apply(&mut allowed);
+        assert_eq!(allowed.len(), 1);
+        assert_eq!(allowed[0].link, "https://a.com/rust/");
+
+        settings.titles = vec![String::from("(")];
+        assert!(PostFilters::new(&settings, now).is_err());
+    }
+
+    // 测试新增的规则同样清理数据库中已保存的文章
+    #[tokio::test]
+    async fn test_take_rejected_stored_posts() {
+        let dir = data_structures::testing::temp_dir("filter");
+        let dbpool = sqlite::connect_sqlite_dbpool(dir.join("data.db"))
+            .await
+            .unwrap();
+        sqlx::migrate!("../db/schema/sqlite")
+            .run(&dbpool)
+            .await
+            .unwrap();
+        let stored = [
+            base_post("Hello World", "https://a.com/hello/", "2024-06-01"),
+            base_post("Rust笔记", "https://a.com/rust/", "2024-06-02"),
+        ]
+        .map(|meta| post(meta, "a"));
+        sqlite::bulk_insert_post_table(stored.into_iter(), &dbpool)
+            .await
+            .unwrap();
+
+        let settings = PostFilterSettings {
+            mode: PostFilterMode::Block,
+            titles: vec![String::from("^Hello World$")],
+            links: vec![],
+            keywords: vec![],
+            max_age_days: 0,
+        };
+        let mut previous_posts = sqlite::select_all_from_posts(&dbpool, 0, 0, "updated")
+            .await
+            .unwrap();
+        let rejected = PostFilters::new(&settings, Utc::now().fixed_offset())
+            .unwrap()
+            .take_rejected(&mut previous_posts);
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(previous_posts.len(), 1);
+        sqlite::delete_post_table(rejected.into_iter(), &dbpool)
+            .await
+            .unwrap();
+
+        let posts = sqlite::select_all_from_posts(&dbpool, 0, 0, "updated")
+            .await
+            .unwrap();
+        assert_eq!(posts.len(), 1);
+        assert_eq!(posts[0].meta.link, "https://a.com/rust/");
+        dbpool.close().await;
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
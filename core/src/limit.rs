@@ -0,0 +1,98 @@
+//! 文章数限制
+//!
+//! 订阅中的文章不一定按时间从新到旧排列，截取前先排序，保证保留的是最新的文章
+
+use data_structures::config::PostSortBy;
+use data_structures::metadata::{BasePosts, Friends};
+use std::cmp::Reverse;
+use std::collections::HashSet;
+
+fn sort_key(post: &BasePosts, sort_by: PostSortBy) -> &str {
+    match sort_by {
+        PostSortBy::Created => &post.created,
+        PostSortBy::Updated => &post.updated,
+    }
+}
+
+/// 按`sort_by`从新到旧排序后保留前`max_posts`篇，0表示不限制
+pub fn limit_posts(posts: &mut Vec<BasePosts>, sort_by: PostSortBy, max_posts: usize) {
+    posts.sort_by(|a, b| sort_key(b, sort_by).cmp(sort_key(a, sort_by)));
+    if max_posts > 0 {
+        posts.truncate(max_posts);
+    }
+}
+
+/// 所有友链合计只保留最新的`max_total`篇文章，返回去除的数量，0表示不限制
+pub fn cap_total_posts(
+    all_res: &mut [(Friends, Vec<BasePosts>)],
+    sort_by: PostSortBy,
+    max_total: usize,
+) -> usize {
+    let mut positions: Vec<(&str, usize, usize)> = all_res
+        .iter()
+        .enumerate()
+        .flat_map(|(i, (_, posts))| {
+            posts
+                .iter()
+                .enumerate()
+                .map(move |(j, post)| (sort_key(post, sort_by), i, j))
+        })
+        .collect();
+    if max_total == 0 || positions.len() <= max_total {
+        return 0;
+    }
+    // 时间相同时保留友链顺序靠前的
+    positions.sort_by_key(|&(key, i, j)| (Reverse(key), i, j));
+    let removed = positions.len() - max_total;
+    let kept: HashSet<(usize, usize)> = positions
+        .into_iter()
+        .take(max_total)
+        .map(|(_, i, j)| (i, j))
+        .collect();
+    for (i, (_, posts)) in all_res.iter_mut().enumerate() {
+        let mut j = 0;
+        posts.retain(|_| {
+            j += 1;
+            kept.contains(&(i, j - 1))
+        });
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data_structures::testing::{base_post, friend};
+
+    // 测试按时间排序后截取，以及合计文章数上限
+    #[test]
+    fn test_limit_posts() {
+        // 订阅按时间从旧到新排列
+        let mut posts = vec![
+            base_post("1", "1", "2024-01-01"),
+            base_post("2", "2", "2024-02-01"),
+            base_post("3", "3", "2024-03-01"),
+        ];
+        limit_posts(&mut posts, PostSortBy::Created, 2);
+        let links: Vec<&str> = posts.iter().map(|post| post.link.as_str()).collect();
+        assert_eq!(links, ["3", "2"]);
+
+        let mut all_res = vec![
+            (
+                friend("a"),
+                vec![
+                    base_post("a1", "a1", "2024-03-01"),
+                    base_post("a2", "a2", "2024-01-01"),
+                ],
+            ),
+            (friend("b"), vec![base_post("b1", "b1", "2024-02-01")]),
+            (friend("c"), vec![base_post("c1", "c1", "2023-01-01")]),
+        ];
+        assert_eq!(cap_total_posts(&mut all_res, PostSortBy::Created, 2), 2);
+        assert_eq!(all_res[0].1.len(), 1);
+        assert_eq!(all_res[0].1[0].link, "a1");
+        assert_eq!(all_res[1].1.len(), 1);
+        assert!(all_res[2].1.is_empty());
+        assert_eq!(cap_total_posts(&mut all_res, PostSortBy::Created, 0), 0);
+    }
+}
@@ -1,48 +1,65 @@
-use std::fs::File;
+mod crawl_metrics;
+mod dedup;
+mod diff;
+mod export;
+mod filter;
+mod limit;
+mod manual;
+mod notify;
+mod static_site;
+mod store;
+
+use std::collections::HashSet;
+use std::time::Instant;
 
 use chrono::Utc;
-use data_structures::metadata::{self};
-use data_structures::response::AllPostData;
-use db::{SqlitePool, mongo, mysql, sqlite};
+use data_structures::config::Settings;
+use data_structures::metadata::{self, FailureKind};
+use db::{mongo, mysql, sqlite};
+use downloader::block::BlockSites;
 use downloader::download;
 use tokio::{self};
-use tracing::{error, info};
-
-/// 极简模式，写入data.json文件
-pub async fn write_data_to_json(pool: &SqlitePool) -> Result<(), Box<dyn std::error::Error>> {
-    let posts = sqlite::select_all_from_posts(pool, 0, 0, "updated").await?;
+use tracing::{debug, error, info};
 
-    let last_updated_time = sqlite::select_latest_time_from_posts(pool).await?;
-
-    let friends = sqlite::select_all_from_friends(pool).await?;
-    let friends_num = friends.len();
-    let mut active_num = 0;
-    let mut lost_num = 0;
-    for friend in friends {
-        if friend.error {
-            lost_num += 1;
-        } else {
-            active_num += 1;
-        }
-    }
-    let data = AllPostData::new(
-        friends_num,
-        active_num,
-        lost_num,
-        posts.len(),
-        last_updated_time,
-        posts,
-        0,
+/// 极简模式，导出数据并生成静态站点
+fn write_simple_mode(
+    fc_settings: &Settings,
+    friends: Vec<metadata::Friends>,
+    posts: Vec<metadata::Posts>,
+    last_updated_time: String,
+) -> Result<(), Box<dyn std::error::Error>> {
+    export::write_export(fc_settings, &friends, &posts, &last_updated_time)?;
+    info!(
+        "数据已成功写入到{}",
+        fc_settings.simple_mode_output.export_path
     );
 
-    // 创建data.json文件并写入数据
-    let file = File::create("data.json")?;
-    serde_json::to_writer_pretty(file, &data)?;
-    info!("数据已成功写入到data.json文件");
-
+    let written = static_site::write_static_site(fc_settings, friends, posts, last_updated_time)?;
+    info!(
+        "静态站点已生成到{}，共{}个文件",
+        fc_settings.simple_mode_output.output_dir, written
+    );
     Ok(())
 }
 
+/// 获取文章时主页或订阅永久迁移到了其他站点，开启`UPDATE_MOVED_FRIENDS`时替换友链地址
+fn follow_site_move(
+    friend: &mut metadata::Friends,
+    site_move: Option<&download::SiteMove>,
+    fc_settings: &Settings,
+) {
+    let Some(site_move) = site_move.filter(|site_move| site_move.permanent) else {
+        return;
+    };
+    info!(
+        "友链 {} 已迁移: {} -> {}",
+        friend.name, site_move.from, site_move.to
+    );
+    if fc_settings.update_moved_friends {
+        friend.link = site_move.moved_link(&friend.link);
+    }
+}
+
 #[tokio::main]
 async fn main() {
     let _guard = tools::init_tracing(
@@ -50,39 +67,103 @@ async fn main() {
         Some("error,core=debug,db=debug,downloader=debug,tools=debug,data_structures=debug"),
     );
 
+    let start = Instant::now();
     let now = Utc::now().with_timezone(&downloader::BEIJING_OFFSET.unwrap());
 
     let css_rules: tools::Value = tools::get_yaml("./css_rules.yaml").unwrap();
-    let fc_settings = tools::get_yaml_settings("./fc_settings.yaml").unwrap();
+    let mut fc_settings = tools::get_yaml_settings("./fc_settings.yaml").unwrap();
+    // 读取配置后立即编译`BLOCK_SITE`，有无效规则时不再继续
+    let mut block_sites = match BlockSites::new(&fc_settings.block_site) {
+        Ok(block_sites) => block_sites,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    let manual_entries = manual::load_manual_entries(&fc_settings).await;
+    manual::merge_manual_entries(&mut fc_settings, manual_entries.friends);
+    manual::extend_block_sites(&mut block_sites, &fc_settings, manual_entries.blocked_sites);
+    let friend_overrides = manual::FriendOverrides::new(manual_entries.overrides);
+    let post_filters = match filter::PostFilters::new(&fc_settings.post_filters, now) {
+        Ok(post_filters) => post_filters,
+        Err(e) => {
+            error!("`POST_FILTERS`中的正则无效: {}", e);
+            return;
+        }
+    };
 
+    let metrics_handle = if fc_settings.metrics.enable {
+        match crawl_metrics::install_recorder() {
+            Ok(handle) => Some(handle),
+            Err(e) => {
+                error!("初始化抓取指标失败: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
     let client = download::build_client();
 
     // let _cssrule = css_rules.clone();
     let format_base_friends =
-        download::start_crawl_linkpages(&fc_settings, &css_rules, &client).await;
+        download::start_crawl_linkpages(&fc_settings, &block_sites, &css_rules, &client).await;
     // info!("{:?}", format_base_friends);
+    // 友链页中与配置项友链重复或者重复出现的友链只抓取一次
+    let reserved_links: HashSet<String> = if fc_settings.settings_friends_links.enable {
+        fc_settings
+            .settings_friends_links
+            .list
+            .iter()
+            .filter_map(|postpage_vec| postpage_vec.get(1))
+            .map(|link| downloader::dedup_key(link))
+            .collect()
+    } else {
+        HashSet::new()
+    };
+    let (format_base_friends, duplicate_friends_num) =
+        dedup::dedup_friends(format_base_friends, &reserved_links);
+    if duplicate_friends_num > 0 {
+        info!("去除重复友链 {} 个", duplicate_friends_num);
+    }
     let mut all_res = vec![];
     let mut tasks = vec![];
+    // 隐藏的友链不抓取，写入时删除已保存的文章
+    let mut hidden_friends = vec![];
 
-    for friend in format_base_friends {
+    for mut friend in format_base_friends {
         // if friend.link != "https://akilar.top/" {
         //     continue;
         // }
+        let friend_override = friend_overrides.get(&friend.link);
+        if friend_override.is_some_and(|friend_override| friend_override.hidden) {
+            info!("跳过隐藏的友链: {}", friend.link);
+            hidden_friends.push(friend);
+            continue;
+        }
+        let (feed_suffix, css_rules) =
+            manual::apply_override(&mut friend, friend_override, String::new(), &css_rules);
+        let max_posts = manual::max_posts(friend_override, fc_settings.max_posts_num);
         let fc_settings = fc_settings.clone();
         let client = client.clone();
-        let css_rules = css_rules.clone();
+        let block_sites = block_sites.clone();
         let task = tokio::spawn(async move {
-            let format_base_posts = download::start_crawl_postpages(
+            // 链接无效的友链无法抓取
+            if friend.error_kind == Some(FailureKind::InvalidLink) {
+                return (friend, vec![], None, max_posts);
+            }
+            let (format_base_posts, site_move) = download::start_crawl_postpages(
                 friend.link.clone(),
-                &fc_settings,
-                "".to_string(),
+                &block_sites,
+                feed_suffix,
                 &css_rules,
                 &client,
             )
             .await
             .unwrap();
+            follow_site_move(&mut friend, site_move.as_ref(), &fc_settings);
             // info!("{:?}",format_base_posts);
-            (friend, format_base_posts)
+            (friend, format_base_posts, site_move, max_posts)
         });
         tasks.push(task);
     }
@@ -95,28 +176,38 @@ async fn main() {
         for postpage_vec in settings_friend_postpages {
             let tm = now;
             let created_at = tools::strptime_to_string_ymdhms(tm);
-            let base_post = metadata::Friends::new(
+            let mut base_post = metadata::Friends::new(
                 postpage_vec[0].clone(),
                 postpage_vec[1].clone(),
                 postpage_vec[2].clone(),
                 false,
                 created_at,
             );
+            let feed_suffix = if postpage_vec.len() == 3 {
+                String::from("")
+            } else if postpage_vec.len() == 4 {
+                postpage_vec[3].clone()
+            } else {
+                panic!("`SETTINGS_FRIENDS_LINKS-list`下的数组长度只能为3或4");
+            };
+            let friend_override = friend_overrides.get(&base_post.link);
+            if friend_override.is_some_and(|friend_override| friend_override.hidden) {
+                info!("跳过隐藏的友链: {}", base_post.link);
+                hidden_friends.push(base_post);
+                continue;
+            }
+            let (feed_suffix, css_rules) =
+                manual::apply_override(&mut base_post, friend_override, feed_suffix, &css_rules);
+            let max_posts = manual::max_posts(friend_override, fc_settings.max_posts_num);
             // 请求主页面
             let fc_settings = fc_settings.clone();
             let client = client.clone();
-            let css_rules = css_rules.clone();
+            let block_sites = block_sites.clone();
             let task = tokio::spawn(async move {
-                let format_base_posts = match download::start_crawl_postpages(
+                let (format_base_posts, site_move) = match download::start_crawl_postpages(
                     base_post.link.clone(),
-                    &fc_settings,
-                    if postpage_vec.len() == 3 {
-                        String::from("")
-                    } else if postpage_vec.len() == 4 {
-                        postpage_vec[3].clone()
-                    } else {
-                        panic!("`SETTINGS_FRIENDS_LINKS-list`下的数组长度只能为3或4");
-                    },
+                    &block_sites,
+                    feed_suffix,
                     &css_rules,
                     &client,
                 )
@@ -125,32 +216,97 @@ async fn main() {
                     Ok(v) => v,
                     Err(e) => {
                         error!("{}", e);
-                        return (base_post, vec![]);
+                        return (base_post, vec![], None, max_posts);
                     }
                 };
+                follow_site_move(&mut base_post, site_move.as_ref(), &fc_settings);
                 // info!("{:?}",format_base_posts);
-                (base_post, format_base_posts)
+                (base_post, format_base_posts, site_move, max_posts)
             });
             tasks.push(task);
         }
     }
+    let tag_aliases = fc_settings.tag_aliases();
+    let mut site_moves = Vec::new();
+    let mut filtered_posts_num = 0;
+    // 文章全部被过滤或超出文章总数上限的友链，仍然视为获取成功
+    let mut fully_filtered = HashSet::new();
     for task in tasks {
-        let mut res = task.await.unwrap();
-        if fc_settings.max_posts_num > 0 {
-            res.1 = res
-                .1
-                .iter()
-                .take(fc_settings.max_posts_num)
-                .cloned()
-                .collect();
+        let (friend, posts, site_move, max_posts) = task.await.unwrap();
+        site_moves.extend(site_move);
+        let mut res = (friend, posts);
+        if !res.1.is_empty() {
+            filtered_posts_num += post_filters.apply(&mut res.1);
+            if res.1.is_empty() {
+                fully_filtered.insert(res.0.link.clone());
+            }
+        }
+        // 记录最新文章的日期，用于判断友链是否不活跃
+        res.0.latest_post = res.1.iter().map(|post| post.created.clone()).max();
+        limit::limit_posts(&mut res.1, fc_settings.post_sort_by, max_posts);
+        // 统一标签写法
+        for post in res.1.iter_mut() {
+            post.tags = tag_aliases.normalize_tags(&post.tags);
         }
         all_res.push(res);
     }
-    let mut success_posts = Vec::new();
-    let mut success_friends = Vec::new();
-    let mut failed_friends = Vec::new();
-    let affected_rows;
-    match fc_settings.database.as_str() {
+    if filtered_posts_num > 0 {
+        info!("过滤文章 {} 篇", filtered_posts_num);
+    }
+    let duplicate_posts_num = dedup::dedup_posts(&mut all_res, fc_settings.dedup_strategy);
+    if duplicate_posts_num > 0 {
+        info!("去除重复文章 {} 篇", duplicate_posts_num);
+    }
+    let had_posts: Vec<bool> = all_res.iter().map(|(_, posts)| !posts.is_empty()).collect();
+    let capped_posts_num = limit::cap_total_posts(
+        &mut all_res,
+        fc_settings.post_sort_by,
+        fc_settings.max_total_posts,
+    );
+    if capped_posts_num > 0 {
+        info!("超出文章总数上限，去除较旧的文章 {} 篇", capped_posts_num);
+        for ((friend, posts), had_posts) in all_res.iter().zip(had_posts) {
+            if had_posts && posts.is_empty() {
+                fully_filtered.insert(friend.link.clone());
+            }
+        }
+    }
+    // 诊断没有获取到文章的友链，链接无效的友链已记录原因
+    let needs_diagnosis = |friend: &metadata::Friends, posts: &Vec<metadata::BasePosts>| {
+        posts.is_empty()
+            && !fully_filtered.contains(&friend.link)
+            && friend.error_kind != Some(FailureKind::InvalidLink)
+    };
+    let failed_links: Vec<String> = all_res
+        .iter()
+        .filter(|(friend, posts)| needs_diagnosis(friend, posts))
+        .map(|(friend, _)| friend.link.clone())
+        .collect();
+    if !failed_links.is_empty() {
+        let failure_kinds = downloader::diagnose::diagnose_failures(&client, failed_links).await;
+        for (friend, posts) in all_res.iter_mut() {
+            if needs_diagnosis(friend, posts) {
+                friend.error_kind = failure_kinds.get(&friend.link).copied();
+            }
+        }
+    }
+    // 镜像头像，失败的保留原地址
+    if fc_settings.avatar_cache.enable {
+        let avatars = all_res.iter().map(|(friend, _)| friend.avatar.clone());
+        let mirrored = downloader::avatar::mirror_avatars(
+            &client,
+            avatars.collect::<Vec<_>>(),
+            &fc_settings.avatar_cache,
+        )
+        .await;
+        for (friend, _) in all_res.iter_mut() {
+            if let Some(avatar) = mirrored.get(&friend.avatar) {
+                friend.avatar = avatar.clone();
+            }
+        }
+    }
+    let created_at = tools::strptime_to_string_ymdhms(now);
+    let saved = match fc_settings.database.as_str() {
         "sqlite" => {
             // get sqlite conn pool
             let dbpool = sqlite::connect_sqlite_dbpool("data.db").await.unwrap();
@@ -161,58 +317,16 @@ async fn main() {
                     return;
                 }
             };
-            if let Err(e) = sqlite::truncate_friend_table(&dbpool).await {
-                error!("{}", e);
-                return;
-            }
-            for mut crawl_res in all_res {
-                if !crawl_res.1.is_empty() {
-                    let posts = crawl_res.1.iter().map(|post| {
-                        metadata::Posts::new(
-                            post.clone(),
-                            crawl_res.0.name.clone(),
-                            crawl_res.0.avatar.clone(),
-                            tools::strptime_to_string_ymdhms(now),
-                        )
-                    });
-                    if let Err(e) = sqlite::delete_post_table(posts.clone(), &dbpool).await {
-                        error!("{}", e);
-                        return;
-                    }
-                    if let Err(e) = sqlite::bulk_insert_post_table(posts, &dbpool).await {
-                        error!("{}", e);
-                        return;
-                    }
-                    if let Err(e) = sqlite::insert_friend_table(&crawl_res.0, &dbpool).await {
-                        error!("{}", e);
-                        return;
-                    }
-                    success_friends.push(crawl_res.0);
-                    success_posts.push(crawl_res.1);
-                } else {
-                    crawl_res.0.error = true;
-                    if let Err(e) = sqlite::insert_friend_table(&crawl_res.0, &dbpool).await {
-                        error!("{}", e);
-                        return;
-                    }
-                    failed_friends.push(crawl_res.0);
-                }
-            }
-
-            // outdated posts cleanup
-            affected_rows =
-                match sqlite::delete_outdated_posts(fc_settings.outdate_clean, &dbpool).await {
-                    Ok(v) => v,
-                    Err(e) => {
-                        error!("清理过期文章失败:{}", e);
-                        0
-                    }
-                };
-            if fc_settings.simple_mode
-                && let Err(e) = write_data_to_json(&dbpool).await
-            {
-                info!("写入JSON数据失败: {}", e);
-            }
+            store::save_crawl_results(
+                &dbpool,
+                &fc_settings,
+                &post_filters,
+                all_res,
+                &hidden_friends,
+                &fully_filtered,
+                &created_at,
+            )
+            .await
         }
         "mysql" => {
             // get mysql conn pool
@@ -237,53 +351,16 @@ async fn main() {
                     return;
                 }
             };
-            if let Err(e) = mysql::truncate_friend_table(&dbpool).await {
-                error!("{}", e);
-                return;
-            }
-            for mut crawl_res in all_res {
-                if !crawl_res.1.is_empty() {
-                    let posts = crawl_res.1.iter().map(|post| {
-                        metadata::Posts::new(
-                            post.clone(),
-                            crawl_res.0.name.clone(),
-                            crawl_res.0.avatar.clone(),
-                            tools::strptime_to_string_ymdhms(now),
-                        )
-                    });
-                    if let Err(e) = mysql::delete_post_table(posts.clone(), &dbpool).await {
-                        error!("{}", e);
-                        return;
-                    }
-                    if let Err(e) = mysql::bulk_insert_post_table(posts, &dbpool).await {
-                        error!("{}", e);
-                        return;
-                    }
-                    if let Err(e) = mysql::insert_friend_table(&crawl_res.0, &dbpool).await {
-                        error!("{}", e);
-                        return;
-                    }
-                    success_friends.push(crawl_res.0);
-                    success_posts.push(crawl_res.1);
-                } else {
-                    crawl_res.0.error = true;
-                    if let Err(e) = mysql::insert_friend_table(&crawl_res.0, &dbpool).await {
-                        error!("{}", e);
-                        return;
-                    }
-                    failed_friends.push(crawl_res.0);
-                }
-            }
-
-            // outdated posts cleanup
-            affected_rows =
-                match mysql::delete_outdated_posts(fc_settings.outdate_clean, &dbpool).await {
-                    Ok(v) => v,
-                    Err(e) => {
-                        error!("清理过期文章失败:{}", e);
-                        0
-                    }
-                };
+            store::save_crawl_results(
+                &dbpool,
+                &fc_settings,
+                &post_filters,
+                all_res,
+                &hidden_friends,
+                &fully_filtered,
+                &created_at,
+            )
+            .await
         }
         "mongodb" => {
             let mongodburi = match tools::get_env_var("MONGODB_URI") {
@@ -300,72 +377,115 @@ async fn main() {
                     return;
                 }
             };
-            if let Err(e) = mongo::truncate_friend_table(&clientdb).await {
-                error!("{}", e);
-                return;
+            if let Err(e) = mongo::create_posts_text_index(&clientdb).await {
+                error!("创建全文索引失败: {}", e);
             }
-            for mut crawl_res in all_res {
-                if !crawl_res.1.is_empty() {
-                    let posts = crawl_res.1.iter().map(|post| {
-                        metadata::Posts::new(
-                            post.clone(),
-                            crawl_res.0.name.clone(),
-                            crawl_res.0.avatar.clone(),
-                            tools::strptime_to_string_ymdhms(now),
-                        )
-                    });
-                    if let Err(e) = mongo::delete_post_table(posts.clone(), &clientdb).await {
-                        error!("{}", e);
-                        return;
-                    }
-                    if let Err(e) = mongo::bulk_insert_post_table(posts, &clientdb).await {
-                        error!("{}", e);
-                        return;
-                    }
-                    if let Err(e) = mongo::insert_friend_table(&crawl_res.0, &clientdb).await {
-                        error!("{}", e);
-                        return;
-                    }
-                    success_friends.push(crawl_res.0);
-                    success_posts.push(crawl_res.1);
-                } else {
-                    crawl_res.0.error = true;
-                    if let Err(e) = mongo::insert_friend_table(&crawl_res.0, &clientdb).await {
-                        error!("{}", e);
-                        return;
-                    }
-                    failed_friends.push(crawl_res.0);
-                }
-            }
-
-            // outdated posts cleanup
-            affected_rows =
-                match mongo::delete_outdated_posts(fc_settings.outdate_clean, &clientdb).await {
-                    Ok(v) => v,
-                    Err(e) => {
-                        error!("清理过期文章失败:{}", e);
-                        0
-                    }
-                };
+            store::save_crawl_results(
+                &clientdb,
+                &fc_settings,
+                &post_filters,
+                all_res,
+                &hidden_friends,
+                &fully_filtered,
+                &created_at,
+            )
+            .await
         }
         _ => return,
     };
+    let store::SavedResults {
+        previous_friends,
+        post_diff,
+        success_friends,
+        success_posts,
+        failed_friends,
+        outdated_posts_num: affected_rows,
+    } = match saved {
+        Ok(saved) => saved,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+
+    info!(
+        "本次移除文章数 {}，标题变化文章数 {}",
+        post_diff.removed_posts.len(),
+        post_diff.title_changes.len()
+    );
+    for change in &post_diff.title_changes {
+        debug!(
+            "{} 的文章标题变化: {} -> {}",
+            change.author, change.old_title, change.new_title
+        );
+    }
+    let summary = notify::CrawlSummary::new(
+        &previous_friends,
+        post_diff.new_posts,
+        &success_friends,
+        &failed_friends,
+    );
+    // 首次运行时所有文章都是新文章，不发送通知
+    if !previous_friends.is_empty() {
+        notify::dispatch(&fc_settings.webhooks, &summary).await;
+    }
 
     info!(
         "成功友链数 {}，失败友链数 {}",
         success_friends.len(),
         failed_friends.len()
     );
-    info!(
-        "本次获取总文章数 {}",
-        success_posts.iter().fold(0, |acc, x| { acc + x.len() })
-    );
+    info!("本次新增文章数 {}", summary.new_posts.len());
+    let posts_num = success_posts.iter().fold(0, |acc, x| acc + x.len());
+    info!("本次获取总文章数 {}", posts_num);
     info!(
         "清理过期文章(距今超过{}天) {} 条",
         fc_settings.outdate_clean, affected_rows
     );
+    let mut failure_counts: Vec<(FailureKind, usize)> = Vec::new();
+    for friend in &failed_friends {
+        let kind = friend.error_kind.unwrap_or(FailureKind::Other);
+        match failure_counts.iter_mut().find(|(k, _)| *k == kind) {
+            Some((_, count)) => *count += 1,
+            None => failure_counts.push((kind, 1)),
+        }
+    }
+    failure_counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    info!(
+        "失联原因统计 {}",
+        failure_counts
+            .iter()
+            .map(|(kind, count)| format!("{kind}: {count}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
     info!(
         "失联友链明细 {}",
         serde_json::to_string_pretty(&failed_friends).unwrap()
     );
+    for site_move in site_moves {
+        info!(
+            "{}重定向友链 {} -> {}",
+            if site_move.permanent {
+                "永久"
+            } else {
+                "临时"
+            },
+            site_move.from,
+            site_move.to
+        );
+    }
+    if let Some(handle) = &metrics_handle {
+        crawl_metrics::record_run(
+            start.elapsed(),
+            &success_friends,
+            &failed_friends,
+            posts_num,
+            summary.new_posts.len(),
+        );
+        if let Err(e) = crawl_metrics::write_crawl_record(handle, &fc_settings.metrics.crawl_record)
+        {
+            error!("写入抓取指标失败: {}", e);
+        }
+    }
 }
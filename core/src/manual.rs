@@ -0,0 +1,351 @@
+//! 通过管理接口添加的友链、屏蔽站点和友链覆盖设置
+//!
+//! 友链表每次运行都会清空，手动添加的友链保存在单独的表中，
+//! 运行前读取并合并到配置中，与配置项友链一样抓取
+
+use data_structures::config::Settings;
+use data_structures::metadata::{BlockedSite, FriendOverride, Friends, ManualFriend};
+use db::{mongo, mysql, sqlite};
+use downloader::block::BlockSites;
+use std::collections::{HashMap, HashSet};
+use tracing::{error, info};
+
+#[derive(Debug, Default)]
+pub struct ManualEntries {
+    pub friends: Vec<ManualFriend>,
+    pub blocked_sites: Vec<BlockedSite>,
+    pub overrides: Vec<FriendOverride>,
+}
+
+/// 读取手动添加的友链、屏蔽站点和友链覆盖设置，读取失败时视为没有
+pub async fn load_manual_entries(fc_settings: &Settings) -> ManualEntries {
+    match fc_settings.database.as_str() {
+        "sqlite" => {
+            let dbpool = match sqlite::connect_sqlite_dbpool("data.db").await {
+                Ok(dbpool) => dbpool,
+                Err(e) => {
+                    error!("{}", e);
+                    return ManualEntries::default();
+                }
+            };
+            if let Err(e) = sqlx::migrate!("../db/schema/sqlite").run(&dbpool).await {
+                error!("{}", e);
+                return ManualEntries::default();
+            }
+            log_errors(
+                sqlite::select_all_from_manual_friends(&dbpool).await,
+                sqlite::select_all_from_blocked_sites(&dbpool).await,
+                sqlite::select_all_from_friend_overrides(&dbpool).await,
+            )
+        }
+        "mysql" => {
+            let dbpool = match tools::get_env_var("MYSQL_URI") {
+                Ok(mysqlconnstr) => mysql::connect_mysql_dbpool(&mysqlconnstr).await,
+                Err(e) => {
+                    error!("{}", e);
+                    return ManualEntries::default();
+                }
+            };
+            let dbpool = match dbpool {
+                Ok(dbpool) => dbpool,
+                Err(e) => {
+                    error!("{}", e);
+                    return ManualEntries::default();
+                }
+            };
+            if let Err(e) = sqlx::migrate!("../db/schema/mysql").run(&dbpool).await {
+                error!("{}", e);
+                return ManualEntries::default();
+            }
+            log_errors(
+                mysql::select_all_from_manual_friends(&dbpool).await,
+                mysql::select_all_from_blocked_sites(&dbpool).await,
+                mysql::select_all_from_friend_overrides(&dbpool).await,
+            )
+        }
+        "mongodb" => {
+            let clientdb = match tools::get_env_var("MONGODB_URI") {
+                Ok(mongodburi) => mongo::connect_mongodb_clientdb(&mongodburi).await,
+                Err(e) => {
+                    error!("{}", e);
+                    return ManualEntries::default();
+                }
+            };
+            let clientdb = match clientdb {
+                Ok(clientdb) => clientdb,
+                Err(e) => {
+                    error!("{}", e);
+                    return ManualEntries::default();
+                }
+            };
+            log_errors(
+                mongo::select_all_from_manual_friends(&clientdb).await,
+                mongo::select_all_from_blocked_sites(&clientdb).await,
+                mongo::select_all_from_friend_overrides(&clientdb).await,
+            )
+        }
+        _ => ManualEntries::default(),
+    }
+}
+
+fn log_errors<E: std::fmt::Display>(
+    friends: Result<Vec<ManualFriend>, E>,
+    blocked_sites: Result<Vec<BlockedSite>, E>,
+    overrides: Result<Vec<FriendOverride>, E>,
+) -> ManualEntries {
+    ManualEntries {
+        friends: friends.unwrap_or_else(|e| {
+            error!("读取手动添加的友链失败: {}", e);
+            vec![]
+        }),
+        blocked_sites: blocked_sites.unwrap_or_else(|e| {
+            error!("读取屏蔽站点失败: {}", e);
+            vec![]
+        }),
+        overrides: overrides.unwrap_or_else(|e| {
+            error!("读取友链覆盖设置失败: {}", e);
+            vec![]
+        }),
+    }
+}
+
+/// 在编译好的`BLOCK_SITE`后追加通过管理接口添加的屏蔽站点
+///
+/// 与配置文件重复的规则不再追加，无效或超出大小限制的规则记录日志后跳过
+pub fn extend_block_sites(
+    block_sites: &mut BlockSites,
+    fc_settings: &Settings,
+    sites: Vec<BlockedSite>,
+) {
+    let patterns: Vec<String> = sites
+        .into_iter()
+        .map(|site| site.pattern)
+        .filter(|pattern| !fc_settings.block_site.contains(pattern))
+        .collect();
+    for (pattern, e) in block_sites.extend(&patterns) {
+        error!("跳过无效的屏蔽站点`{}`: {}", pattern, e);
+    }
+}
+
+/// 将手动添加的友链合并到`SETTINGS_FRIENDS_LINKS`
+///
+/// 与配置项友链重复的手动友链不再添加
+pub fn merge_manual_entries(fc_settings: &mut Settings, friends: Vec<ManualFriend>) {
+    if friends.is_empty() {
+        return;
+    }
+    let settings_friends_links = &mut fc_settings.settings_friends_links;
+    // 未开启配置项友链时，配置文件中的列表不生效
+    if !settings_friends_links.enable {
+        settings_friends_links.list.clear();
+        settings_friends_links.enable = true;
+    }
+    let mut reserved_links: HashSet<String> = settings_friends_links
+        .list
+        .iter()
+        .filter_map(|postpage_vec| postpage_vec.get(1))
+        .map(|link| downloader::dedup_key(link))
+        .collect();
+    let mut added = 0;
+    for friend in friends {
+        if !reserved_links.insert(downloader::dedup_key(&friend.link)) {
+            continue;
+        }
+        let mut postpage_vec = vec![friend.name, friend.link, friend.avatar];
+        if !friend.suffix.is_empty() {
+            postpage_vec.push(friend.suffix);
+        }
+        settings_friends_links.list.push(postpage_vec);
+        added += 1;
+    }
+    info!("合并手动添加的友链 {} 个", added);
+}
+
+/// 按友链地址查找覆盖设置，地址按[`downloader::dedup_key`]比较
+#[derive(Debug, Default)]
+pub struct FriendOverrides(HashMap<String, FriendOverride>);
+
+impl FriendOverrides {
+    pub fn new(overrides: Vec<FriendOverride>) -> FriendOverrides {
+        FriendOverrides(
+            overrides
+                .into_iter()
+                .map(|friend_override| {
+                    (
+                        downloader::dedup_key(&friend_override.link),
+                        friend_override,
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    pub fn get(&self, link: &str) -> Option<&FriendOverride> {
+        self.0.get(&downloader::dedup_key(link))
+    }
+}
+
+/// 覆盖友链的名称和置顶状态，返回抓取文章页使用的订阅后缀和css规则
+///
+/// `theme`在`post_page_rules`中不存在时忽略
+pub fn apply_override(
+    friend: &mut Friends,
+    friend_override: Option<&FriendOverride>,
+    feed_suffix: String,
+    css_rules: &tools::Value,
+) -> (String, tools::Value) {
+    let Some(friend_override) = friend_override else {
+        return (feed_suffix, css_rules.clone());
+    };
+    if !friend_override.name.is_empty() {
+        friend.name = friend_override.name.clone();
+    }
+    friend.pinned = friend_override.pinned;
+    let feed_suffix = if friend_override.feed.is_empty() {
+        feed_suffix
+    } else {
+        friend_override.feed.clone()
+    };
+    let mut css_rules = css_rules.clone();
+    if !friend_override.theme.is_empty() {
+        let theme_rules = css_rules["post_page_rules"]
+            .get(friend_override.theme.as_str())
+            .cloned();
+        match theme_rules {
+            Some(theme_rules) => {
+                let mut post_page_rules = tools::Value::Mapping(Default::default());
+                post_page_rules[friend_override.theme.as_str()] = theme_rules;
+                css_rules["post_page_rules"] = post_page_rules;
+            }
+            None => error!(
+                "友链 {} 指定的主题 {} 不存在，已忽略",
+                friend.link, friend_override.theme
+            ),
+        }
+    }
+    (feed_suffix, css_rules)
+}
+
+/// 友链保留的文章数，覆盖设置中不大于0时使用`MAX_POSTS_NUM`
+pub fn max_posts(friend_override: Option<&FriendOverride>, max_posts_num: usize) -> usize {
+    match friend_override {
+        Some(friend_override) if friend_override.max_posts > 0 => {
+            friend_override.max_posts as usize
+        }
+        _ => max_posts_num,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data_structures::testing::settings;
+
+    fn manual_friend(name: &str, link: &str, suffix: &str) -> ManualFriend {
+        ManualFriend {
+            name: name.to_string(),
+            link: link.to_string(),
+            avatar: String::new(),
+            suffix: suffix.to_string(),
+            created_at: String::new(),
+        }
+    }
+
+    fn blocked_site(pattern: &str) -> BlockedSite {
+        BlockedSite {
+            pattern: pattern.to_string(),
+            created_at: String::new(),
+        }
+    }
+
+    // 测试数据库中无效的屏蔽站点被跳过，其余规则追加到配置文件的规则之后
+    #[test]
+    fn test_extend_block_sites() {
+        let mut fc_settings = settings();
+        fc_settings.block_site = vec![String::from("host:a.example.com")];
+        let mut block_sites = BlockSites::new(&fc_settings.block_site).unwrap();
+        extend_block_sites(
+            &mut block_sites,
+            &fc_settings,
+            vec![blocked_site("("), blocked_site("host:b.example.com")],
+        );
+        assert!(block_sites.is_blocked("https://a.example.com/"));
+        assert!(block_sites.is_blocked("https://b.example.com/"));
+    }
+
+    // 测试合并手动友链，跳过与配置项重复的友链
+    #[test]
+    fn test_merge_manual_entries() {
+        let mut fc_settings = settings();
+        fc_settings.settings_friends_links.list = vec![vec![
+            String::from("配置项"),
+            String::from("https://configured.example.com/"),
+            String::new(),
+        ]];
+
+        merge_manual_entries(
+            &mut fc_settings,
+            vec![
+                manual_friend("手动1", "https://manual.example.com/", "atom.xml"),
+                manual_friend("手动2", "https://www.manual.example.com", ""),
+            ],
+        );
+
+        assert!(fc_settings.settings_friends_links.enable);
+        // 未开启时配置文件中的列表被清空，重复的手动友链只保留第一个
+        assert_eq!(
+            fc_settings.settings_friends_links.list,
+            vec![vec![
+                String::from("手动1"),
+                String::from("https://manual.example.com/"),
+                String::new(),
+                String::from("atom.xml"),
+            ]]
+        );
+    }
+
+    // 测试覆盖名称、置顶、订阅地址，以及只保留指定主题的css规则
+    #[test]
+    fn test_apply_override() {
+        let css_rules = tools::get_yaml("../css_rules.yaml").unwrap();
+        let overrides = FriendOverrides::new(vec![FriendOverride {
+            link: String::from("https://www.b.example.com"),
+            name: String::from("新名称"),
+            feed: String::from("https://b.example.com/custom.xml"),
+            theme: String::from("butterfly"),
+            hidden: false,
+            pinned: true,
+            max_posts: 5,
+            created_at: String::new(),
+        }]);
+        let mut friend = Friends::new(
+            String::from("b"),
+            String::from("https://b.example.com/"),
+            String::new(),
+            false,
+            String::new(),
+        );
+
+        let (feed_suffix, friend_css_rules) = apply_override(
+            &mut friend,
+            overrides.get("https://b.example.com/"),
+            String::new(),
+            &css_rules,
+        );
+        assert_eq!(friend.name, "新名称");
+        assert!(friend.pinned);
+        assert_eq!(feed_suffix, "https://b.example.com/custom.xml");
+        let themes = friend_css_rules["post_page_rules"].as_mapping().unwrap();
+        assert_eq!(themes.len(), 1);
+        assert!(themes.contains_key("butterfly"));
+        // 链接页规则不受影响
+        assert_eq!(
+            friend_css_rules["link_page_rules"],
+            css_rules["link_page_rules"]
+        );
+
+        assert_eq!(max_posts(overrides.get("https://b.example.com/"), 25), 5);
+        assert!(overrides.get("https://c.example.com/").is_none());
+        assert_eq!(max_posts(None, 25), 25);
+    }
+}
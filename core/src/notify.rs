@@ -0,0 +1,329 @@
+//! 运行结束后的通知
+
+use crate::diff::PostBrief;
+use chrono::Utc;
+use data_structures::config::{EmailWebhook, Webhook};
+use data_structures::metadata::{FailureKind, Friends};
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::error::Error;
+use std::time::Duration;
+use tools::resolve_secret;
+use tracing::{error, info};
+
+/// 文本通知中最多列出的文章数
+const TEXT_MAX_POSTS: usize = 20;
+/// Discord单条消息的长度上限
+const DISCORD_MAX_CHARS: usize = 2000;
+/// Telegram单条消息的长度上限
+const TELEGRAM_MAX_CHARS: usize = 4096;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FriendChange {
+    pub name: String,
+    pub link: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<FailureKind>,
+}
+
+impl From<&Friends> for FriendChange {
+    fn from(friend: &Friends) -> Self {
+        FriendChange {
+            name: friend.name.clone(),
+            link: friend.link.clone(),
+            error_kind: friend.error_kind,
+        }
+    }
+}
+
+/// 本次运行的汇总
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CrawlSummary {
+    pub time: String,
+    pub success_friends_num: usize,
+    pub failed_friends_num: usize,
+    /// 上次运行时不存在的文章
+    pub new_posts: Vec<PostBrief>,
+    /// 上次运行时正常，本次失联的友链
+    pub lost_friends: Vec<FriendChange>,
+    /// 上次运行时失联，本次恢复的友链
+    pub recovered_friends: Vec<FriendChange>,
+}
+
+impl CrawlSummary {
+    /// `previous_friends`为写入数据库前查询到的上次运行结果，`new_posts`来自`PostDiff`
+    pub fn new(
+        previous_friends: &[Friends],
+        new_posts: Vec<PostBrief>,
+        success_friends: &[Friends],
+        failed_friends: &[Friends],
+    ) -> CrawlSummary {
+        let previous_error: HashMap<&str, bool> = previous_friends
+            .iter()
+            .map(|friend| (friend.link.as_str(), friend.error))
+            .collect();
+        let lost_friends = failed_friends
+            .iter()
+            .filter(|friend| previous_error.get(friend.link.as_str()) == Some(&false))
+            .map(FriendChange::from)
+            .collect();
+        let recovered_friends = success_friends
+            .iter()
+            .filter(|friend| previous_error.get(friend.link.as_str()) == Some(&true))
+            .map(FriendChange::from)
+            .collect();
+        CrawlSummary {
+            time: tools::strptime_to_string_ymdhms(
+                Utc::now().with_timezone(&downloader::BEIJING_OFFSET.unwrap()),
+            ),
+            success_friends_num: success_friends.len(),
+            failed_friends_num: failed_friends.len(),
+            new_posts,
+            lost_friends,
+            recovered_friends,
+        }
+    }
+
+    /// 没有新文章，也没有友链状态变化
+    pub fn is_empty(&self) -> bool {
+        self.new_posts.is_empty()
+            && self.lost_friends.is_empty()
+            && self.recovered_friends.is_empty()
+    }
+
+    pub fn subject(&self) -> String {
+        format!(
+            "友链朋友圈：{}篇新文章，{}个失联，{}个恢复",
+            self.new_posts.len(),
+            self.lost_friends.len(),
+            self.recovered_friends.len()
+        )
+    }
+
+    /// 用于Telegram、Discord和邮件的纯文本
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![
+            self.subject(),
+            format!(
+                "成功友链数 {}，失败友链数 {}",
+                self.success_friends_num, self.failed_friends_num
+            ),
+        ];
+        if !self.new_posts.is_empty() {
+            lines.push(String::new());
+            lines.push(String::from("新文章："));
+            for post in self.new_posts.iter().take(TEXT_MAX_POSTS) {
+                lines.push(format!("- {}：{} {}", post.author, post.title, post.link));
+            }
+            if self.new_posts.len() > TEXT_MAX_POSTS {
+                lines.push(format!("……等共{}篇", self.new_posts.len()));
+            }
+        }
+        for (title, friends) in [
+            ("新失联友链：", &self.lost_friends),
+            ("恢复的友链：", &self.recovered_friends),
+        ] {
+            if friends.is_empty() {
+                continue;
+            }
+            lines.push(String::new());
+            lines.push(String::from(title));
+            for friend in friends {
+                match friend.error_kind {
+                    Some(kind) => {
+                        lines.push(format!("- {} {}（{}）", friend.name, friend.link, kind))
+                    }
+                    None => lines.push(format!("- {} {}", friend.name, friend.link)),
+                }
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+async fn post_json(
+    client: &reqwest::Client,
+    url: &str,
+    body: &serde_json::Value,
+) -> Result<(), Box<dyn Error>> {
+    // 错误信息中去除url，避免其中的令牌（如Telegram的bot token）写入日志
+    client
+        .post(resolve_secret(url)?)
+        .json(body)
+        .send()
+        .await
+        .map_err(|e| e.without_url())?
+        .error_for_status()
+        .map_err(|e| e.without_url())?;
+    Ok(())
+}
+
+async fn send_email(webhook: &EmailWebhook, summary: &CrawlSummary) -> Result<(), Box<dyn Error>> {
+    let mut builder = Message::builder()
+        .from(resolve_secret(&webhook.from)?.parse()?)
+        .subject(summary.subject())
+        .header(ContentType::TEXT_PLAIN);
+    for to in &webhook.to {
+        builder = builder.to(resolve_secret(to)?.parse()?);
+    }
+    let message = builder.body(summary.to_text())?;
+
+    let smtp_host = resolve_secret(&webhook.smtp_host)?;
+    let transport = if webhook.starttls {
+        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&smtp_host)?
+    } else {
+        AsyncSmtpTransport::<Tokio1Executor>::relay(&smtp_host)?
+    };
+    let transport = transport
+        .port(webhook.smtp_port)
+        .credentials(Credentials::new(
+            resolve_secret(&webhook.username)?,
+            resolve_secret(&webhook.password)?,
+        ))
+        .build();
+    transport.send(message).await?;
+    Ok(())
+}
+
+async fn send(
+    client: &reqwest::Client,
+    webhook: &Webhook,
+    summary: &CrawlSummary,
+) -> Result<(), Box<dyn Error>> {
+    match webhook {
+        Webhook::Json { url } => post_json(client, url, &serde_json::to_value(summary)?).await,
+        Webhook::Telegram { bot_token, chat_id } => {
+            let url = format!(
+                "https://api.telegram.org/bot{}/sendMessage",
+                resolve_secret(bot_token)?
+            );
+            let text: String = summary.to_text().chars().take(TELEGRAM_MAX_CHARS).collect();
+            let body = json!({
+                "chat_id": resolve_secret(chat_id)?,
+                "text": text,
+                "disable_web_page_preview": true,
+            });
+            post_json(client, &url, &body).await
+        }
+        Webhook::Discord { url } => {
+            let content: String = summary.to_text().chars().take(DISCORD_MAX_CHARS).collect();
+            post_json(client, url, &json!({ "content": content })).await
+        }
+        Webhook::Email(webhook) => send_email(webhook, summary).await,
+    }
+}
+
+/// 向所有配置的webhook发送汇总，没有变化时不发送
+pub async fn dispatch(webhooks: &[Webhook], summary: &CrawlSummary) {
+    if webhooks.is_empty() || summary.is_empty() {
+        return;
+    }
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::new(20, 0))
+        .build()
+    {
+        Ok(v) => v,
+        Err(e) => {
+            error!("创建通知客户端失败: {}", e);
+            return;
+        }
+    };
+    for webhook in webhooks {
+        match send(&client, webhook, summary).await {
+            Ok(()) => info!("通知发送成功: {}", webhook_type(webhook)),
+            Err(e) => error!("通知发送失败: {}, {}", webhook_type(webhook), e),
+        }
+    }
+}
+
+fn webhook_type(webhook: &Webhook) -> &'static str {
+    match webhook {
+        Webhook::Json { .. } => "json",
+        Webhook::Telegram { .. } => "telegram",
+        Webhook::Discord { .. } => "discord",
+        Webhook::Email(_) => "email",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::PostDiff;
+    use data_structures::testing::{base_post, friend};
+
+    // 测试运行汇总
+    #[test]
+    fn test_crawl_summary() {
+        let previous_friends = vec![
+            friend("a"),
+            Friends {
+                error: true,
+                ..friend("b")
+            },
+            friend("c"),
+        ];
+        let success_friends = vec![friend("a"), friend("b")];
+        let new_posts = PostDiff::new(
+            &[],
+            &[
+                (
+                    friend("a"),
+                    vec![base_post("标题", "https://a.example.com/2/", "2024-06-01")],
+                ),
+                (
+                    friend("b"),
+                    vec![base_post("标题", "https://b.example.com/1/", "2024-06-01")],
+                ),
+            ],
+        )
+        .new_posts;
+        let failed_friends = vec![
+            Friends {
+                error: true,
+                ..friend("c")
+            },
+            Friends {
+                error: true,
+                ..friend("d")
+            },
+        ];
+
+        let summary = CrawlSummary::new(
+            &previous_friends,
+            new_posts,
+            &success_friends,
+            &failed_friends,
+        );
+        assert_eq!(summary.new_posts.len(), 2);
+        assert_eq!(summary.new_posts[1].author, "b");
+        // d是新友链，不算新失联
+        assert_eq!(summary.lost_friends.len(), 1);
+        assert_eq!(summary.lost_friends[0].name, "c");
+        assert_eq!(summary.recovered_friends.len(), 1);
+        assert_eq!(summary.recovered_friends[0].name, "b");
+        assert!(!summary.is_empty());
+        assert!(
+            summary
+                .to_text()
+                .contains("- b：标题 https://b.example.com/1/")
+        );
+    }
+
+    // 测试发送失败时错误信息中不包含url里的令牌
+    #[tokio::test]
+    async fn test_post_json_error_without_url() {
+        let client = reqwest::Client::new();
+        let err = post_json(
+            &client,
+            "http://127.0.0.1:1/bot123456:SECRET/sendMessage",
+            &json!({}),
+        )
+        .await
+        .unwrap_err();
+        assert!(!err.to_string().contains("SECRET"));
+    }
+}
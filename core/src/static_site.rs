@@ -0,0 +1,338 @@
+//! 极简模式的静态站点
+//!
+//! 按api的路径和返回格式生成json文件，静态托管（如GitHub Pages）后不需要运行api也能使用：
+//! - `all.json`和`all/{页码}.json`：分页的文章数据，对应`/all`
+//! - `friend.json`：所有友链，对应`/friend`
+//! - `post/{host}.json`：某个友链的文章，对应`/post?link=`
+//! - `randomfriend.json`、`randompost.json`：随机池，由前端从中随机选取
+//! - `atom.xml`：最新文章的Atom订阅
+//!
+//! `all/`和`post/`目录由这里管理，上次运行遗留的多余文件会被删除
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Utc};
+use data_structures::config::{Settings, SimpleModeSettings};
+use data_structures::metadata::{Friends, Posts};
+use data_structures::response::{AllPostData, AllPostDataSomeFriend, Pagination};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+use url::Url;
+
+/// 统计(活跃, 不活跃, 失联)友链数
+pub fn count_friends(friends: &[Friends], dormant_before: Option<&str>) -> (usize, usize, usize) {
+    let mut active_num = 0;
+    let mut dormant_num = 0;
+    let mut lost_num = 0;
+    for friend in friends {
+        if friend.error {
+            lost_num += 1;
+        } else if matches!((friend.latest_post.as_deref(), dormant_before), (Some(latest), Some(before)) if latest < before)
+        {
+            dormant_num += 1;
+        } else {
+            active_num += 1;
+        }
+    }
+    (active_num, dormant_num, lost_num)
+}
+
+fn write_json<T: Serialize>(path: &Path, data: &T) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = fs::File::create(path)?;
+    serde_json::to_writer(file, data)?;
+    Ok(())
+}
+
+/// 删除`dir`中本次没有写入的json文件，即文章或友链减少后遗留的分页和友链文件
+fn remove_stale_files(dir: &Path, written: &HashSet<PathBuf>) -> Result<usize, Box<dyn Error>> {
+    let mut removed = 0;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file()
+            && path.extension().is_some_and(|ext| ext == "json")
+            && !written.contains(&path)
+        {
+            fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// 将`%Y-%m-%d`或`%Y-%m-%d %H:%M:%S`格式的北京时间转换为RFC 3339，无法解析时返回None
+fn to_rfc3339(time: &str) -> Option<String> {
+    let naive = NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(time, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })?;
+    let offset = FixedOffset::east_opt(8 * 60 * 60)?;
+    let time: DateTime<FixedOffset> = naive.and_local_timezone(offset).single()?;
+    Some(time.to_rfc3339())
+}
+
+/// 生成Atom订阅，`posts`应已按时间倒序
+fn atom_feed(settings: &SimpleModeSettings, posts: &[Posts], updated: &str) -> String {
+    let site_url = settings.site_url.trim_end_matches('/');
+    let now = Utc::now().to_rfc3339();
+    let mut feed = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    feed.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    feed.push_str(&format!(
+        "  <title>{}</title>\n",
+        escape_xml(&settings.feed_title)
+    ));
+    if site_url.is_empty() {
+        feed.push_str("  <id>urn:fcircle:feed</id>\n");
+    } else {
+        feed.push_str(&format!("  <id>{}/atom.xml</id>\n", escape_xml(site_url)));
+        feed.push_str(&format!(
+            "  <link rel=\"self\" href=\"{}/atom.xml\"/>\n",
+            escape_xml(site_url)
+        ));
+    }
+    feed.push_str(&format!(
+        "  <updated>{}</updated>\n",
+        to_rfc3339(updated).unwrap_or_else(|| now.clone())
+    ));
+    let feed_size = if settings.feed_size == 0 {
+        posts.len()
+    } else {
+        settings.feed_size
+    };
+    for post in posts.iter().take(feed_size) {
+        let link = escape_xml(&post.meta.link);
+        let published = to_rfc3339(&post.meta.created).unwrap_or_else(|| now.clone());
+        let updated = to_rfc3339(&post.meta.updated).unwrap_or_else(|| published.clone());
+        feed.push_str("  <entry>\n");
+        feed.push_str(&format!(
+            "    <title>{}</title>\n",
+            escape_xml(&post.meta.title)
+        ));
+        feed.push_str(&format!("    <link href=\"{link}\"/>\n"));
+        feed.push_str(&format!("    <id>{link}</id>\n"));
+        feed.push_str(&format!(
+            "    <author><name>{}</name></author>\n",
+            escape_xml(&post.author)
+        ));
+        feed.push_str(&format!("    <published>{published}</published>\n"));
+        feed.push_str(&format!("    <updated>{updated}</updated>\n"));
+        if let Some(summary) = &post.meta.summary {
+            feed.push_str(&format!("    <summary>{}</summary>\n", escape_xml(summary)));
+        }
+        for tag in post.meta.tags.iter() {
+            feed.push_str(&format!("    <category term=\"{}\"/>\n", escape_xml(tag)));
+        }
+        feed.push_str("  </entry>\n");
+    }
+    feed.push_str("</feed>\n");
+    feed
+}
+
+/// 生成静态站点，返回写入的文件数
+///
+/// `posts`应已按`updated`倒序，与`/all`的默认排序一致
+pub fn write_static_site(
+    fc_settings: &Settings,
+    friends: Vec<Friends>,
+    posts: Vec<Posts>,
+    last_updated_time: String,
+) -> Result<usize, Box<dyn Error>> {
+    let settings = &fc_settings.simple_mode_output;
+    let output_dir = Path::new(&settings.output_dir);
+    let mut written = 0;
+    // `all/`和`post/`中本次写入的文件
+    let mut written_paths = HashSet::new();
+
+    let dormant_before = fc_settings.friend_activity().dormant_before();
+    let (active_num, dormant_num, lost_num) = count_friends(&friends, dormant_before.as_deref());
+
+    // 分页的文章数据，第1页同时写入all.json
+    let total = posts.len();
+    let page_size = if settings.page_size == 0 {
+        total.max(1)
+    } else {
+        settings.page_size
+    };
+    let page_num = total.div_ceil(page_size).max(1);
+    for page in 1..=page_num {
+        let offset = (page - 1) * page_size;
+        let page_posts: Vec<Posts> = posts.iter().skip(offset).take(page_size).cloned().collect();
+        let data = AllPostData::new(
+            friends.len(),
+            active_num,
+            dormant_num,
+            lost_num,
+            last_updated_time.clone(),
+            page_posts,
+            Pagination::new(total, offset, settings.page_size),
+        );
+        if page == 1 {
+            write_json(&output_dir.join("all.json"), &data)?;
+            written += 1;
+        }
+        let path = output_dir.join("all").join(format!("{page}.json"));
+        write_json(&path, &data)?;
+        written_paths.insert(path);
+        written += 1;
+    }
+
+    write_json(&output_dir.join("friend.json"), &friends)?;
+    write_json(&output_dir.join("randomfriend.json"), &friends)?;
+    write_json(&output_dir.join("randompost.json"), &posts)?;
+    written += 3;
+
+    // 每个友链的文章，按host命名
+    let mut hosts = HashSet::new();
+    for friend in &friends {
+        let host = match Url::parse(&friend.link)
+            .ok()
+            .and_then(|url| url.host_str().map(String::from))
+        {
+            Some(host) => host,
+            None => {
+                warn!("友链 {} 的地址无法解析出host: {}", friend.name, friend.link);
+                continue;
+            }
+        };
+        if !hosts.insert(host.clone()) {
+            warn!("友链 {} 与其他友链的host相同，跳过: {}", friend.name, host);
+            continue;
+        }
+        let mut friend_posts: Vec<Posts> = posts
+            .iter()
+            .filter(|post| post.author == friend.name)
+            .cloned()
+            .collect();
+        friend_posts.sort_by(|a, b| b.meta.created.cmp(&a.meta.created));
+        let data = AllPostDataSomeFriend::new(
+            friend.name.clone(),
+            friend.link.clone(),
+            friend.avatar.clone(),
+            friend_posts.len(),
+            friend_posts,
+            0,
+        );
+        let path = output_dir.join("post").join(format!("{host}.json"));
+        write_json(&path, &data)?;
+        written_paths.insert(path);
+        written += 1;
+    }
+    let mut removed = 0;
+    for dir in ["all", "post"] {
+        let dir = output_dir.join(dir);
+        if dir.is_dir() {
+            removed += remove_stale_files(&dir, &written_paths)?;
+        }
+    }
+    if removed > 0 {
+        info!("删除上次运行遗留的静态文件 {} 个", removed);
+    }
+
+    let mut feed_posts = posts;
+    feed_posts.sort_by(|a, b| b.meta.created.cmp(&a.meta.created));
+    fs::write(
+        output_dir.join("atom.xml"),
+        atom_feed(settings, &feed_posts, &last_updated_time),
+    )?;
+    written += 1;
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data_structures::testing::{self, base_post, friend, post};
+
+    // 测试Atom订阅的转义和时间格式
+    #[test]
+    fn test_atom_feed() {
+        let settings = SimpleModeSettings {
+            site_url: String::from("https://example.com/"),
+            ..Default::default()
+        };
+        let posts = vec![post(
+            base_post("a<b>&c", "https://alice.example.com/a<b>&c/", "2024-06-01"),
+            "alice",
+        )];
+        let feed = atom_feed(&settings, &posts, "2024-06-02 08:00:00");
+        assert!(feed.contains("<title>a&lt;b&gt;&amp;c</title>"));
+        assert!(feed.contains("<published>2024-06-01T00:00:00+08:00</published>"));
+        assert!(feed.contains("<updated>2024-06-02T08:00:00+08:00</updated>"));
+        assert!(feed.contains("href=\"https://example.com/atom.xml\""));
+    }
+
+    // 测试静态站点的文件结构，以及删除遗留的分页和友链文件
+    #[test]
+    fn test_write_static_site() {
+        let output_dir = testing::temp_dir("static_site");
+        let mut fc_settings = testing::settings();
+        fc_settings.simple_mode_output.output_dir = output_dir.to_string_lossy().to_string();
+        fc_settings.simple_mode_output.page_size = 2;
+
+        let friends = vec![friend("alice"), friend("bob")];
+        let posts = vec![
+            post(
+                base_post("3", "https://alice.example.com/3/", "2024-06-03"),
+                "alice",
+            ),
+            post(
+                base_post("2", "https://bob.example.com/2/", "2024-06-02"),
+                "bob",
+            ),
+            post(
+                base_post("1", "https://alice.example.com/1/", "2024-06-01"),
+                "alice",
+            ),
+        ];
+        // all.json + 2页 + friend/randomfriend/randompost + 2个post + atom.xml
+        let written = write_static_site(
+            &fc_settings,
+            friends.clone(),
+            posts.clone(),
+            String::from("2024-06-03"),
+        )
+        .unwrap();
+        assert_eq!(written, 9);
+
+        let page2: serde_json::Value =
+            serde_json::from_slice(&fs::read(output_dir.join("all/2.json")).unwrap()).unwrap();
+        assert_eq!(page2["pagination"]["offset"], 2);
+        assert_eq!(page2["article_data"].as_array().unwrap().len(), 1);
+        let alice: serde_json::Value = serde_json::from_slice(
+            &fs::read(output_dir.join("post/alice.example.com.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(alice["statistical_data"]["article_num"], 2);
+        assert!(output_dir.join("atom.xml").exists());
+
+        // 文章和友链减少后，多余的分页和友链文件被删除
+        write_static_site(
+            &fc_settings,
+            friends[..1].to_vec(),
+            posts[..2].to_vec(),
+            String::from("2024-06-03"),
+        )
+        .unwrap();
+        assert!(output_dir.join("all/1.json").exists());
+        assert!(!output_dir.join("all/2.json").exists());
+        assert!(output_dir.join("post/alice.example.com.json").exists());
+        assert!(!output_dir.join("post/bob.example.com.json").exists());
+        fs::remove_dir_all(&output_dir).unwrap();
+    }
+}
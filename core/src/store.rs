@@ -0,0 +1,346 @@
+//! 写入本次运行的结果
+//!
+//! 三种数据库的写入流程相同，[`Store`]把各数据库的函数包装为同一套接口，
+//! 写入顺序和日志只在[`save_crawl_results`]中维护一份
+
+use crate::diff::PostDiff;
+use crate::filter::PostFilters;
+use data_structures::config::{PostSortBy, Settings};
+use data_structures::metadata::{BasePosts, Friends, Posts};
+use db::{MongoDatabase, MySqlPool, SqlitePool, mongo, mysql, sqlite};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use tracing::{error, info};
+
+type StoreResult<T> = Result<T, Box<dyn Error>>;
+
+/// 写入流程用到的数据库操作，与`db`中的同名函数一一对应
+pub trait Store {
+    async fn select_all_from_friends(&self) -> StoreResult<Vec<Friends>>;
+    /// 查询所有文章，按`updated`排序
+    async fn select_all_from_posts(&self) -> StoreResult<Vec<Posts>>;
+    async fn select_latest_time_from_posts(&self) -> StoreResult<String>;
+    async fn delete_post_table(&self, posts: Vec<Posts>) -> StoreResult<()>;
+    async fn bulk_insert_post_table(&self, posts: Vec<Posts>) -> StoreResult<()>;
+    async fn delete_friend_posts(&self, link: &str, author: &str) -> StoreResult<usize>;
+    async fn insert_friend_table(&self, friend: &Friends) -> StoreResult<()>;
+    async fn truncate_friend_table(&self) -> StoreResult<()>;
+    async fn delete_excess_posts(
+        &self,
+        max_total: usize,
+        sort_by: PostSortBy,
+    ) -> StoreResult<usize>;
+    async fn delete_outdated_posts(&self, days: usize) -> StoreResult<usize>;
+}
+
+macro_rules! impl_store {
+    ($pool:ty, $db:ident) => {
+        // mongo的部分函数已经返回`Box<dyn Error>`，统一转换错误类型
+        #[allow(clippy::needless_question_mark)]
+        impl Store for $pool {
+            async fn select_all_from_friends(&self) -> StoreResult<Vec<Friends>> {
+                Ok($db::select_all_from_friends(self).await?)
+            }
+
+            async fn select_all_from_posts(&self) -> StoreResult<Vec<Posts>> {
+                Ok($db::select_all_from_posts(self, 0, 0, "updated").await?)
+            }
+
+            async fn select_latest_time_from_posts(&self) -> StoreResult<String> {
+                Ok($db::select_latest_time_from_posts(self).await?)
+            }
+
+            async fn delete_post_table(&self, posts: Vec<Posts>) -> StoreResult<()> {
+                Ok($db::delete_post_table(posts.into_iter(), self).await?)
+            }
+
+            async fn bulk_insert_post_table(&self, posts: Vec<Posts>) -> StoreResult<()> {
+                Ok($db::bulk_insert_post_table(posts.into_iter(), self).await?)
+            }
+
+            async fn delete_friend_posts(&self, link: &str, author: &str) -> StoreResult<usize> {
+                Ok($db::delete_friend_posts(link, author, self).await?)
+            }
+
+            async fn insert_friend_table(&self, friend: &Friends) -> StoreResult<()> {
+                Ok($db::insert_friend_table(friend, self).await?)
+            }
+
+            async fn truncate_friend_table(&self) -> StoreResult<()> {
+                Ok($db::truncate_friend_table(self).await?)
+            }
+
+            async fn delete_excess_posts(
+                &self,
+                max_total: usize,
+                sort_by: PostSortBy,
+            ) -> StoreResult<usize> {
+                Ok($db::delete_excess_posts(max_total, sort_by, self).await?)
+            }
+
+            async fn delete_outdated_posts(&self, days: usize) -> StoreResult<usize> {
+                Ok($db::delete_outdated_posts(days, self).await?)
+            }
+        }
+    };
+}
+
+impl_store!(SqlitePool, sqlite);
+impl_store!(MySqlPool, mysql);
+impl_store!(MongoDatabase, mongo);
+
+/// 写入后的统计，用于生成通知和日志
+pub struct SavedResults {
+    /// 上次运行的友链
+    pub previous_friends: Vec<Friends>,
+    pub post_diff: PostDiff,
+    pub success_friends: Vec<Friends>,
+    pub success_posts: Vec<Vec<BasePosts>>,
+    pub failed_friends: Vec<Friends>,
+    /// 清理的过期文章数
+    pub outdated_posts_num: usize,
+}
+
+/// 与上次的结果比较后写入友链和文章，再按文章总数上限和过期时间清理已保存的文章
+///
+/// `fully_filtered`中的友链即使没有文章也视为获取成功，`created_at`为本次写入的时间；
+/// `hidden_friends`为覆盖设置中隐藏的友链，不再写入，已保存的文章一并删除
+pub async fn save_crawl_results(
+    store: &impl Store,
+    fc_settings: &Settings,
+    post_filters: &PostFilters,
+    all_res: Vec<(Friends, Vec<BasePosts>)>,
+    hidden_friends: &[Friends],
+    fully_filtered: &HashSet<String>,
+    created_at: &str,
+) -> StoreResult<SavedResults> {
+    let previous_friends = store.select_all_from_friends().await.unwrap_or_default();
+    let mut previous_posts = store.select_all_from_posts().await.unwrap_or_default();
+    // 已保存的文章同样按当前的过滤规则清理
+    let rejected_posts = post_filters.take_rejected(&mut previous_posts);
+    let post_diff = PostDiff::new(&previous_posts, &all_res);
+    if !rejected_posts.is_empty() {
+        info!("删除不符合过滤规则的已保存文章 {} 篇", rejected_posts.len());
+        store.delete_post_table(rejected_posts).await?;
+    }
+    store.truncate_friend_table().await?;
+
+    // 上次运行时友链写入的名称，即已保存文章的作者
+    let previous_names: HashMap<String, &str> = previous_friends
+        .iter()
+        .map(|friend| (downloader::dedup_key(&friend.link), friend.name.as_str()))
+        .collect();
+    for friend in hidden_friends {
+        let author = previous_names
+            .get(&downloader::dedup_key(&friend.link))
+            .copied()
+            .unwrap_or(&friend.name);
+        let deleted = store.delete_friend_posts(&friend.link, author).await?;
+        if deleted > 0 {
+            info!("删除隐藏的友链 {} 已保存的文章 {} 篇", friend.link, deleted);
+        }
+    }
+    // 覆盖名称后文章的作者随之改变，先删除旧名称下的文章，避免新旧名称的文章同时存在
+    for (friend, _) in &all_res {
+        let old_name = previous_names.get(&downloader::dedup_key(&friend.link));
+        if let Some(&old_name) = old_name.filter(|&&old_name| old_name != friend.name) {
+            store.delete_friend_posts(&friend.link, old_name).await?;
+        }
+    }
+
+    let mut success_posts = Vec::new();
+    let mut success_friends = Vec::new();
+    let mut failed_friends = Vec::new();
+    for (mut friend, posts) in all_res {
+        if fully_filtered.contains(&friend.link) {
+            store.insert_friend_table(&friend).await?;
+            success_friends.push(friend);
+            success_posts.push(posts);
+        } else if !posts.is_empty() {
+            let stored_posts: Vec<Posts> = posts
+                .iter()
+                .map(|post| {
+                    let mut post = Posts::new(
+                        post.clone(),
+                        friend.name.clone(),
+                        friend.avatar.clone(),
+                        created_at.to_string(),
+                    );
+                    post_diff.apply_first_seen(&mut post);
+                    post
+                })
+                .collect();
+            store.delete_post_table(stored_posts.clone()).await?;
+            store.bulk_insert_post_table(stored_posts).await?;
+            store.insert_friend_table(&friend).await?;
+            success_friends.push(friend);
+            success_posts.push(posts);
+        } else {
+            friend.error = true;
+            store.insert_friend_table(&friend).await?;
+            failed_friends.push(friend);
+        }
+    }
+
+    // 之前运行保存的文章同样计入文章总数上限
+    match store
+        .delete_excess_posts(fc_settings.max_total_posts, fc_settings.post_sort_by)
+        .await
+    {
+        Ok(0) => (),
+        Ok(v) => info!("超出文章总数上限，删除已保存的较旧文章 {} 篇", v),
+        Err(e) => error!("删除超出上限的文章失败:{}", e),
+    }
+
+    // outdated posts cleanup
+    let outdated_posts_num = match store.delete_outdated_posts(fc_settings.outdate_clean).await {
+        Ok(v) => v,
+        Err(e) => {
+            error!("清理过期文章失败:{}", e);
+            0
+        }
+    };
+    if fc_settings.simple_mode {
+        let res = match tokio::try_join!(
+            store.select_all_from_friends(),
+            store.select_all_from_posts(),
+            store.select_latest_time_from_posts(),
+        ) {
+            Ok((friends, posts, last_updated_time)) => {
+                crate::write_simple_mode(fc_settings, friends, posts, last_updated_time)
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            info!("写入JSON数据失败: {}", e);
+        }
+    }
+
+    Ok(SavedResults {
+        previous_friends,
+        post_diff,
+        success_friends,
+        success_posts,
+        failed_friends,
+        outdated_posts_num,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use data_structures::testing::{base_post, friend, settings, temp_dir};
+
+    // 测试再次写入时沿用first_seen，没有获取到文章的友链记为失败
+    #[tokio::test]
+    async fn test_save_crawl_results() {
+        let dir = temp_dir("store");
+        let dbpool = sqlite::connect_sqlite_dbpool(dir.join("data.db"))
+            .await
+            .unwrap();
+        sqlx::migrate!("../db/schema/sqlite")
+            .run(&dbpool)
+            .await
+            .unwrap();
+        let fc_settings = settings();
+        let post_filters =
+            PostFilters::new(&fc_settings.post_filters, Utc::now().fixed_offset()).unwrap();
+        let today = Utc::now().format("%Y-%m-%d").to_string();
+        let hello = base_post("Hello", "https://a.example.com/hello/", &today);
+        let rust = base_post("Rust", "https://a.example.com/rust/", &today);
+        let crawl = |posts: Vec<BasePosts>| vec![(friend("a"), posts), (friend("b"), vec![])];
+
+        let saved = save_crawl_results(
+            &dbpool,
+            &fc_settings,
+            &post_filters,
+            crawl(vec![hello.clone()]),
+            &[],
+            &HashSet::new(),
+            "2024-06-01 08:00:00",
+        )
+        .await
+        .unwrap();
+        assert_eq!(saved.success_friends.len(), 1);
+        assert_eq!(saved.failed_friends.len(), 1);
+        assert!(saved.failed_friends[0].error);
+
+        let saved = save_crawl_results(
+            &dbpool,
+            &fc_settings,
+            &post_filters,
+            crawl(vec![hello, rust]),
+            &[],
+            &HashSet::new(),
+            "2024-06-02 08:00:00",
+        )
+        .await
+        .unwrap();
+        assert_eq!(saved.previous_friends.len(), 2);
+        assert_eq!(saved.post_diff.new_posts.len(), 1);
+        let posts = dbpool.select_all_from_posts().await.unwrap();
+        let first_seen = |title: &str| {
+            posts
+                .iter()
+                .find(|post| post.meta.title == title)
+                .map(|post| post.first_seen.clone())
+        };
+        assert_eq!(first_seen("Hello").as_deref(), Some("2024-06-01 08:00:00"));
+        assert_eq!(first_seen("Rust").as_deref(), Some("2024-06-02 08:00:00"));
+    }
+
+    // 测试隐藏的友链删除已保存的文章，覆盖名称的友链不保留旧名称的文章
+    #[tokio::test]
+    async fn test_save_crawl_results_hidden_and_renamed() {
+        let dir = temp_dir("store_hidden");
+        let dbpool = sqlite::connect_sqlite_dbpool(dir.join("data.db"))
+            .await
+            .unwrap();
+        sqlx::migrate!("../db/schema/sqlite")
+            .run(&dbpool)
+            .await
+            .unwrap();
+        let fc_settings = settings();
+        let post_filters =
+            PostFilters::new(&fc_settings.post_filters, Utc::now().fixed_offset()).unwrap();
+        let today = Utc::now().format("%Y-%m-%d").to_string();
+        let a_post = base_post("A", "https://a.example.com/hello/", &today);
+        let b_post = base_post("B", "https://b.example.com/hello/", &today);
+        save_crawl_results(
+            &dbpool,
+            &fc_settings,
+            &post_filters,
+            vec![
+                (friend("a"), vec![a_post.clone()]),
+                (friend("b"), vec![b_post]),
+            ],
+            &[],
+            &HashSet::new(),
+            "2024-06-01 08:00:00",
+        )
+        .await
+        .unwrap();
+
+        let mut renamed = friend("a");
+        renamed.name = String::from("新名称");
+        save_crawl_results(
+            &dbpool,
+            &fc_settings,
+            &post_filters,
+            vec![(renamed, vec![a_post])],
+            &[friend("b")],
+            &HashSet::new(),
+            "2024-06-02 08:00:00",
+        )
+        .await
+        .unwrap();
+        let posts = dbpool.select_all_from_posts().await.unwrap();
+        assert_eq!(posts.len(), 1);
+        assert_eq!(posts[0].author, "新名称");
+        assert_eq!(posts[0].first_seen, "2024-06-01 08:00:00");
+        let friends = dbpool.select_all_from_friends().await.unwrap();
+        assert_eq!(friends.len(), 1);
+        assert_eq!(friends[0].name, "新名称");
+    }
+}
@@ -1,11 +1,116 @@
 pub mod query_params {
     use serde::Deserialize;
-    #[derive(Debug, Deserialize)]
+    #[derive(Debug, Default, Deserialize)]
     pub struct AllQueryParams {
         pub start: Option<usize>,
         pub end: Option<usize>,
+        /// 页码，从1开始，需配合`limit`使用
+        pub page: Option<usize>,
+        /// 每页数量，为0或缺省时返回全部
+        pub limit: Option<usize>,
+        /// 上一次响应返回的`next_cursor`，优先级高于`page`和`start`/`end`
+        pub cursor: Option<String>,
         #[serde(rename(deserialize = "rule"))]
         pub sort_rule: Option<String>,
+        /// 以下为过滤条件，见[`PostFilter`]
+        pub author: Option<String>,
+        pub keyword: Option<String>,
+        pub created_since: Option<String>,
+        pub created_until: Option<String>,
+        pub updated_since: Option<String>,
+        pub updated_until: Option<String>,
+        pub since: Option<String>,
+    }
+
+    /// 文章过滤条件，所有条件之间为“与”的关系，为`None`的条件不参与过滤
+    ///
+    /// 日期格式与数据库中保存的一致：`created`/`updated`为`%Y-%m-%d`，
+    /// `since`对应`first_seen`，为`%Y-%m-%d %H:%M:%S`
+    #[derive(Debug, Clone, Default, PartialEq)]
+    pub struct PostFilter {
+        /// 作者名，精确匹配
+        pub author: Option<String>,
+        /// 标题关键字，模糊匹配
+        pub keyword: Option<String>,
+        pub created_since: Option<String>,
+        pub created_until: Option<String>,
+        pub updated_since: Option<String>,
+        pub updated_until: Option<String>,
+        /// 上次访问时间，只返回在此之后第一次抓取到的文章
+        pub since: Option<String>,
+    }
+
+    impl PostFilter {
+        pub fn is_empty(&self) -> bool {
+            *self == PostFilter::default()
+        }
+    }
+
+    impl AllQueryParams {
+        /// 提取过滤条件，空字符串视为未设置
+        pub fn filter(&self) -> PostFilter {
+            let non_empty = |v: &Option<String>| {
+                v.as_ref()
+                    .map(|v| v.trim().to_string())
+                    .filter(|v| !v.is_empty())
+            };
+            PostFilter {
+                author: non_empty(&self.author),
+                keyword: non_empty(&self.keyword),
+                created_since: non_empty(&self.created_since),
+                created_until: non_empty(&self.created_until),
+                updated_since: non_empty(&self.updated_since),
+                updated_until: non_empty(&self.updated_until),
+                since: non_empty(&self.since),
+            }
+        }
+
+        /// 排序规则只能为`created`或`updated`，缺省为`updated`
+        pub fn checked_sort_rule(&self) -> Result<&'static str, String> {
+            match self.sort_rule.as_deref() {
+                None | Some("updated") => Ok("updated"),
+                Some("created") => Ok("created"),
+                Some(rule) => Err(format!("无效的排序规则: {rule}")),
+            }
+        }
+
+        /// 解析分页参数，返回`(offset, limit)`，`limit`为0表示不限制数量
+        ///
+        /// 优先级：`cursor` > `page`/`limit` > `start`/`end`
+        pub fn pagination(&self) -> Result<(usize, usize), String> {
+            let limit = self.limit.unwrap_or(0);
+            if let Some(cursor) = &self.cursor {
+                let offset = super::response::Pagination::decode_cursor(cursor)
+                    .ok_or(format!("无效的cursor: {cursor}"))?;
+                return checked_range(offset, limit);
+            }
+            if let Some(page) = self.page {
+                if page == 0 {
+                    return Err(String::from("page必须从1开始"));
+                }
+                let offset = (page - 1)
+                    .checked_mul(limit)
+                    .ok_or(format!("page({page})超出范围"))?;
+                return checked_range(offset, limit);
+            }
+            let start = self.start.unwrap_or(0);
+            let end = self.end.unwrap_or(0);
+            if start == 0 && end == 0 {
+                return Ok((0, limit));
+            }
+            if start > end {
+                return Err(format!("start({start})不能大于end({end})"));
+            }
+            Ok((start, end - start))
+        }
+    }
+
+    /// `offset + limit`溢出时返回错误
+    fn checked_range(offset: usize, limit: usize) -> Result<(usize, usize), String> {
+        match offset.checked_add(limit) {
+            Some(_) => Ok((offset, limit)),
+            None => Err(format!("offset({offset})超出范围")),
+        }
     }
 
     #[derive(Debug, Deserialize)]
@@ -20,15 +125,129 @@ pub mod query_params {
     pub struct RandomQueryParams {
         pub num: Option<usize>,
     }
+
+    impl RandomQueryParams {
+        /// `/randompost`一次最多返回的文章数
+        pub const MAX_POSTS_NUM: usize = 100;
+
+        /// `/randompost`返回的文章数，缺省为1，超过[`Self::MAX_POSTS_NUM`]时返回错误
+        pub fn posts_num(&self) -> Result<usize, String> {
+            match self.num {
+                Some(num) if num > Self::MAX_POSTS_NUM => {
+                    Err(format!("num({num})不能大于{}", Self::MAX_POSTS_NUM))
+                }
+                num => Ok(num.unwrap_or(1)),
+            }
+        }
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct TagParams {
+        pub num: Option<i32>,
+        #[serde(rename(deserialize = "rule"))]
+        pub sort_rule: Option<String>,
+    }
+
+    impl TagParams {
+        /// 排序规则只能为`created`或`updated`，缺省为`created`
+        pub fn checked_sort_rule(&self) -> Result<&'static str, String> {
+            match self.sort_rule.as_deref() {
+                None | Some("created") => Ok("created"),
+                Some("updated") => Ok("updated"),
+                Some(rule) => Err(format!("无效的排序规则: {rule}")),
+            }
+        }
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct NewQueryParams {
+        /// `%Y-%m-%d %H:%M:%S`，只返回在此之后第一次获取到的文章，缺省时为最近一次运行的时间
+        pub since: Option<String>,
+        /// 当num<0或缺省时，返回所有数据
+        pub num: Option<i32>,
+    }
+
+    /// 管理接口中修改、删除友链的参数
+    #[derive(Debug, Deserialize)]
+    pub struct AdminFriendParams {
+        pub link: String,
+    }
+
+    /// 管理接口中删除屏蔽站点的参数
+    #[derive(Debug, Deserialize)]
+    pub struct AdminBlockParams {
+        pub pattern: String,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct SearchQueryParams {
+        /// 检索词，多个词用空格分隔
+        pub q: Option<String>,
+        pub num: Option<usize>,
+    }
+
+    impl SearchQueryParams {
+        /// `/search`一次最多返回的文章数
+        pub const MAX_POSTS_NUM: usize = 100;
+
+        /// `/search`返回的文章数，缺省为20，超过[`Self::MAX_POSTS_NUM`]时返回错误
+        pub fn posts_num(&self) -> Result<usize, String> {
+            match self.num {
+                Some(num) if num > Self::MAX_POSTS_NUM => {
+                    Err(format!("num({num})不能大于{}", Self::MAX_POSTS_NUM))
+                }
+                num => Ok(num.unwrap_or(20)),
+            }
+        }
+    }
 }
 
 /// 包含基本数据结构定义
 pub mod metadata {
     use serde::{Deserialize, Serialize};
-    use sqlx::FromRow;
+    use sqlx::{Database, Decode, Encode, FromRow, Type, encode::IsNull, error::BoxDynError};
+
+    /// 文章标签
+    ///
+    /// 序列化为数组；在sqlite和mysql中以`,`拼接后保存为一个字段
+    #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+    #[serde(transparent)]
+    pub struct Tags(pub Vec<String>);
+
+    impl Tags {
+        /// 去除空白和重复的标签，保持原有顺序
+        pub fn new(tags: impl IntoIterator<Item = String>) -> Tags {
+            let mut result: Vec<String> = Vec::new();
+            for tag in tags {
+                let tag = tag.trim().replace(',', "，");
+                if !tag.is_empty() && !result.contains(&tag) {
+                    result.push(tag);
+                }
+            }
+            Tags(result)
+        }
+
+        /// 数据库中的保存格式
+        pub fn to_db_string(&self) -> String {
+            self.0.join(",")
+        }
+    }
+
+    impl From<String> for Tags {
+        fn from(value: String) -> Self {
+            Tags::new(value.split(',').map(String::from))
+        }
+    }
+
+    impl std::ops::Deref for Tags {
+        type Target = Vec<String>;
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
 
     /// 文章结构定义
-    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+    #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, FromRow)]
     pub struct BasePosts {
         pub title: String,
         pub created: String,
@@ -37,6 +256,15 @@ pub mod metadata {
         // #[serde(skip_serializing)]
         // #[serde(default)]
         pub rule: String,
+        /// 摘要，纯文本
+        #[serde(default)]
+        pub summary: Option<String>,
+        /// 封面图片地址
+        #[serde(default)]
+        pub cover: Option<String>,
+        #[serde(default)]
+        #[sqlx(try_from = "String")]
+        pub tags: Tags,
     }
 
     #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
@@ -49,6 +277,9 @@ pub mod metadata {
         #[serde(rename = "createdAt")]
         #[sqlx(rename = "createdAt")]
         pub created_at: String,
+        /// 第一次获取到该文章的时间，`%Y-%m-%d %H:%M:%S`
+        #[serde(default)]
+        pub first_seen: String,
     }
 
     impl BasePosts {
@@ -65,21 +296,129 @@ pub mod metadata {
                 updated,
                 link,
                 rule,
+                summary: None,
+                cover: None,
+                tags: Tags::default(),
             }
         }
     }
 
     impl Posts {
+        /// `first_seen`默认与`created_at`相同，即本次第一次获取到
         pub fn new(meta: BasePosts, author: String, avatar: String, created_at: String) -> Posts {
             Posts {
                 meta,
                 author,
                 avatar,
+                first_seen: created_at.clone(),
                 created_at,
             }
         }
     }
 
+    /// 友链获取失败的原因
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum FailureKind {
+        /// 域名无法解析，通常是域名过期
+        Nxdomain,
+        /// 连接被拒绝
+        ConnectionRefused,
+        /// 连接超时
+        Timeout,
+        /// TLS握手失败
+        Tls,
+        /// 主页返回4xx
+        HttpClientError,
+        /// 主页返回5xx
+        HttpServerError,
+        /// 域名停放页，通常是域名过期后被抢注
+        Parked,
+        /// 主页可以访问，但没有获取到文章
+        NoPosts,
+        /// 友链页中的链接无效，无法抓取
+        InvalidLink,
+        /// 其它错误
+        Other,
+    }
+
+    impl FailureKind {
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                FailureKind::Nxdomain => "nxdomain",
+                FailureKind::ConnectionRefused => "connection_refused",
+                FailureKind::Timeout => "timeout",
+                FailureKind::Tls => "tls",
+                FailureKind::HttpClientError => "http_client_error",
+                FailureKind::HttpServerError => "http_server_error",
+                FailureKind::Parked => "parked",
+                FailureKind::NoPosts => "no_posts",
+                FailureKind::InvalidLink => "invalid_link",
+                FailureKind::Other => "other",
+            }
+        }
+    }
+
+    impl std::str::FromStr for FailureKind {
+        type Err = String;
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "nxdomain" => Ok(FailureKind::Nxdomain),
+                "connection_refused" => Ok(FailureKind::ConnectionRefused),
+                "timeout" => Ok(FailureKind::Timeout),
+                "tls" => Ok(FailureKind::Tls),
+                "http_client_error" => Ok(FailureKind::HttpClientError),
+                "http_server_error" => Ok(FailureKind::HttpServerError),
+                "parked" => Ok(FailureKind::Parked),
+                "no_posts" => Ok(FailureKind::NoPosts),
+                "invalid_link" => Ok(FailureKind::InvalidLink),
+                "other" => Ok(FailureKind::Other),
+                _ => Err(format!("未知的失败原因`{s}`")),
+            }
+        }
+    }
+
+    impl std::fmt::Display for FailureKind {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str(self.as_str())
+        }
+    }
+
+    // 在sqlite和mysql中以字符串保存
+    impl<DB: Database> Type<DB> for FailureKind
+    where
+        String: Type<DB>,
+    {
+        fn type_info() -> DB::TypeInfo {
+            <String as Type<DB>>::type_info()
+        }
+
+        fn compatible(ty: &DB::TypeInfo) -> bool {
+            <String as Type<DB>>::compatible(ty)
+        }
+    }
+
+    impl<'q, DB: Database> Encode<'q, DB> for FailureKind
+    where
+        String: Encode<'q, DB>,
+    {
+        fn encode_by_ref(
+            &self,
+            buf: &mut <DB as Database>::ArgumentBuffer<'q>,
+        ) -> Result<IsNull, BoxDynError> {
+            <String as Encode<'q, DB>>::encode(self.as_str().to_string(), buf)
+        }
+    }
+
+    impl<'r, DB: Database> Decode<'r, DB> for FailureKind
+    where
+        String: Decode<'r, DB>,
+    {
+        fn decode(value: <DB as Database>::ValueRef<'r>) -> Result<Self, BoxDynError> {
+            Ok(<String as Decode<'r, DB>>::decode(value)?.parse()?)
+        }
+    }
+
     #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
     pub struct Friends {
         pub name: String,
@@ -89,6 +428,22 @@ pub mod metadata {
         #[serde(rename = "createdAt")]
         #[sqlx(rename = "createdAt")]
         pub created_at: String,
+        /// 获取失败的原因，`error`为false时为None
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[sqlx(default)]
+        pub error_kind: Option<FailureKind>,
+        /// 友链页中的头像地址无效，已使用默认头像，不影响抓取
+        #[serde(default)]
+        #[sqlx(default)]
+        pub invalid_avatar: bool,
+        /// 最新文章的创建日期，用于判断友链是否不活跃
+        #[serde(default)]
+        #[sqlx(default)]
+        pub latest_post: Option<String>,
+        /// 置顶的友链在`/friend`中排在前面
+        #[serde(default)]
+        #[sqlx(default)]
+        pub pinned: bool,
     }
 
     impl Friends {
@@ -105,14 +460,72 @@ pub mod metadata {
                 avatar,
                 error,
                 created_at,
+                error_kind: None,
+                invalid_avatar: false,
+                latest_post: None,
+                pinned: false,
             }
         }
     }
+
+    /// 通过管理接口添加的友链，每次运行时与友链页中的友链一起抓取
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+    pub struct ManualFriend {
+        pub name: String,
+        pub link: String,
+        #[serde(default)]
+        pub avatar: String,
+        /// 订阅地址后缀，与`SETTINGS_FRIENDS_LINKS`中的第4项相同，为空时自动识别
+        #[serde(default)]
+        pub suffix: String,
+        #[serde(rename = "createdAt", default)]
+        #[sqlx(rename = "createdAt")]
+        pub created_at: String,
+    }
+
+    /// 按友链地址覆盖抓取和展示方式，对友链页和配置项中的友链都生效，空值表示不覆盖
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+    pub struct FriendOverride {
+        pub link: String,
+        /// 展示的名称
+        #[serde(default)]
+        pub name: String,
+        /// 订阅地址，可以是完整地址或相对主页的后缀
+        #[serde(default)]
+        pub feed: String,
+        /// 只使用`css_rules.yaml`中`post_page_rules`下的这个主题解析文章页
+        #[serde(default)]
+        pub theme: String,
+        /// 不抓取也不展示
+        #[serde(default)]
+        pub hidden: bool,
+        #[serde(default)]
+        pub pinned: bool,
+        /// 保留的文章数，不大于0时使用`MAX_POSTS_NUM`
+        #[serde(default)]
+        pub max_posts: i32,
+        #[serde(rename = "createdAt", default)]
+        #[sqlx(rename = "createdAt")]
+        pub created_at: String,
+    }
+
+    /// 通过管理接口添加的屏蔽站点，与`BLOCK_SITE`合并使用
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+    pub struct BlockedSite {
+        pub pattern: String,
+        #[serde(rename = "createdAt", default)]
+        #[sqlx(rename = "createdAt")]
+        pub created_at: String,
+    }
 }
 
 /// 配置
 pub mod config {
+    use super::metadata::Tags;
+    use chrono::{FixedOffset, TimeDelta, Utc};
     use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
     #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
     pub struct LinkMeta {
         pub link: String,
@@ -135,6 +548,12 @@ pub mod config {
         pub block_site: Vec<String>,
         #[serde(rename = "MAX_POSTS_NUM")]
         pub max_posts_num: usize,
+        /// 截取每个友链的文章前按哪个时间从新到旧排序
+        #[serde(rename = "POST_SORT_BY", default)]
+        pub post_sort_by: PostSortBy,
+        /// 所有友链合计保存的文章数上限，超出时只保留最新的，0表示不限制
+        #[serde(rename = "MAX_TOTAL_POSTS", default)]
+        pub max_total_posts: usize,
         #[serde(rename = "OUTDATE_CLEAN")]
         pub outdate_clean: usize,
         #[serde(rename = "DATABASE")]
@@ -143,21 +562,506 @@ pub mod config {
         pub deploy_type: String,
         #[serde(rename = "SIMPLE_MODE")]
         pub simple_mode: bool,
+        #[serde(rename = "SIMPLE_MODE_OUTPUT", default)]
+        pub simple_mode_output: SimpleModeSettings,
         #[serde(rename = "CRON")]
         pub cron: String,
+        /// 标签别名，键为规范写法，值为该标签的其它写法
+        #[serde(rename = "TAG_ALIASES", default)]
+        pub tag_aliases: HashMap<String, Vec<String>>,
+        #[serde(rename = "DEDUP_STRATEGY", default)]
+        pub dedup_strategy: DedupStrategy,
+        /// 友链页中没有解析到头像或头像无效时使用的默认头像
+        #[serde(rename = "DEFAULT_AVATAR", default = "default_avatar")]
+        pub default_avatar: String,
+        #[serde(rename = "AVATAR_CACHE", default)]
+        pub avatar_cache: AvatarCacheSettings,
+        /// 友链主页或订阅永久重定向到其他站点时，是否用新地址替换保存的友链地址
+        #[serde(rename = "UPDATE_MOVED_FRIENDS", default)]
+        pub update_moved_friends: bool,
+        /// 最新文章超过多少天的友链视为不活跃，0表示不区分
+        #[serde(rename = "INACTIVE_DAYS", default = "default_inactive_days")]
+        pub inactive_days: usize,
+        /// 每次运行结束后，有新文章或友链状态变化时发送通知
+        #[serde(rename = "WEBHOOKS", default)]
+        pub webhooks: Vec<Webhook>,
+        #[serde(rename = "METRICS", default)]
+        pub metrics: MetricsSettings,
+        /// 最近一次运行距今超过多少小时时，`/readyz`返回未就绪，0表示不检查
+        #[serde(rename = "READY_MAX_DATA_AGE", default)]
+        pub ready_max_data_age: usize,
+        #[serde(rename = "API", default)]
+        pub api: ApiSettings,
+        #[serde(rename = "HTTP_CACHE", default)]
+        pub http_cache: HttpCacheSettings,
+        #[serde(rename = "RATE_LIMIT", default)]
+        pub rate_limit: RateLimitSettings,
+        #[serde(rename = "API_KEYS", default)]
+        pub api_keys: ApiKeySettings,
+        #[serde(rename = "ADMIN", default)]
+        pub admin: AdminSettings,
+        #[serde(rename = "POST_FILTERS", default)]
+        pub post_filters: PostFilterSettings,
+    }
+
+    /// 通知方式，字段值为`${变量名}`时从环境变量读取
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(tag = "type", rename_all = "lowercase")]
+    pub enum Webhook {
+        /// 以json格式POST本次运行的汇总
+        Json {
+            url: String,
+        },
+        Telegram {
+            bot_token: String,
+            chat_id: String,
+        },
+        Discord {
+            url: String,
+        },
+        Email(EmailWebhook),
+    }
+
+    /// 通过SMTP发送邮件
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct EmailWebhook {
+        pub smtp_host: String,
+        #[serde(default = "default_smtp_port")]
+        pub smtp_port: u16,
+        /// 为true时使用STARTTLS，否则直接使用TLS连接
+        #[serde(default)]
+        pub starttls: bool,
+        pub username: String,
+        pub password: String,
+        pub from: String,
+        pub to: Vec<String>,
+    }
+
+    fn default_smtp_port() -> u16 {
+        465
+    }
+
+    fn default_inactive_days() -> usize {
+        365
+    }
+
+    /// 友链活跃度的判断标准
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct FriendActivity {
+        pub inactive_days: usize,
+    }
+
+    impl FriendActivity {
+        /// 最新文章早于该日期（`%Y-%m-%d`）的友链视为不活跃，不区分时返回None
+        pub fn dormant_before(&self) -> Option<String> {
+            if self.inactive_days == 0 {
+                return None;
+            }
+            let before = Utc::now() - TimeDelta::days(self.inactive_days as i64);
+            Some(before.format("%Y-%m-%d").to_string())
+        }
+    }
+
+    /// `/readyz`的判断标准
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct Readiness {
+        pub max_data_age: usize,
+    }
+
+    impl Readiness {
+        /// 最近一次运行早于该时间（北京时间`%Y-%m-%d %H:%M:%S`）时视为数据过旧，不检查时返回None
+        pub fn stale_before(&self) -> Option<String> {
+            if self.max_data_age == 0 {
+                return None;
+            }
+            let offset = FixedOffset::east_opt(8 * 60 * 60)?;
+            let before =
+                Utc::now().with_timezone(&offset) - TimeDelta::hours(self.max_data_age as i64);
+            Some(before.format("%Y-%m-%d %H:%M:%S").to_string())
+        }
+    }
+
+    /// 极简模式的导出文件和静态站点
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct SimpleModeSettings {
+        /// 导出文件路径
+        pub export_path: String,
+        pub export_format: ExportFormat,
+        /// 静态站点的输出目录
+        pub output_dir: String,
+        /// `all/{页码}.json`每页的文章数，0表示不分页
+        pub page_size: usize,
+        /// Atom订阅中的文章数，0表示全部
+        pub feed_size: usize,
+        pub feed_title: String,
+        /// 静态站点的访问地址，用于生成Atom订阅的地址，可以为空
+        pub site_url: String,
+    }
+
+    impl Default for SimpleModeSettings {
+        fn default() -> Self {
+            SimpleModeSettings {
+                export_path: String::from("./data.json"),
+                export_format: ExportFormat::default(),
+                output_dir: String::from("./static"),
+                page_size: 50,
+                feed_size: 50,
+                feed_title: String::from("友链朋友圈"),
+                site_url: String::new(),
+            }
+        }
+    }
+
+    /// 极简模式导出文件的格式
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ExportFormat {
+        /// 格式化的json，与`/all`的返回格式相同
+        #[default]
+        Json,
+        /// 不换行的json，与`/all`的返回格式相同
+        CompactJson,
+        /// 每行一篇文章
+        Ndjson,
+        /// 每行一篇文章，标签以`,`拼接
+        Csv,
+    }
+
+    fn default_avatar() -> String {
+        String::from("https://sdn.geekzu.org/avatar/57d8260dfb55501c37dde588e7c3852c")
+    }
+
+    /// api的监听地址和跨域设置
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct ApiSettings {
+        pub host: String,
+        pub port: u16,
+        /// 允许跨域访问的来源，如`https://blog.example.com`，为空或包含`*`时允许所有来源
+        pub allowed_origins: Vec<String>,
+        /// 路径前缀，如`/fcircle`，反向代理到子路径时使用
+        pub path_prefix: String,
+        /// 监听的Unix socket路径，设置后忽略`host`和`port`
+        pub unix_socket: String,
+    }
+
+    impl Default for ApiSettings {
+        fn default() -> Self {
+            ApiSettings {
+                host: String::from("0.0.0.0"),
+                port: 8000,
+                allowed_origins: Vec::new(),
+                path_prefix: String::new(),
+                unix_socket: String::new(),
+            }
+        }
+    }
+
+    impl ApiSettings {
+        pub fn listen_addr(&self) -> String {
+            format!("{}:{}", self.host, self.port)
+        }
+
+        /// 规范化的路径前缀，以`/`开头、不以`/`结尾，未设置时返回None
+        pub fn path_prefix(&self) -> Option<String> {
+            let prefix = self.path_prefix.trim().trim_matches('/');
+            if prefix.is_empty() {
+                None
+            } else {
+                Some(format!("/{prefix}"))
+            }
+        }
+
+        pub fn allow_any_origin(&self) -> bool {
+            self.allowed_origins.is_empty() || self.allowed_origins.iter().any(|o| o == "*")
+        }
+    }
+
+    /// api的响应缓存，数据只在core运行后变化，以最近一次运行的时间作为版本
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct HttpCacheSettings {
+        pub enable: bool,
+        /// `Cache-Control`中的`max-age`（秒）
+        pub max_age: u64,
+        /// 重新查询最近一次运行时间的间隔（秒）
+        pub check_interval: u64,
+        /// 内存中最多缓存的响应数
+        pub max_entries: usize,
+    }
+
+    impl Default for HttpCacheSettings {
+        fn default() -> Self {
+            HttpCacheSettings {
+                enable: false,
+                max_age: 60,
+                check_interval: 10,
+                max_entries: 256,
+            }
+        }
+    }
+
+    /// 按IP限制api的请求频率，携带有效API密钥的请求不受限制
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct RateLimitSettings {
+        pub enable: bool,
+        /// 每个IP每分钟的请求数
+        pub per_minute: u32,
+        /// 允许的突发请求数
+        pub burst: u32,
+        /// 是否从`X-Forwarded-For`/`X-Real-IP`获取客户端IP，只应在反向代理后开启
+        pub trust_forwarded: bool,
+    }
+
+    impl Default for RateLimitSettings {
+        fn default() -> Self {
+            RateLimitSettings {
+                enable: false,
+                per_minute: 60,
+                burst: 20,
+                trust_forwarded: false,
+            }
+        }
+    }
+
+    /// API密钥，通过`X-API-Key`或`Authorization: Bearer`请求头携带
+    #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct ApiKeySettings {
+        /// 有效的密钥，可以写成`${变量名}`从环境变量读取
+        pub keys: Vec<String>,
+        /// 需要密钥才能访问的路由，如`/randompost`
+        pub routes: Vec<String>,
+    }
+
+    /// 管理接口，`token`为空时不注册`/admin`下的路由
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct AdminSettings {
+        /// 通过`Authorization: Bearer`请求头携带，可以写成`${变量名}`从环境变量读取
+        pub token: String,
+        /// `POST /admin/crawl`执行的命令，即core的可执行文件
+        pub core_command: String,
+    }
+
+    impl Default for AdminSettings {
+        fn default() -> Self {
+            AdminSettings {
+                token: String::new(),
+                core_command: String::from("./fcircle_core"),
+            }
+        }
+    }
+
+    /// Prometheus指标，api通过`/metrics`提供
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct MetricsSettings {
+        pub enable: bool,
+        /// core每次运行结束后写入抓取指标的文件，api读取后一并返回
+        pub crawl_record: String,
+    }
+
+    impl Default for MetricsSettings {
+        fn default() -> Self {
+            MetricsSettings {
+                enable: false,
+                crawl_record: String::from("./crawl_metrics.prom"),
+            }
+        }
+    }
+
+    /// 头像镜像，由core下载头像到本地，api通过`/avatar/{hash}`提供
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct AvatarCacheSettings {
+        pub enable: bool,
+        /// 头像缓存目录
+        pub dir: String,
+        /// 头像缩放后的最大边长（像素）
+        pub size: u32,
+        /// 头像地址前缀，镜像成功后头像地址替换为`{url_prefix}/{hash}`
+        pub url_prefix: String,
+        /// 找不到头像时返回的本地图片，为空时重定向到`DEFAULT_AVATAR`
+        pub default_image: String,
+    }
+
+    impl Default for AvatarCacheSettings {
+        fn default() -> Self {
+            AvatarCacheSettings {
+                enable: false,
+                dir: String::from("./avatars"),
+                size: 128,
+                url_prefix: String::from("/avatar"),
+                default_image: String::new(),
+            }
+        }
+    }
+
+    impl AvatarCacheSettings {
+        /// 返回头像文件路径，`hash`不是64位小写十六进制时返回None，防止路径穿越
+        pub fn path_of(&self, hash: &str) -> Option<PathBuf> {
+            if hash.len() == 64
+                && hash
+                    .bytes()
+                    .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+            {
+                Some(Path::new(&self.dir).join(format!("{hash}.png")))
+            } else {
+                None
+            }
+        }
+
+        /// 返回镜像后的头像地址
+        pub fn url_of(&self, hash: &str) -> String {
+            format!("{}/{}", self.url_prefix.trim_end_matches('/'), hash)
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum PostSortBy {
+        #[default]
+        Created,
+        Updated,
+    }
+
+    /// 同一篇文章被多次获取且标题或时间不一致时，保留哪一条
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum DedupStrategy {
+        /// 保留最先获取到的，即友链顺序靠前的
+        #[default]
+        First,
+        /// 保留`updated`最新的
+        Latest,
+        /// 保留`created`最早的
+        Earliest,
+    }
+
+    /// 文章过滤，core写入数据库前生效
+    #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+    #[serde(default)]
+    pub struct PostFilterSettings {
+        pub mode: PostFilterMode,
+        /// 标题正则
+        pub titles: Vec<String>,
+        /// 文章链接正则
+        pub links: Vec<String>,
+        /// 标题或摘要中包含的关键词，不区分大小写
+        pub keywords: Vec<String>,
+        /// 只保留创建时间在多少天内的文章，0表示不限制，两种模式下都生效
+        pub max_age_days: usize,
+    }
+
+    /// `titles`、`links`、`keywords`的用法
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    pub enum PostFilterMode {
+        /// 去除符合任一规则的文章
+        #[default]
+        Block,
+        /// 只保留符合任一规则的文章，没有规则时保留所有文章
+        Allow,
+    }
+
+    impl Settings {
+        pub fn tag_aliases(&self) -> TagAliases {
+            TagAliases::new(&self.tag_aliases)
+        }
+
+        pub fn friend_activity(&self) -> FriendActivity {
+            FriendActivity {
+                inactive_days: self.inactive_days,
+            }
+        }
+
+        pub fn readiness(&self) -> Readiness {
+            Readiness {
+                max_data_age: self.ready_max_data_age,
+            }
+        }
+    }
+
+    /// 标签别名表，用于统一大小写以及中英文等不同写法
+    #[derive(Debug, Clone, Default, PartialEq)]
+    pub struct TagAliases {
+        /// 小写的写法 -> 规范写法
+        variants: HashMap<String, String>,
+        /// 规范写法 -> 所有写法（含规范写法）
+        groups: HashMap<String, Vec<String>>,
+    }
+
+    impl TagAliases {
+        pub fn new(aliases: &HashMap<String, Vec<String>>) -> TagAliases {
+            let mut tag_aliases = TagAliases::default();
+            for (canonical, others) in aliases {
+                let canonical = canonical.trim().to_string();
+                let mut group = vec![canonical.clone()];
+                group.extend(others.iter().map(|v| v.trim().to_string()));
+                for variant in &group {
+                    tag_aliases
+                        .variants
+                        .insert(variant.to_lowercase(), canonical.clone());
+                }
+                tag_aliases.groups.insert(canonical, group);
+            }
+            tag_aliases
+        }
+
+        /// 返回标签的规范写法，没有配置别名时原样返回
+        pub fn normalize(&self, tag: &str) -> String {
+            let tag = tag.trim();
+            match self.variants.get(&tag.to_lowercase()) {
+                Some(canonical) => canonical.clone(),
+                None => tag.to_string(),
+            }
+        }
+
+        /// 规范化所有标签，并去除只有大小写不同的重复标签
+        pub fn normalize_tags(&self, tags: &Tags) -> Tags {
+            let mut seen: Vec<String> = Vec::new();
+            let normalized = tags.iter().map(|tag| self.normalize(tag)).filter(|tag| {
+                let key = tag.to_lowercase();
+                if seen.contains(&key) {
+                    false
+                } else {
+                    seen.push(key);
+                    true
+                }
+            });
+            Tags::new(normalized.collect::<Vec<_>>())
+        }
+
+        /// 返回标签的所有写法，用于查询别名生效前保存的数据
+        pub fn variants(&self, tag: &str) -> Vec<String> {
+            let canonical = self.normalize(tag);
+            match self.groups.get(&canonical) {
+                Some(group) => group.clone(),
+                None => vec![canonical],
+            }
+        }
     }
 }
 
 /// 响应
 pub mod response {
+    use super::config::TagAliases;
     use super::metadata::*;
     use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
 
     /// 统计数据
     #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
     pub struct StatisticalData {
         friends_num: usize,
+        /// 可以访问且近期有更新的友链数
         active_num: usize,
+        /// 可以访问但长期没有更新的友链数
+        dormant_num: usize,
+        /// 失联友链数
         error_num: usize,
         article_num: usize,
         last_updated_time: String,
@@ -166,6 +1070,7 @@ pub mod response {
         fn new(
             friends_num: usize,
             active_num: usize,
+            dormant_num: usize,
             error_num: usize,
             article_num: usize,
             last_updated_time: String,
@@ -173,6 +1078,7 @@ pub mod response {
             StatisticalData {
                 friends_num,
                 active_num,
+                dormant_num,
                 error_num,
                 article_num,
                 last_updated_time,
@@ -191,70 +1097,289 @@ pub mod response {
         link: String,
         author: String,
         avatar: String,
+        summary: Option<String>,
+        cover: Option<String>,
+        tags: Tags,
+        first_seen: String,
     }
 
     impl ArticleData {
-        fn new(
-            floor: usize,
-            title: String,
-            created: String,
-            updated: String,
-            link: String,
-            author: String,
-            avatar: String,
-        ) -> Self {
+        fn new(floor: usize, posts: Posts) -> Self {
             ArticleData {
                 floor,
-                title,
-                created,
-                updated,
-                link,
-                author,
-                avatar,
+                title: posts.meta.title,
+                created: posts.meta.created,
+                updated: posts.meta.updated,
+                link: posts.meta.link,
+                author: posts.author,
+                avatar: posts.avatar,
+                summary: posts.meta.summary,
+                cover: posts.meta.cover,
+                tags: posts.meta.tags,
+                first_seen: posts.first_seen,
+            }
+        }
+    }
+
+    /// 分页数据
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct Pagination {
+        /// 符合条件的文章总数
+        pub total: usize,
+        pub offset: usize,
+        /// 每页数量，0表示不限制
+        pub limit: usize,
+        /// 下一页的游标，没有下一页时为`None`
+        pub next_cursor: Option<String>,
+    }
+
+    impl Pagination {
+        pub fn new(total: usize, offset: usize, limit: usize) -> Self {
+            let next_cursor = offset
+                .checked_add(limit)
+                .filter(|&next_offset| limit > 0 && next_offset < total)
+                .map(Self::encode_cursor);
+            Pagination {
+                total,
+                offset,
+                limit,
+                next_cursor,
             }
         }
+
+        /// 游标对客户端不透明，目前仅编码了偏移量
+        pub fn encode_cursor(offset: usize) -> String {
+            format!("o{offset:x}")
+        }
+
+        pub fn decode_cursor(cursor: &str) -> Option<usize> {
+            usize::from_str_radix(cursor.strip_prefix('o')?, 16).ok()
+        }
     }
 
     /// 所有文章数据
     #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
     pub struct AllPostData {
         pub statistical_data: StatisticalData,
+        pub pagination: Pagination,
         pub article_data: Vec<ArticleData>,
     }
 
     impl AllPostData {
+        /// 统计数据中的`article_num`取`pagination.total`，即文章总数而不是当前页的数量
         pub fn new(
             friends_num: usize,
             active_num: usize,
+            dormant_num: usize,
             error_num: usize,
-            article_num: usize,
             last_updated_time: String,
             posts: Vec<Posts>,
-            start_offset: usize, // 用于计算floor
+            pagination: Pagination,
         ) -> AllPostData {
+            let start_offset = pagination.offset; // 用于计算floor
             let article_data: Vec<ArticleData> = posts
                 .into_iter()
                 .enumerate()
-                .map(|(floor, posts)| {
-                    ArticleData::new(
-                        floor + start_offset + 1,
-                        posts.meta.title,
-                        posts.meta.created,
-                        posts.meta.updated,
-                        posts.meta.link,
-                        posts.author,
-                        posts.avatar,
-                    )
-                })
+                .map(|(floor, posts)| ArticleData::new(floor + start_offset + 1, posts))
                 .collect();
             AllPostData {
                 statistical_data: StatisticalData::new(
                     friends_num,
                     active_num,
+                    dormant_num,
                     error_num,
-                    article_num,
+                    pagination.total,
                     last_updated_time,
                 ),
+                pagination,
+                article_data,
+            }
+        }
+    }
+
+    /// 全文检索的单条结果
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct SearchArticleData {
+        #[serde(flatten)]
+        pub article: ArticleData,
+        /// 用`<mark></mark>`标记了检索词的标题
+        pub highlight: String,
+    }
+
+    /// 全文检索结果，`floor`即相关度排名
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct SearchData {
+        pub query: String,
+        pub article_num: usize,
+        pub article_data: Vec<SearchArticleData>,
+    }
+
+    impl SearchData {
+        /// `posts`需已按相关度排序
+        pub fn new(query: String, posts: Vec<Posts>) -> SearchData {
+            let terms: Vec<&str> = query.split_whitespace().collect();
+            let article_data: Vec<SearchArticleData> = posts
+                .into_iter()
+                .enumerate()
+                .map(|(floor, posts)| SearchArticleData {
+                    highlight: highlight(&posts.meta.title, &terms),
+                    article: ArticleData::new(floor + 1, posts),
+                })
+                .collect();
+            SearchData {
+                query,
+                article_num: article_data.len(),
+                article_data,
+            }
+        }
+    }
+
+    /// 在`text`中用`<mark></mark>`标记出所有`terms`，忽略ASCII大小写
+    ///
+    /// 标题来自抓取的订阅，除`<mark>`外的内容都经过HTML转义
+    fn highlight(text: &str, terms: &[&str]) -> String {
+        let lower = text.to_ascii_lowercase();
+        // 标记每个字节是否命中
+        let mut marked = vec![false; text.len()];
+        for term in terms {
+            let term = term.to_ascii_lowercase();
+            if term.is_empty() {
+                continue;
+            }
+            for (i, _) in lower.match_indices(&term) {
+                marked[i..i + term.len()].iter_mut().for_each(|m| *m = true);
+            }
+        }
+        let mut result = String::with_capacity(text.len());
+        let mut in_mark = false;
+        for (i, c) in text.char_indices() {
+            if marked[i] != in_mark {
+                result.push_str(if marked[i] { "<mark>" } else { "</mark>" });
+                in_mark = marked[i];
+            }
+            match c {
+                '&' => result.push_str("&amp;"),
+                '<' => result.push_str("&lt;"),
+                '>' => result.push_str("&gt;"),
+                '"' => result.push_str("&quot;"),
+                '\'' => result.push_str("&#39;"),
+                c => result.push(c),
+            }
+        }
+        if in_mark {
+            result.push_str("</mark>");
+        }
+        result
+    }
+
+    /// `/readyz`的检查结果
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct ReadyData {
+        pub ready: bool,
+        /// 最近一次运行的时间，没有数据时为None
+        pub last_updated_time: Option<String>,
+        /// 未通过的检查项
+        pub errors: Vec<String>,
+    }
+
+    impl ReadyData {
+        pub fn new(last_updated_time: Option<String>, errors: Vec<String>) -> ReadyData {
+            ReadyData {
+                ready: errors.is_empty(),
+                last_updated_time,
+                errors,
+            }
+        }
+    }
+
+    /// `POST /admin/crawl`的结果
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct CrawlData {
+        /// 本次运行的进程号
+        pub pid: Option<u32>,
+        pub started_at: String,
+    }
+
+    /// 标签及其文章数
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct TagData {
+        pub name: String,
+        pub count: usize,
+    }
+
+    impl TagData {
+        /// 按规范写法合并各标签的文章数，按文章数从多到少排序
+        ///
+        /// 没有配置别名的标签忽略大小写合并，显示文章数最多的写法
+        pub fn merge(counts: Vec<(String, usize)>, aliases: &TagAliases) -> Vec<TagData> {
+            // 小写的规范写法 -> (各写法及其文章数, 总数)
+            let mut groups: HashMap<String, (Vec<(String, usize)>, usize)> = HashMap::new();
+            for (tag, count) in counts {
+                let canonical = aliases.normalize(&tag);
+                let entry = groups.entry(canonical.to_lowercase()).or_default();
+                match entry.0.iter_mut().find(|(name, _)| *name == canonical) {
+                    Some(variant) => variant.1 += count,
+                    None => entry.0.push((canonical, count)),
+                }
+                entry.1 += count;
+            }
+            let mut tags: Vec<TagData> = groups
+                .into_values()
+                .map(|(variants, count)| {
+                    let name = variants
+                        .into_iter()
+                        .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+                        .map(|(name, _)| name)
+                        .unwrap_or_default();
+                    TagData { name, count }
+                })
+                .collect();
+            tags.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+            tags
+        }
+    }
+
+    /// 某个标签的所有文章数据
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct AllPostDataSomeTag {
+        pub tag: String,
+        pub article_num: usize,
+        pub article_data: Vec<ArticleData>,
+    }
+
+    impl AllPostDataSomeTag {
+        pub fn new(tag: String, posts: Vec<Posts>) -> AllPostDataSomeTag {
+            let article_data: Vec<ArticleData> = posts
+                .into_iter()
+                .enumerate()
+                .map(|(floor, posts)| ArticleData::new(floor + 1, posts))
+                .collect();
+            AllPostDataSomeTag {
+                tag,
+                article_num: article_data.len(),
+                article_data,
+            }
+        }
+    }
+
+    /// 新文章数据，按第一次获取到的时间倒序
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct NewPostData {
+        pub since: String,
+        pub article_num: usize,
+        pub article_data: Vec<ArticleData>,
+    }
+
+    impl NewPostData {
+        pub fn new(since: String, posts: Vec<Posts>) -> NewPostData {
+            let article_data: Vec<ArticleData> = posts
+                .into_iter()
+                .enumerate()
+                .map(|(floor, posts)| ArticleData::new(floor + 1, posts))
+                .collect();
+            NewPostData {
+                since,
+                article_num: article_data.len(),
                 article_data,
             }
         }
@@ -298,17 +1423,7 @@ pub mod response {
             let article_data: Vec<ArticleData> = posts
                 .into_iter()
                 .enumerate()
-                .map(|(floor, posts)| {
-                    ArticleData::new(
-                        floor + start_offset + 1,
-                        posts.meta.title,
-                        posts.meta.created,
-                        posts.meta.updated,
-                        posts.meta.link,
-                        posts.author,
-                        posts.avatar,
-                    )
-                })
+                .map(|(floor, posts)| ArticleData::new(floor + start_offset + 1, posts))
                 .collect();
             AllPostDataSomeFriend {
                 statistical_data: StatisticalDataOfSomeFriend::new(name, link, avatar, article_num),
@@ -317,3 +1432,179 @@ pub mod response {
         }
     }
 }
+
+/// 测试共用的配置、友链和文章，开启`testing`特性时可用
+#[cfg(feature = "testing")]
+pub mod testing {
+    use crate::config::Settings;
+    use crate::metadata::{BasePosts, Friends, Posts};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// 只包含必填项的配置，不读取仓库中的`fc_settings.yaml`
+    pub fn settings() -> Settings {
+        serde_json::from_value(serde_json::json!({
+            "LINK": [],
+            "SETTINGS_FRIENDS_LINKS": {"enable": false, "json_api": "", "list": []},
+            "BLOCK_SITE": [],
+            "MAX_POSTS_NUM": 5,
+            "OUTDATE_CLEAN": 60,
+            "DATABASE": "sqlite",
+            "DEPLOY_TYPE": "server",
+            "SIMPLE_MODE": false,
+            "CRON": "",
+        }))
+        .unwrap()
+    }
+
+    /// 每次调用都返回一个新的空临时目录，测试可以并行运行
+    pub fn temp_dir(name: &str) -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "fcircle_test_{name}_{}_{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// 链接为`https://{name}.example.com/`的友链
+    pub fn friend(name: &str) -> Friends {
+        Friends::new(
+            name.to_string(),
+            format!("https://{name}.example.com/"),
+            String::new(),
+            false,
+            String::from("2024-06-01 00:00:00"),
+        )
+    }
+
+    /// 创建和更新时间相同的文章
+    pub fn base_post(title: &str, link: &str, created: &str) -> BasePosts {
+        BasePosts::new(
+            title.to_string(),
+            created.to_string(),
+            created.to_string(),
+            link.to_string(),
+            String::from("feed"),
+        )
+    }
+
+    /// `author`的文章，在文章创建当天获取
+    pub fn post(meta: BasePosts, author: &str) -> Posts {
+        let created_at = format!("{} 00:00:00", meta.created);
+        Posts::new(meta, author.to_string(), String::new(), created_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::config::ApiSettings;
+    use super::metadata::{BasePosts, Posts};
+    use super::query_params::{AllQueryParams, RandomQueryParams, SearchQueryParams};
+    use super::response::{Pagination, SearchData};
+
+    // 测试超大的page和cursor不会溢出
+    #[test]
+    fn test_pagination_overflow() {
+        let params = AllQueryParams {
+            page: Some(usize::MAX),
+            limit: Some(10),
+            ..Default::default()
+        };
+        assert!(params.pagination().is_err());
+
+        let params = AllQueryParams {
+            cursor: Some(String::from("offffffffffffffff")),
+            limit: Some(10),
+            ..Default::default()
+        };
+        assert!(params.pagination().is_err());
+
+        let params = AllQueryParams {
+            page: Some(3),
+            limit: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(params.pagination(), Ok((20, 10)));
+
+        let pagination = Pagination::new(100, usize::MAX, 10);
+        assert_eq!(pagination.next_cursor, None);
+    }
+
+    // 测试`/all`只接受created和updated两种排序规则
+    #[test]
+    fn test_all_sort_rule() {
+        let params = |rule: Option<&str>| AllQueryParams {
+            sort_rule: rule.map(String::from),
+            ..Default::default()
+        };
+        assert_eq!(params(None).checked_sort_rule(), Ok("updated"));
+        assert_eq!(params(Some("created")).checked_sort_rule(), Ok("created"));
+        assert!(
+            params(Some("updated; DROP TABLE posts"))
+                .checked_sort_rule()
+                .is_err()
+        );
+    }
+
+    // 测试高亮时转义标题中的HTML
+    #[test]
+    fn test_search_highlight_escape() {
+        let meta = BasePosts::new(
+            String::from("<script>alert('Rust')</script> & rust"),
+            String::from("2024-01-01"),
+            String::from("2024-01-01"),
+            String::from("https://a.com/"),
+            String::from("feed"),
+        );
+        let posts = Posts::new(meta, String::new(), String::new(), String::new());
+        let data = SearchData::new(String::from("rust"), vec![posts]);
+        assert_eq!(
+            data.article_data[0].highlight,
+            "&lt;script&gt;alert(&#39;<mark>Rust</mark>&#39;)&lt;/script&gt; &amp; <mark>rust</mark>"
+        );
+    }
+
+    // 测试路径前缀的规范化和跨域来源的判断
+    #[test]
+    fn test_api_settings() {
+        let api = |path_prefix: &str, allowed_origins: &[&str]| ApiSettings {
+            path_prefix: path_prefix.to_string(),
+            allowed_origins: allowed_origins.iter().map(|o| o.to_string()).collect(),
+            ..Default::default()
+        };
+        assert_eq!(
+            api("fcircle/", &[]).path_prefix().as_deref(),
+            Some("/fcircle")
+        );
+        assert_eq!(api(" /a/b/ ", &[]).path_prefix().as_deref(), Some("/a/b"));
+        assert_eq!(api("/", &[]).path_prefix(), None);
+        assert_eq!(api("", &[]).path_prefix(), None);
+
+        assert!(api("", &[]).allow_any_origin());
+        assert!(api("", &["https://a.com", "*"]).allow_any_origin());
+        assert!(!api("", &["https://a.com"]).allow_any_origin());
+    }
+
+    // 测试随机文章数的上限
+    #[test]
+    fn test_random_posts_num() {
+        let params = |num| RandomQueryParams { num };
+        assert_eq!(params(None).posts_num(), Ok(1));
+        assert_eq!(params(Some(100)).posts_num(), Ok(100));
+        assert!(params(Some(101)).posts_num().is_err());
+    }
+
+    // 测试检索结果数的上限
+    #[test]
+    fn test_search_posts_num() {
+        let params = |num| SearchQueryParams { q: None, num };
+        assert_eq!(params(None).posts_num(), Ok(20));
+        assert_eq!(params(Some(100)).posts_num(), Ok(100));
+        assert!(params(Some(usize::MAX)).posts_num().is_err());
+    }
+}
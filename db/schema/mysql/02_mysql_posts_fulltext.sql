@@ -0,0 +1,2 @@
+-- 文章标题全文检索，ngram分词以支持中文
+ALTER TABLE `posts` ADD FULLTEXT INDEX `ft_posts_title` (`title`) WITH PARSER ngram;
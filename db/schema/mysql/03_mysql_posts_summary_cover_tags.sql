@@ -0,0 +1,8 @@
+ALTER TABLE `posts`
+  ADD COLUMN `summary` TEXT DEFAULT NULL,
+  ADD COLUMN `cover` varchar(1024) DEFAULT NULL,
+  ADD COLUMN `tags` varchar(1024) NOT NULL DEFAULT '';
+
+-- 全文检索同时覆盖标题和摘要
+ALTER TABLE `posts` DROP INDEX `ft_posts_title`;
+ALTER TABLE `posts` ADD FULLTEXT INDEX `ft_posts_title_summary` (`title`, `summary`) WITH PARSER ngram;
@@ -0,0 +1 @@
+ALTER TABLE `friends` ADD COLUMN `error_kind` varchar(32) DEFAULT NULL;
@@ -0,0 +1,21 @@
+-- 通过管理接口添加的友链，每次运行时与友链页中的友链一起抓取
+-- utf8mb4下唯一索引最长3072字节，因此link和pattern最长768个字符
+CREATE TABLE `manual_friends` (
+  `id` int NOT NULL AUTO_INCREMENT,
+  `name` varchar(256) NOT NULL,
+  `link` varchar(768) NOT NULL,
+  `avatar` varchar(1024) NOT NULL DEFAULT '',
+  `suffix` varchar(256) NOT NULL DEFAULT '',
+  `createdAt` VARCHAR(1024) NOT NULL,
+  PRIMARY KEY (`id`),
+  UNIQUE KEY `uk_manual_friends_link` (`link`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_0900_ai_ci;
+
+-- 通过管理接口添加的屏蔽站点，与BLOCK_SITE合并使用
+CREATE TABLE `blocked_sites` (
+  `id` int NOT NULL AUTO_INCREMENT,
+  `pattern` varchar(768) NOT NULL,
+  `createdAt` VARCHAR(1024) NOT NULL,
+  PRIMARY KEY (`id`),
+  UNIQUE KEY `uk_blocked_sites_pattern` (`pattern`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_0900_ai_ci;
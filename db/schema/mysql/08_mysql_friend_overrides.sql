@@ -0,0 +1,15 @@
+-- 按友链地址覆盖抓取和展示方式，空值表示不覆盖
+CREATE TABLE `friend_overrides` (
+  `id` int NOT NULL AUTO_INCREMENT,
+  `link` varchar(768) NOT NULL,
+  `name` varchar(256) NOT NULL DEFAULT '',
+  `feed` varchar(1024) NOT NULL DEFAULT '',
+  `theme` varchar(256) NOT NULL DEFAULT '',
+  `hidden` tinyint(1) NOT NULL DEFAULT '0',
+  `pinned` tinyint(1) NOT NULL DEFAULT '0',
+  `createdAt` VARCHAR(1024) NOT NULL,
+  PRIMARY KEY (`id`),
+  UNIQUE KEY `uk_friend_overrides_link` (`link`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_0900_ai_ci;
+
+ALTER TABLE `friends` ADD COLUMN `pinned` tinyint(1) NOT NULL DEFAULT '0';
@@ -0,0 +1,2 @@
+-- 每个友链保留的文章数，不大于0时使用MAX_POSTS_NUM
+ALTER TABLE `friend_overrides` ADD COLUMN `max_posts` int NOT NULL DEFAULT '0';
@@ -0,0 +1,3 @@
+-- 头像地址无效不再记为获取失败的原因
+ALTER TABLE `friends` ADD COLUMN `invalid_avatar` tinyint(1) NOT NULL DEFAULT '0';
+UPDATE `friends` SET `invalid_avatar` = 1, `error_kind` = NULL WHERE `error_kind` = 'invalid_avatar';
@@ -0,0 +1,22 @@
+-- 文章标题全文检索，trigram分词以支持中文
+CREATE VIRTUAL TABLE posts_fts USING fts5(
+	title,
+	content = 'posts',
+	content_rowid = 'id',
+	tokenize = 'trigram'
+);
+
+INSERT INTO posts_fts(posts_fts) VALUES ('rebuild');
+
+CREATE TRIGGER posts_fts_ai AFTER INSERT ON posts BEGIN
+	INSERT INTO posts_fts(rowid, title) VALUES (new.id, new.title);
+END;
+
+CREATE TRIGGER posts_fts_ad AFTER DELETE ON posts BEGIN
+	INSERT INTO posts_fts(posts_fts, rowid, title) VALUES ('delete', old.id, old.title);
+END;
+
+CREATE TRIGGER posts_fts_au AFTER UPDATE ON posts BEGIN
+	INSERT INTO posts_fts(posts_fts, rowid, title) VALUES ('delete', old.id, old.title);
+	INSERT INTO posts_fts(rowid, title) VALUES (new.id, new.title);
+END;
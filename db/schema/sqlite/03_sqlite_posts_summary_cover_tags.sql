@@ -0,0 +1,32 @@
+ALTER TABLE posts ADD COLUMN summary TEXT;
+ALTER TABLE posts ADD COLUMN cover VARCHAR(1024);
+ALTER TABLE posts ADD COLUMN tags VARCHAR(1024) NOT NULL DEFAULT '';
+
+-- 全文检索同时覆盖标题和摘要
+DROP TRIGGER posts_fts_ai;
+DROP TRIGGER posts_fts_ad;
+DROP TRIGGER posts_fts_au;
+DROP TABLE posts_fts;
+
+CREATE VIRTUAL TABLE posts_fts USING fts5(
+	title,
+	summary,
+	content = 'posts',
+	content_rowid = 'id',
+	tokenize = 'trigram'
+);
+
+INSERT INTO posts_fts(posts_fts) VALUES ('rebuild');
+
+CREATE TRIGGER posts_fts_ai AFTER INSERT ON posts BEGIN
+	INSERT INTO posts_fts(rowid, title, summary) VALUES (new.id, new.title, new.summary);
+END;
+
+CREATE TRIGGER posts_fts_ad AFTER DELETE ON posts BEGIN
+	INSERT INTO posts_fts(posts_fts, rowid, title, summary) VALUES ('delete', old.id, old.title, old.summary);
+END;
+
+CREATE TRIGGER posts_fts_au AFTER UPDATE ON posts BEGIN
+	INSERT INTO posts_fts(posts_fts, rowid, title, summary) VALUES ('delete', old.id, old.title, old.summary);
+	INSERT INTO posts_fts(rowid, title, summary) VALUES (new.id, new.title, new.summary);
+END;
@@ -0,0 +1 @@
+ALTER TABLE friends ADD COLUMN error_kind VARCHAR(32);
@@ -0,0 +1 @@
+ALTER TABLE friends ADD COLUMN latest_post VARCHAR(256);
@@ -0,0 +1,6 @@
+ALTER TABLE posts ADD COLUMN first_seen VARCHAR(256) NOT NULL DEFAULT '';
+
+-- 已有文章无法得知第一次获取的时间，以本次写入时间代替
+UPDATE posts SET first_seen = createdAt;
+
+CREATE INDEX idx_posts_first_seen ON posts (first_seen);
@@ -0,0 +1,18 @@
+-- 通过管理接口添加的友链，每次运行时与友链页中的友链一起抓取
+CREATE TABLE manual_friends (
+	id INTEGER NOT NULL,
+	name VARCHAR(256) NOT NULL,
+	link VARCHAR(1024) NOT NULL UNIQUE,
+	avatar VARCHAR(1024) NOT NULL DEFAULT '',
+	suffix VARCHAR(256) NOT NULL DEFAULT '',
+	"createdAt" VARCHAR(1024) NOT NULL,
+	PRIMARY KEY (id)
+);
+
+-- 通过管理接口添加的屏蔽站点，与BLOCK_SITE合并使用
+CREATE TABLE blocked_sites (
+	id INTEGER NOT NULL,
+	pattern VARCHAR(1024) NOT NULL UNIQUE,
+	"createdAt" VARCHAR(1024) NOT NULL,
+	PRIMARY KEY (id)
+);
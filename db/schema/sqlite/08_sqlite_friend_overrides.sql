@@ -0,0 +1,14 @@
+-- 按友链地址覆盖抓取和展示方式，空值表示不覆盖
+CREATE TABLE friend_overrides (
+	id INTEGER NOT NULL,
+	link VARCHAR(1024) NOT NULL UNIQUE,
+	name VARCHAR(256) NOT NULL DEFAULT '',
+	feed VARCHAR(1024) NOT NULL DEFAULT '',
+	theme VARCHAR(256) NOT NULL DEFAULT '',
+	hidden BOOLEAN NOT NULL DEFAULT FALSE,
+	pinned BOOLEAN NOT NULL DEFAULT FALSE,
+	"createdAt" VARCHAR(1024) NOT NULL,
+	PRIMARY KEY (id)
+);
+
+ALTER TABLE friends ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT FALSE;
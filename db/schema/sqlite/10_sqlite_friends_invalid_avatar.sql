@@ -0,0 +1,3 @@
+-- 头像地址无效不再记为获取失败的原因
+ALTER TABLE friends ADD COLUMN invalid_avatar BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE friends SET invalid_avatar = TRUE, error_kind = NULL WHERE error_kind = 'invalid_avatar';
@@ -4,3 +4,20 @@ pub mod sqlite;
 
 pub use mongodb::Database as MongoDatabase;
 pub use sqlx::{MySqlPool, SqlitePool};
+
+use data_structures::config::PostSortBy;
+
+/// 文章排序使用的列
+fn post_sort_column(sort_by: PostSortBy) -> &'static str {
+    match sort_by {
+        PostSortBy::Created => "created",
+        PostSortBy::Updated => "updated",
+    }
+}
+
+/// 转义`LIKE`中的`\`、`%`和`_`，需配合`ESCAPE '\'`使用
+fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
@@ -1,11 +1,15 @@
 use chrono::{Duration, Local};
-use data_structures::metadata::{self, Friends, Posts};
+use data_structures::{
+    config::PostSortBy,
+    metadata::{self, BlockedSite, FriendOverride, Friends, ManualFriend, Posts},
+    query_params::PostFilter,
+};
 use futures::TryStreamExt;
 use mongodb::{
-    Client, Database as MongoDatabase,
-    bson::{Regex, doc},
+    Client, Database as MongoDatabase, IndexModel,
+    bson::{Bson, Document, Regex, doc},
     error::Error,
-    options::ClientOptions,
+    options::{ClientOptions, IndexOptions},
 };
 
 pub async fn connect_mongodb_clientdb(
@@ -64,6 +68,21 @@ pub async fn delete_post_table(
     Ok(())
 }
 
+/// 删除作者为`author`或链接以友链`link`开头的文章，返回删除的数量
+pub async fn delete_friend_posts(
+    link: &str,
+    author: &str,
+    db: &MongoDatabase,
+) -> Result<usize, Error> {
+    let collection = db.collection::<Posts>("Posts");
+    let filter = doc! {"$or": [
+        {"author": author},
+        {"$expr": {"$eq": [{"$indexOfCP": ["$link", link]}, 0]}},
+    ]};
+    let result = collection.delete_many(filter).await?;
+    Ok(result.deleted_count as usize)
+}
+
 pub async fn truncate_friend_table(db: &MongoDatabase) -> Result<(), Box<dyn std::error::Error>> {
     let collection = db.collection::<Friends>("Friends");
     collection.drop().await?;
@@ -76,19 +95,90 @@ pub async fn truncate_friend_table(db: &MongoDatabase) -> Result<(), Box<dyn std
 ///
 /// 如果`start`和`end`同时为0，则查询全部；
 ///
-/// 否则只查询`start-end`条数据，如果`start>end`，会报错
+/// 如果`end`为0，则查询`start`之后的全部数据；
+///
+/// 否则只查询`start-end`条数据，如果`start>end`，返回空
 pub async fn select_all_from_posts(
     pool: &MongoDatabase,
     start: usize,
     end: usize,
     sort_rule: &str,
+) -> Result<Vec<metadata::Posts>, Error> {
+    select_all_from_posts_with_filter(pool, &PostFilter::default(), start, end, sort_rule).await
+}
+
+/// 转义正则表达式中的特殊字符，用于关键字的字面匹配
+fn escape_regex(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "\\.+*?()|[]{}^$#&-~".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// 将过滤条件转换为查询文档
+fn post_filter_doc(filter: &PostFilter) -> Document {
+    let mut filter_doc = doc! {};
+    if let Some(author) = &filter.author {
+        filter_doc.insert("author", author);
+    }
+    if let Some(keyword) = &filter.keyword {
+        let re = Regex {
+            pattern: escape_regex(keyword),
+            options: String::from("i"),
+        };
+        filter_doc.insert("title", re);
+    }
+    for (field, since, until) in [
+        ("created", &filter.created_since, &filter.created_until),
+        ("updated", &filter.updated_since, &filter.updated_until),
+    ] {
+        let mut range = doc! {};
+        if let Some(since) = since {
+            range.insert("$gte", since);
+        }
+        if let Some(until) = until {
+            range.insert("$lte", until);
+        }
+        if !range.is_empty() {
+            filter_doc.insert(field, range);
+        }
+    }
+    if let Some(since) = &filter.since {
+        filter_doc.insert("first_seen", doc! { "$gt": since });
+    }
+    filter_doc
+}
+
+/// 按照过滤条件查询`posts`表，分页规则与[`select_all_from_posts`]相同
+pub async fn select_all_from_posts_with_filter(
+    pool: &MongoDatabase,
+    filter: &PostFilter,
+    start: usize,
+    end: usize,
+    sort_rule: &str,
 ) -> Result<Vec<metadata::Posts>, Error> {
     let collection = pool.collection::<Posts>("Posts");
+    let filter_doc = post_filter_doc(filter);
     let cursor = if start == 0 && end == 0 {
-        collection.find(doc! {}).sort(doc! {sort_rule: -1}).await?
+        collection
+            .find(filter_doc)
+            .sort(doc! {sort_rule: -1})
+            .await?
+    } else if end == 0 {
+        collection
+            .find(filter_doc)
+            .sort(doc! {sort_rule: -1})
+            .skip(start as u64)
+            .await?
+    } else if start >= end {
+        return Ok(Vec::new());
     } else {
         collection
-            .find(doc! {})
+            .find(filter_doc)
             .sort(doc! {sort_rule: -1})
             .limit((end - start) as i64)
             .skip(start as u64)
@@ -98,6 +188,126 @@ pub async fn select_all_from_posts(
     Ok(posts)
 }
 
+/// 为`posts`表的标题和摘要创建全文索引，索引已存在时不做处理
+pub async fn create_posts_text_index(db: &MongoDatabase) -> Result<(), Error> {
+    let collection = db.collection::<Posts>("Posts");
+    // 每个集合只能有一个全文索引，删除旧版本仅包含标题的索引
+    let _ = collection.drop_index("posts_title_text").await;
+    let options = IndexOptions::builder()
+        .name(String::from("posts_title_summary_text"))
+        // 不使用词干提取和停用词，避免影响中文标题
+        .default_language(String::from("none"))
+        .build();
+    let index = IndexModel::builder()
+        .keys(doc! {"title": "text", "summary": "text"})
+        .options(options)
+        .build();
+    collection.create_index(index).await?;
+    Ok(())
+}
+
+/// 按标题和摘要全文检索`posts`表，结果按相关度排序，最多返回`num`条
+///
+/// 依赖[`create_posts_text_index`]创建的索引
+pub async fn search_posts(
+    pool: &MongoDatabase,
+    keyword: &str,
+    num: usize,
+) -> Result<Vec<metadata::Posts>, Error> {
+    let collection = pool.collection::<Posts>("Posts");
+    let cursor = collection
+        .find(doc! {"$text": {"$search": keyword}})
+        .projection(doc! {"score": {"$meta": "textScore"}})
+        .sort(doc! {"score": {"$meta": "textScore"}})
+        .limit(num as i64)
+        .await?;
+    let posts = cursor.try_collect().await?;
+    Ok(posts)
+}
+
+/// 统计`posts`表中每个标签的文章数，标签区分大小写
+pub async fn select_tag_counts_from_posts(
+    pool: &MongoDatabase,
+) -> Result<Vec<(String, usize)>, Error> {
+    let collection = pool.collection::<Posts>("Posts");
+    let pipeline = vec![
+        doc! {"$unwind": "$tags"},
+        doc! {"$group": {"_id": "$tags", "count": {"$sum": 1}}},
+    ];
+    let mut cursor = collection.aggregate(pipeline).await?;
+    let mut counts = Vec::new();
+    while let Some(result) = cursor.try_next().await? {
+        if let (Ok(tag), Ok(count)) = (result.get_str("_id"), result.get_i32("count")) {
+            counts.push((tag.to_string(), count as usize));
+        }
+    }
+    Ok(counts)
+}
+
+/// 查询`posts`表中含有`tags`任意一个标签的数据，标签忽略大小写
+///
+/// 当num<0时，返回所有数据；`sort_rule`只能为`created`或`updated`，其他值按`created`排序
+pub async fn select_all_from_posts_with_tags(
+    pool: &MongoDatabase,
+    tags: &[String],
+    num: i32,
+    sort_rule: &str,
+) -> Result<Vec<metadata::Posts>, Error> {
+    if tags.is_empty() {
+        return Ok(Vec::new());
+    }
+    let sort_rule = match sort_rule {
+        "updated" => "updated",
+        _ => "created",
+    };
+    let collection = pool.collection::<Posts>("Posts");
+    let patterns: Vec<Regex> = tags
+        .iter()
+        .map(|tag| Regex {
+            pattern: format!("^{}$", escape_regex(tag)),
+            options: String::from("i"),
+        })
+        .collect();
+    let filter = doc! {"tags": {"$in": patterns}};
+    let cursor = if num >= 0 {
+        collection
+            .find(filter)
+            .sort(doc! {sort_rule: -1})
+            .limit(num as i64)
+            .await?
+    } else {
+        collection.find(filter).sort(doc! {sort_rule: -1}).await?
+    };
+    let posts = cursor.try_collect().await?;
+    Ok(posts)
+}
+
+/// 查询`posts`表中`first_seen`不早于`since`的数据，按`first_seen`倒序
+///
+/// 当num<0时，返回所有数据
+pub async fn select_new_posts(
+    pool: &MongoDatabase,
+    since: &str,
+    num: i32,
+) -> Result<Vec<metadata::Posts>, Error> {
+    let collection = pool.collection::<Posts>("Posts");
+    let filter = doc! {"first_seen": {"$gte": since}};
+    let sort = doc! {"first_seen": -1, "updated": -1};
+    let cursor = if num >= 0 {
+        collection.find(filter).sort(sort).limit(num as i64).await?
+    } else {
+        collection.find(filter).sort(sort).await?
+    };
+    let posts = cursor.try_collect().await?;
+    Ok(posts)
+}
+
+/// 检查数据库是否可以连接
+pub async fn ping(pool: &MongoDatabase) -> Result<(), Error> {
+    pool.run_command(doc! {"ping": 1}).await?;
+    Ok(())
+}
+
 /// 获取`posts`表中最近一次更新（`createdAt`最新）的时间
 pub async fn select_latest_time_from_posts(pool: &MongoDatabase) -> Result<String, Error> {
     let collection = pool.collection::<Posts>("Posts");
@@ -112,11 +322,64 @@ pub async fn select_latest_time_from_posts(pool: &MongoDatabase) -> Result<Strin
     }
 }
 
+/// 统计`posts`表的数据总数
+pub async fn select_count_from_posts(pool: &MongoDatabase) -> Result<usize, Error> {
+    select_count_from_posts_with_filter(pool, &PostFilter::default()).await
+}
+
+/// 统计`posts`表中符合过滤条件的数据总数
+pub async fn select_count_from_posts_with_filter(
+    pool: &MongoDatabase,
+    filter: &PostFilter,
+) -> Result<usize, Error> {
+    let collection = pool.collection::<Posts>("Posts");
+    let num = collection.count_documents(post_filter_doc(filter)).await?;
+    Ok(num as usize)
+}
+
+/// 统计`friends`表的数据，返回`(总数, 失联数)`
+pub async fn select_count_from_friends(pool: &MongoDatabase) -> Result<(usize, usize), Error> {
+    let collection = pool.collection::<Friends>("Friends");
+    let total = collection.count_documents(doc! {}).await?;
+    let error_num = collection.count_documents(doc! {"error": true}).await?;
+    Ok((total as usize, error_num as usize))
+}
+
+/// 统计可以访问但最新文章早于`before`的友链数
+pub async fn select_dormant_count_from_friends(
+    pool: &MongoDatabase,
+    before: &str,
+) -> Result<usize, Error> {
+    let collection = pool.collection::<Friends>("Friends");
+    let dormant_num = collection
+        .count_documents(doc! {"error": false, "latest_post": {"$lt": before}})
+        .await?;
+    Ok(dormant_num as usize)
+}
+
+/// 随机查询`posts`表中的`num`条数据
+pub async fn select_random_posts(
+    pool: &MongoDatabase,
+    num: usize,
+) -> Result<Vec<metadata::Posts>, Error> {
+    let collection = pool.collection::<Posts>("Posts");
+    let cursor = collection
+        .aggregate(vec![doc! {"$sample": {"size": num as i64}}])
+        .with_type::<Posts>()
+        .await?;
+    let posts = cursor.try_collect().await?;
+    Ok(posts)
+}
+
+/// 查询`friends`表的所有数据，置顶的友链在前
 pub async fn select_all_from_friends(
     pool: &MongoDatabase,
 ) -> Result<Vec<metadata::Friends>, Error> {
     let collection = pool.collection::<Friends>("Friends");
-    let cursor = collection.find(doc! {}).await?;
+    let cursor = collection
+        .find(doc! {})
+        .sort(doc! {"pinned": -1, "_id": 1})
+        .await?;
     let friends = cursor.try_collect().await?;
     Ok(friends)
 }
@@ -170,6 +433,141 @@ pub async fn select_all_from_posts_with_linklike(
     Ok(posts)
 }
 
+/// 为手动添加的友链、屏蔽站点和友链覆盖设置创建唯一索引，重复插入时返回错误
+pub async fn create_admin_indexes(db: &MongoDatabase) -> Result<(), Error> {
+    let unique = IndexOptions::builder().unique(true).build();
+    db.collection::<ManualFriend>("ManualFriends")
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! {"link": 1})
+                .options(unique.clone())
+                .build(),
+        )
+        .await?;
+    db.collection::<BlockedSite>("BlockedSites")
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! {"pattern": 1})
+                .options(unique.clone())
+                .build(),
+        )
+        .await?;
+    db.collection::<FriendOverride>("FriendOverrides")
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! {"link": 1})
+                .options(unique)
+                .build(),
+        )
+        .await?;
+    Ok(())
+}
+
+/// 插入一条手动添加的友链，`link`已存在时返回错误
+///
+/// 依赖[`create_admin_indexes`]创建的索引
+pub async fn insert_manual_friend(friend: &ManualFriend, db: &MongoDatabase) -> Result<(), Error> {
+    let collection = db.collection::<ManualFriend>("ManualFriends");
+    collection.insert_one(friend).await?;
+    Ok(())
+}
+
+/// 按`link`修改手动添加的友链，返回修改的数量
+pub async fn update_manual_friend(
+    friend: &ManualFriend,
+    db: &MongoDatabase,
+) -> Result<usize, Error> {
+    let collection = db.collection::<ManualFriend>("ManualFriends");
+    let result = collection
+        .update_one(
+            doc! {"link": &friend.link},
+            doc! {"$set": {"name": &friend.name, "avatar": &friend.avatar, "suffix": &friend.suffix}},
+        )
+        .await?;
+    Ok(result.matched_count as usize)
+}
+
+/// 按`link`删除手动添加的友链，返回删除的数量
+pub async fn delete_manual_friend(link: &str, db: &MongoDatabase) -> Result<usize, Error> {
+    let collection = db.collection::<ManualFriend>("ManualFriends");
+    let result = collection.delete_one(doc! {"link": link}).await?;
+    Ok(result.deleted_count as usize)
+}
+
+pub async fn select_all_from_manual_friends(
+    db: &MongoDatabase,
+) -> Result<Vec<ManualFriend>, Error> {
+    let collection = db.collection::<ManualFriend>("ManualFriends");
+    let cursor = collection.find(doc! {}).sort(doc! {"_id": 1}).await?;
+    let friends = cursor.try_collect().await?;
+    Ok(friends)
+}
+
+/// 添加或修改友链的覆盖设置，`link`已存在时只修改，保留原来的`createdAt`
+pub async fn upsert_friend_override(
+    friend_override: &FriendOverride,
+    db: &MongoDatabase,
+) -> Result<(), Error> {
+    let collection = db.collection::<FriendOverride>("FriendOverrides");
+    collection
+        .update_one(
+            doc! {"link": &friend_override.link},
+            doc! {
+                "$set": {
+                    "name": &friend_override.name,
+                    "feed": &friend_override.feed,
+                    "theme": &friend_override.theme,
+                    "hidden": friend_override.hidden,
+                    "pinned": friend_override.pinned,
+                    "max_posts": friend_override.max_posts,
+                },
+                "$setOnInsert": {"createdAt": &friend_override.created_at},
+            },
+        )
+        .upsert(true)
+        .await?;
+    Ok(())
+}
+
+/// 按`link`删除友链的覆盖设置，返回删除的数量
+pub async fn delete_friend_override(link: &str, db: &MongoDatabase) -> Result<usize, Error> {
+    let collection = db.collection::<FriendOverride>("FriendOverrides");
+    let result = collection.delete_one(doc! {"link": link}).await?;
+    Ok(result.deleted_count as usize)
+}
+
+pub async fn select_all_from_friend_overrides(
+    db: &MongoDatabase,
+) -> Result<Vec<FriendOverride>, Error> {
+    let collection = db.collection::<FriendOverride>("FriendOverrides");
+    let cursor = collection.find(doc! {}).sort(doc! {"_id": 1}).await?;
+    let overrides = cursor.try_collect().await?;
+    Ok(overrides)
+}
+
+/// 插入一条屏蔽站点，`pattern`已存在时返回错误
+///
+/// 依赖[`create_admin_indexes`]创建的索引
+pub async fn insert_blocked_site(site: &BlockedSite, db: &MongoDatabase) -> Result<(), Error> {
+    let collection = db.collection::<BlockedSite>("BlockedSites");
+    collection.insert_one(site).await?;
+    Ok(())
+}
+
+/// 删除屏蔽站点，返回删除的数量
+pub async fn delete_blocked_site(pattern: &str, db: &MongoDatabase) -> Result<usize, Error> {
+    let collection = db.collection::<BlockedSite>("BlockedSites");
+    let result = collection.delete_one(doc! {"pattern": pattern}).await?;
+    Ok(result.deleted_count as usize)
+}
+
+pub async fn select_all_from_blocked_sites(db: &MongoDatabase) -> Result<Vec<BlockedSite>, Error> {
+    let collection = db.collection::<BlockedSite>("BlockedSites");
+    let cursor = collection.find(doc! {}).sort(doc! {"_id": 1}).await?;
+    let sites = cursor.try_collect().await?;
+    Ok(sites)
+}
+
 pub async fn delete_outdated_posts(days: usize, clientdb: &MongoDatabase) -> Result<usize, Error> {
     let now = Local::now() - Duration::days(days as i64);
     let collection = clientdb.collection::<Posts>("Posts");
@@ -178,10 +576,42 @@ pub async fn delete_outdated_posts(days: usize, clientdb: &MongoDatabase) -> Res
     Ok(result.deleted_count as usize)
 }
 
+/// 只保留按`sort_by`从新到旧排序的前`max_total`篇文章，返回删除的数量，0表示不限制
+///
+/// 时间相同时保留先写入的
+pub async fn delete_excess_posts(
+    max_total: usize,
+    sort_by: PostSortBy,
+    clientdb: &MongoDatabase,
+) -> Result<usize, Error> {
+    if max_total == 0 {
+        return Ok(0);
+    }
+    let collection = clientdb.collection::<Document>("Posts");
+    let excess: Vec<Document> = collection
+        .find(doc! {})
+        .sort(doc! {crate::post_sort_column(sort_by): -1, "_id": 1})
+        .skip(max_total as u64)
+        .projection(doc! {"_id": 1})
+        .await?
+        .try_collect()
+        .await?;
+    let ids: Vec<Bson> = excess
+        .into_iter()
+        .filter_map(|post| post.get("_id").cloned())
+        .collect();
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let result = collection.delete_many(doc! {"_id": {"$in": ids}}).await?;
+    Ok(result.deleted_count as usize)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use data_structures::metadata::{BasePosts, Friends, Posts};
+    use data_structures::metadata::{BasePosts, FailureKind, Friends, Posts, Tags};
+    use data_structures::testing::{base_post, friend, post};
     use std::time::SystemTime;
 
     // MongoDB连接URI
@@ -194,6 +624,12 @@ mod tests {
         // 清空集合以确保测试环境干净
         let _ = db.collection::<Friends>("Friends").drop().await;
         let _ = db.collection::<Posts>("Posts").drop().await;
+        let _ = db.collection::<ManualFriend>("ManualFriends").drop().await;
+        let _ = db.collection::<BlockedSite>("BlockedSites").drop().await;
+        let _ = db
+            .collection::<FriendOverride>("FriendOverrides")
+            .drop()
+            .await;
 
         db
     }
@@ -217,6 +653,10 @@ mod tests {
             error: false,
             avatar: "https://example.com/avatar.jpg".to_string(),
             created_at: SystemTime::now().elapsed().unwrap().as_secs().to_string(),
+            error_kind: None,
+            invalid_avatar: false,
+            latest_post: None,
+            pinned: false,
         };
 
         // 插入数据
@@ -251,6 +691,7 @@ mod tests {
             updated: "2023-01-01".to_string(),
             link: "https://example.com/post".to_string(),
             rule: "test".to_string(),
+            ..Default::default()
         };
 
         let post = Posts {
@@ -258,6 +699,7 @@ mod tests {
             author: "测试作者".to_string(),
             avatar: "https://example.com/avatar.jpg".to_string(),
             created_at: SystemTime::now().elapsed().unwrap().as_secs().to_string(),
+            first_seen: String::new(),
         };
 
         // 插入数据
@@ -288,6 +730,10 @@ mod tests {
                 error: false,
                 avatar: "https://example1.com/avatar.jpg".to_string(),
                 created_at: SystemTime::now().elapsed().unwrap().as_secs().to_string(),
+                error_kind: None,
+                invalid_avatar: false,
+                latest_post: None,
+                pinned: false,
             },
             Friends {
                 name: "用户2".to_string(),
@@ -295,6 +741,10 @@ mod tests {
                 error: false,
                 avatar: "https://example2.com/avatar.jpg".to_string(),
                 created_at: SystemTime::now().elapsed().unwrap().as_secs().to_string(),
+                error_kind: None,
+                invalid_avatar: false,
+                latest_post: None,
+                pinned: false,
             },
         ];
 
@@ -333,10 +783,12 @@ mod tests {
                     updated: "2023-01-01".to_string(),
                     link: "https://example.com/post1".to_string(),
                     rule: "test".to_string(),
+                    ..Default::default()
                 },
                 author: "作者1".to_string(),
                 avatar: "https://example.com/avatar1.jpg".to_string(),
                 created_at: "2023-01-01".to_string(),
+                first_seen: String::new(),
             },
             Posts {
                 meta: BasePosts {
@@ -345,10 +797,12 @@ mod tests {
                     updated: "2023-01-02".to_string(),
                     link: "https://example.com/post2".to_string(),
                     rule: "test".to_string(),
+                    ..Default::default()
                 },
                 author: "作者2".to_string(),
                 avatar: "https://example.com/avatar2.jpg".to_string(),
                 created_at: "2023-01-02".to_string(),
+                first_seen: String::new(),
             },
         ];
 
@@ -382,10 +836,12 @@ mod tests {
                     updated: "2023-01-01".to_string(),
                     link: "https://example.com/post1".to_string(),
                     rule: "test".to_string(),
+                    ..Default::default()
                 },
                 author: "作者1".to_string(),
                 avatar: "https://example.com/avatar1.jpg".to_string(),
                 created_at: "2023-01-01".to_string(),
+                first_seen: String::new(),
             },
             Posts {
                 meta: BasePosts {
@@ -394,10 +850,12 @@ mod tests {
                     updated: "2023-01-02".to_string(),
                     link: "https://example.com/post2".to_string(),
                     rule: "test".to_string(),
+                    ..Default::default()
                 },
                 author: "作者2".to_string(),
                 avatar: "https://example.com/avatar2.jpg".to_string(),
                 created_at: "2023-01-02".to_string(),
+                first_seen: String::new(),
             },
         ];
 
@@ -437,6 +895,10 @@ mod tests {
             error: false,
             avatar: "https://example.com/avatar.jpg".to_string(),
             created_at: "2023-01-01".to_string(),
+            error_kind: None,
+            invalid_avatar: false,
+            latest_post: None,
+            pinned: false,
         };
 
         insert_friend_table(&friend, &db).await.unwrap();
@@ -469,18 +931,14 @@ mod tests {
         // 创建测试数据 - 插入5篇文章
         let mut posts = Vec::new();
         for i in 1..=5 {
-            posts.push(Posts {
-                meta: BasePosts {
-                    title: format!("帖子{i}"),
-                    created: format!("2023-01-0{i}"),
-                    updated: format!("2023-01-0{i}"),
-                    link: format!("https://example.com/post{i}"),
-                    rule: "test".to_string(),
-                },
-                author: format!("作者{i}"),
-                avatar: format!("https://example.com/avatar{i}.jpg"),
-                created_at: format!("2023-01-0{i}"),
-            });
+            posts.push(post(
+                base_post(
+                    &format!("帖子{i}"),
+                    &format!("https://example.com/post{i}"),
+                    &format!("2023-01-0{i}"),
+                ),
+                &format!("作者{i}"),
+            ));
         }
 
         // 批量插入
@@ -520,10 +978,12 @@ mod tests {
                     updated: "2023-01-01".to_string(),
                     link: "https://example.com/post1".to_string(),
                     rule: "test".to_string(),
+                    ..Default::default()
                 },
                 author: "作者1".to_string(),
                 avatar: "https://example.com/avatar1.jpg".to_string(),
                 created_at: "2023-01-01".to_string(),
+                first_seen: String::new(),
             },
             Posts {
                 meta: BasePosts {
@@ -532,10 +992,12 @@ mod tests {
                     updated: "2023-01-02".to_string(),
                     link: "https://example.com/post2".to_string(),
                     rule: "test".to_string(),
+                    ..Default::default()
                 },
                 author: "作者2".to_string(),
                 avatar: "https://example.com/avatar2.jpg".to_string(),
                 created_at: "2023-01-02".to_string(),
+                first_seen: String::new(),
             },
         ];
         bulk_insert_post_table(posts.into_iter(), &db)
@@ -556,6 +1018,10 @@ mod tests {
                 error: false,
                 avatar: "https://example1.com/avatar.jpg".to_string(),
                 created_at: "2023-01-01".to_string(),
+                error_kind: None,
+                invalid_avatar: false,
+                latest_post: None,
+                pinned: false,
             },
             Friends {
                 name: "用户2".to_string(),
@@ -563,6 +1029,10 @@ mod tests {
                 error: false,
                 avatar: "https://example2.com/avatar.jpg".to_string(),
                 created_at: "2023-01-02".to_string(),
+                error_kind: None,
+                invalid_avatar: false,
+                latest_post: None,
+                pinned: false,
             },
         ];
         bulk_insert_friend_table(friends.into_iter(), &db)
@@ -585,6 +1055,10 @@ mod tests {
                 error: false,
                 avatar: "https://example.com/avatar.jpg".to_string(),
                 created_at: "2023-01-01".to_string(),
+                error_kind: None,
+                invalid_avatar: false,
+                latest_post: None,
+                pinned: false,
             },
             Friends {
                 name: "用户2".to_string(),
@@ -592,6 +1066,10 @@ mod tests {
                 error: false,
                 avatar: "https://example.org/avatar.jpg".to_string(),
                 created_at: "2023-01-02".to_string(),
+                error_kind: None,
+                invalid_avatar: false,
+                latest_post: None,
+                pinned: false,
             },
         ];
         bulk_insert_friend_table(friends.into_iter(), &db)
@@ -615,10 +1093,12 @@ mod tests {
                     updated: "2023-01-01".to_string(),
                     link: "https://example.com/post1".to_string(),
                     rule: "test".to_string(),
+                    ..Default::default()
                 },
                 author: "作者1".to_string(),
                 avatar: "https://example.com/avatar1.jpg".to_string(),
                 created_at: "2023-01-01".to_string(),
+                first_seen: String::new(),
             },
             Posts {
                 meta: BasePosts {
@@ -627,10 +1107,12 @@ mod tests {
                     updated: "2023-01-02".to_string(),
                     link: "https://example.org/post2".to_string(),
                     rule: "test".to_string(),
+                    ..Default::default()
                 },
                 author: "作者2".to_string(),
                 avatar: "https://example.org/avatar2.jpg".to_string(),
                 created_at: "2023-01-02".to_string(),
+                first_seen: String::new(),
             },
         ];
         bulk_insert_post_table(posts.into_iter(), &db)
@@ -669,10 +1151,12 @@ mod tests {
                     updated: today.clone(), // 今天的帖子
                     link: "https://example.com/post1".to_string(),
                     rule: "test".to_string(),
+                    ..Default::default()
                 },
                 author: "作者1".to_string(),
                 avatar: "https://example.com/avatar1.jpg".to_string(),
                 created_at: today.clone(),
+                first_seen: String::new(),
             },
             Posts {
                 meta: BasePosts {
@@ -681,10 +1165,12 @@ mod tests {
                     updated: yesterday.clone(), // 昨天的帖子
                     link: "https://example.com/post2".to_string(),
                     rule: "test".to_string(),
+                    ..Default::default()
                 },
                 author: "作者2".to_string(),
                 avatar: "https://example.com/avatar2.jpg".to_string(),
                 created_at: yesterday.clone(),
+                first_seen: String::new(),
             },
             Posts {
                 meta: BasePosts {
@@ -693,10 +1179,12 @@ mod tests {
                     updated: old_date.clone(), // 35天前的帖子
                     link: "https://example.com/post3".to_string(),
                     rule: "test".to_string(),
+                    ..Default::default()
                 },
                 author: "作者3".to_string(),
                 avatar: "https://example.com/avatar3.jpg".to_string(),
                 created_at: old_date.clone(),
+                first_seen: String::new(),
             },
         ];
 
@@ -749,10 +1237,12 @@ mod tests {
                     updated: today.clone(),
                     link: "https://example.com/post1".to_string(),
                     rule: "test".to_string(),
+                    ..Default::default()
                 },
                 author: "作者1".to_string(),
                 avatar: "https://example.com/avatar1.jpg".to_string(),
                 created_at: today.clone(),
+                first_seen: String::new(),
             },
             Posts {
                 meta: BasePosts {
@@ -761,10 +1251,12 @@ mod tests {
                     updated: yesterday.clone(),
                     link: "https://example.com/post2".to_string(),
                     rule: "test".to_string(),
+                    ..Default::default()
                 },
                 author: "作者2".to_string(),
                 avatar: "https://example.com/avatar2.jpg".to_string(),
                 created_at: yesterday.clone(),
+                first_seen: String::new(),
             },
         ];
 
@@ -815,10 +1307,12 @@ mod tests {
                     updated: old_date1.clone(),
                     link: "https://example.com/post1".to_string(),
                     rule: "test".to_string(),
+                    ..Default::default()
                 },
                 author: "作者1".to_string(),
                 avatar: "https://example.com/avatar1.jpg".to_string(),
                 created_at: old_date1.clone(),
+                first_seen: String::new(),
             },
             Posts {
                 meta: BasePosts {
@@ -827,10 +1321,12 @@ mod tests {
                     updated: old_date2.clone(),
                     link: "https://example.com/post2".to_string(),
                     rule: "test".to_string(),
+                    ..Default::default()
                 },
                 author: "作者2".to_string(),
                 avatar: "https://example.com/avatar2.jpg".to_string(),
                 created_at: old_date2.clone(),
+                first_seen: String::new(),
             },
         ];
 
@@ -861,4 +1357,576 @@ mod tests {
         // 应该没有剩余帖子
         assert_eq!(remaining_posts.len(), 0);
     }
+
+    // 测试分页查询帖子及统计数量
+    #[tokio::test]
+    async fn test_select_posts_pagination_and_count() {
+        let db = setup_test_db().await;
+
+        // 创建测试数据 - 插入5篇文章
+        let mut posts = Vec::new();
+        for i in 1..=5 {
+            posts.push(post(
+                base_post(
+                    &format!("帖子{i}"),
+                    &format!("https://example.com/post{i}"),
+                    &format!("2023-01-0{i}"),
+                ),
+                &format!("作者{i}"),
+            ));
+        }
+        bulk_insert_post_table(posts.into_iter(), &db)
+            .await
+            .unwrap();
+
+        assert_eq!(select_count_from_posts(&db).await.unwrap(), 5);
+
+        // 第二页，每页2条
+        let page = select_all_from_posts(&db, 2, 4, "updated").await.unwrap();
+        assert_eq!(page.len(), 2);
+        assert_eq!(page[0].meta.title, "帖子3");
+
+        // end为0时返回start之后的全部数据
+        let rest = select_all_from_posts(&db, 3, 0, "updated").await.unwrap();
+        assert_eq!(rest.len(), 2);
+
+        // start>end时返回空
+        let empty = select_all_from_posts(&db, 4, 2, "updated").await.unwrap();
+        assert!(empty.is_empty());
+    }
+
+    // 测试统计好友数量
+    #[tokio::test]
+    async fn test_select_count_from_friends() {
+        let db = setup_test_db().await;
+
+        let friends = vec![
+            Friends {
+                invalid_avatar: true,
+                ..friend("用户1")
+            },
+            Friends {
+                error: true,
+                error_kind: Some(FailureKind::Nxdomain),
+                ..friend("用户2")
+            },
+        ];
+        bulk_insert_friend_table(friends.into_iter(), &db)
+            .await
+            .unwrap();
+
+        let (total, error_num) = select_count_from_friends(&db).await.unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(error_num, 1);
+
+        // 失败原因
+        let friends = select_all_from_friends(&db).await.unwrap();
+        let error_friend = friends.iter().find(|f| f.error).unwrap();
+        assert_eq!(error_friend.error_kind, Some(FailureKind::Nxdomain));
+        // 头像无效的友链不算获取失败
+        let ok_friend = friends.iter().find(|f| !f.error).unwrap();
+        assert!(ok_friend.invalid_avatar);
+        assert_eq!(ok_friend.error_kind, None);
+    }
+
+    // 测试按条件过滤帖子
+    #[tokio::test]
+    async fn test_select_posts_with_filter() {
+        let db = setup_test_db().await;
+
+        let mut posts = Vec::new();
+        for (i, title) in ["Rust入门", "Go入门", "rust进阶"].iter().enumerate() {
+            let i = i + 1;
+            let mut new_post = post(
+                base_post(
+                    title,
+                    &format!("https://example.com/post{i}"),
+                    &format!("2023-01-0{i}"),
+                ),
+                if i == 2 { "作者2" } else { "作者1" },
+            );
+            new_post.meta.updated = format!("2023-02-0{i}");
+            new_post.first_seen = format!("2023-03-0{i} 00:00:00");
+            posts.push(new_post);
+        }
+        bulk_insert_post_table(posts.into_iter(), &db)
+            .await
+            .unwrap();
+
+        let filter = PostFilter {
+            author: Some("作者1".to_string()),
+            ..Default::default()
+        };
+        let result = select_all_from_posts_with_filter(&db, &filter, 0, 0, "updated")
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(
+            select_count_from_posts_with_filter(&db, &filter)
+                .await
+                .unwrap(),
+            2
+        );
+
+        let filter = PostFilter {
+            created_since: Some("2023-01-02".to_string()),
+            created_until: Some("2023-01-02".to_string()),
+            ..Default::default()
+        };
+        let result = select_all_from_posts_with_filter(&db, &filter, 0, 0, "updated")
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].meta.title, "Go入门");
+
+        let filter = PostFilter {
+            keyword: Some("入门".to_string()),
+            since: Some("2023-03-01 00:00:00".to_string()),
+            ..Default::default()
+        };
+        let result = select_all_from_posts_with_filter(&db, &filter, 0, 0, "updated")
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].meta.title, "Go入门");
+    }
+
+    // 测试重新写入的已有文章不会被`since`当作新文章
+    #[tokio::test]
+    async fn test_select_posts_since_first_seen() {
+        let db = setup_test_db().await;
+
+        let old_post = post(
+            base_post("旧文章", "https://example.com/old", "2023-03-01"),
+            "作者1",
+        );
+        insert_post_table(&old_post, &db).await.unwrap();
+
+        // 再次抓取时删除后重新写入，createdAt更新，first_seen沿用上次的值
+        delete_post_table(std::iter::once(old_post.clone()), &db)
+            .await
+            .unwrap();
+        let reinserted = Posts {
+            created_at: "2023-03-05 00:00:00".to_string(),
+            ..old_post
+        };
+        let new_post = post(
+            base_post("新文章", "https://example.com/new", "2023-03-05"),
+            "作者1",
+        );
+        bulk_insert_post_table([reinserted, new_post].into_iter(), &db)
+            .await
+            .unwrap();
+
+        let filter = PostFilter {
+            since: Some("2023-03-02 00:00:00".to_string()),
+            ..Default::default()
+        };
+        let result = select_all_from_posts_with_filter(&db, &filter, 0, 0, "updated")
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].meta.title, "新文章");
+    }
+
+    // 测试全文检索帖子
+    #[tokio::test]
+    async fn test_search_posts() {
+        let db = setup_test_db().await;
+        create_posts_text_index(&db).await.unwrap();
+
+        let mut posts = Vec::new();
+        for (i, title) in ["Rust 异步编程", "Go 并发模型", "用 rust 写爬虫"]
+            .iter()
+            .enumerate()
+        {
+            posts.push(post(
+                base_post(title, &format!("https://example.com/post{i}"), "2023-01-01"),
+                "作者1",
+            ));
+        }
+        bulk_insert_post_table(posts.into_iter(), &db)
+            .await
+            .unwrap();
+
+        let result = search_posts(&db, "rust", 10).await.unwrap();
+        assert_eq!(result.len(), 2);
+        assert!(
+            result
+                .iter()
+                .all(|p| p.meta.title.to_lowercase().contains("rust"))
+        );
+
+        let result = search_posts(&db, "rust", 1).await.unwrap();
+        assert_eq!(result.len(), 1);
+    }
+
+    // 测试保存摘要、封面和标签
+    #[tokio::test]
+    async fn test_insert_post_with_summary_cover_tags() {
+        let db = setup_test_db().await;
+
+        let post = post(
+            BasePosts {
+                summary: Some("这是摘要".to_string()),
+                cover: Some("https://example.com/cover.png".to_string()),
+                tags: Tags::new(vec!["Rust".to_string(), "爬虫".to_string()]),
+                ..base_post("测试帖子", "https://example.com/post", "2023-01-01")
+            },
+            "测试作者",
+        );
+        insert_post_table(&post, &db).await.unwrap();
+
+        let posts = select_all_from_posts(&db, 0, 0, "updated").await.unwrap();
+        assert_eq!(posts.len(), 1);
+        assert_eq!(posts[0].meta, post.meta);
+        assert_eq!(posts[0].meta.tags.len(), 2);
+    }
+
+    // 测试按标签统计和查询帖子
+    #[tokio::test]
+    async fn test_select_posts_with_tags() {
+        let db = setup_test_db().await;
+
+        let mut posts = Vec::new();
+        for (i, tags) in [vec!["Rust", "爬虫"], vec!["rust"], vec!["Go_lang"]]
+            .into_iter()
+            .enumerate()
+        {
+            let mut new_post = post(
+                base_post(
+                    &format!("帖子{i}"),
+                    &format!("https://example.com/post{i}"),
+                    &format!("2023-01-0{}", i + 1),
+                ),
+                "作者1",
+            );
+            new_post.meta.tags = Tags::new(tags.into_iter().map(String::from));
+            posts.push(new_post);
+        }
+        bulk_insert_post_table(posts.into_iter(), &db)
+            .await
+            .unwrap();
+
+        let mut counts = select_tag_counts_from_posts(&db).await.unwrap();
+        counts.sort();
+        assert_eq!(
+            counts,
+            vec![
+                ("Go_lang".to_string(), 1),
+                ("Rust".to_string(), 1),
+                ("rust".to_string(), 1),
+                ("爬虫".to_string(), 1),
+            ]
+        );
+
+        let result = select_all_from_posts_with_tags(&db, &["RUST".to_string()], -1, "created")
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 2);
+
+        // `_`不作为通配符
+        let result = select_all_from_posts_with_tags(&db, &["Go_".to_string()], -1, "created")
+            .await
+            .unwrap();
+        assert!(result.is_empty());
+
+        let result = select_all_from_posts_with_tags(
+            &db,
+            &["爬虫".to_string(), "Go_lang".to_string()],
+            1,
+            "created",
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].meta.title, "帖子2");
+    }
+
+    // 测试统计不活跃友链
+    #[tokio::test]
+    async fn test_select_dormant_count_from_friends() {
+        let db = setup_test_db().await;
+
+        let friends = [
+            ("活跃", false, Some("2024-06-01")),
+            ("不活跃", false, Some("2020-01-01")),
+            ("失联", true, Some("2019-01-01")),
+            ("未知", false, None),
+        ]
+        .into_iter()
+        .map(|(name, error, latest_post)| Friends {
+            error,
+            latest_post: latest_post.map(String::from),
+            ..friend(name)
+        });
+        bulk_insert_friend_table(friends, &db).await.unwrap();
+
+        let dormant_num = select_dormant_count_from_friends(&db, "2023-01-01")
+            .await
+            .unwrap();
+        assert_eq!(dormant_num, 1);
+    }
+
+    // 测试按第一次获取时间查询新文章
+    #[tokio::test]
+    async fn test_select_new_posts() {
+        let db = setup_test_db().await;
+
+        let posts = [
+            ("旧文章", "2024-06-01 00:00:00"),
+            ("新文章1", "2024-06-02 00:00:00"),
+            ("新文章2", "2024-06-03 00:00:00"),
+        ]
+        .into_iter()
+        .map(|(title, first_seen)| Posts {
+            first_seen: first_seen.to_string(),
+            ..post(
+                base_post(
+                    title,
+                    &format!("https://example.com/{title}/"),
+                    "2024-06-01",
+                ),
+                "作者",
+            )
+        });
+        bulk_insert_post_table(posts, &db).await.unwrap();
+
+        let result = select_new_posts(&db, "2024-06-02 00:00:00", -1)
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].meta.title, "新文章2");
+        assert_eq!(result[1].first_seen, "2024-06-02 00:00:00");
+
+        let result = select_new_posts(&db, "2024-06-02 00:00:00", 1)
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 1);
+    }
+
+    // 测试随机查询文章
+    #[tokio::test]
+    async fn test_select_random_posts() {
+        let db = setup_test_db().await;
+
+        let posts = (0..5).map(|i| {
+            post(
+                base_post(
+                    &format!("帖子{i}"),
+                    &format!("https://example.com/{i}/"),
+                    "2024-06-01",
+                ),
+                "作者",
+            )
+        });
+        bulk_insert_post_table(posts, &db).await.unwrap();
+
+        assert_eq!(select_random_posts(&db, 3).await.unwrap().len(), 3);
+        assert_eq!(select_random_posts(&db, 10).await.unwrap().len(), 5);
+    }
+
+    // 测试手动添加友链的增删改查
+    #[tokio::test]
+    async fn test_manual_friends() {
+        let db = setup_test_db().await;
+        create_admin_indexes(&db).await.unwrap();
+
+        let mut friend = ManualFriend {
+            name: "手动友链".to_string(),
+            link: "https://manual.example.com/".to_string(),
+            avatar: String::new(),
+            suffix: String::new(),
+            created_at: "2024-06-01 00:00:00".to_string(),
+        };
+        insert_manual_friend(&friend, &db).await.unwrap();
+        // link重复
+        assert!(insert_manual_friend(&friend, &db).await.is_err());
+
+        friend.name = "改名".to_string();
+        friend.suffix = "atom.xml".to_string();
+        assert_eq!(update_manual_friend(&friend, &db).await.unwrap(), 1);
+        let friends = select_all_from_manual_friends(&db).await.unwrap();
+        assert_eq!(friends.len(), 1);
+        assert_eq!(friends[0].name, "改名");
+        assert_eq!(friends[0].suffix, "atom.xml");
+
+        assert_eq!(
+            delete_manual_friend("https://manual.example.com/", &db)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(update_manual_friend(&friend, &db).await.unwrap(), 0);
+        assert!(
+            select_all_from_manual_friends(&db)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    // 测试屏蔽站点的增删查
+    #[tokio::test]
+    async fn test_blocked_sites() {
+        let db = setup_test_db().await;
+        create_admin_indexes(&db).await.unwrap();
+
+        let site = BlockedSite {
+            pattern: "spam\\.example\\.com".to_string(),
+            created_at: "2024-06-01 00:00:00".to_string(),
+        };
+        insert_blocked_site(&site, &db).await.unwrap();
+        assert!(insert_blocked_site(&site, &db).await.is_err());
+        assert_eq!(
+            select_all_from_blocked_sites(&db).await.unwrap(),
+            vec![site]
+        );
+
+        assert_eq!(
+            delete_blocked_site("spam\\.example\\.com", &db)
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(select_all_from_blocked_sites(&db).await.unwrap().is_empty());
+    }
+
+    // 测试友链覆盖设置的添加、修改和删除，以及置顶友链的排序
+    #[tokio::test]
+    async fn test_friend_overrides() {
+        let db = setup_test_db().await;
+        create_admin_indexes(&db).await.unwrap();
+
+        let mut friend_override = FriendOverride {
+            link: "https://b.example.com/".to_string(),
+            name: "新名称".to_string(),
+            feed: String::new(),
+            theme: String::new(),
+            hidden: false,
+            pinned: true,
+            max_posts: 0,
+            created_at: "2024-06-01 00:00:00".to_string(),
+        };
+        upsert_friend_override(&friend_override, &db).await.unwrap();
+        friend_override.feed = "https://b.example.com/feed.xml".to_string();
+        friend_override.max_posts = 10;
+        friend_override.created_at = "2024-06-02 00:00:00".to_string();
+        upsert_friend_override(&friend_override, &db).await.unwrap();
+        let overrides = select_all_from_friend_overrides(&db).await.unwrap();
+        assert_eq!(overrides.len(), 1);
+        assert_eq!(overrides[0].feed, "https://b.example.com/feed.xml");
+        assert!(overrides[0].pinned);
+        assert_eq!(overrides[0].max_posts, 10);
+        // 修改时保留原来的添加时间
+        assert_eq!(overrides[0].created_at, "2024-06-01 00:00:00");
+
+        let mut friend_a = Friends::new(
+            "a".to_string(),
+            "https://a.example.com/".to_string(),
+            String::new(),
+            false,
+            "2024-06-01 00:00:00".to_string(),
+        );
+        let mut friend_b = friend_a.clone();
+        friend_b.name = "b".to_string();
+        friend_b.pinned = true;
+        friend_a.latest_post = Some("2024-06-01".to_string());
+        bulk_insert_friend_table(vec![friend_a, friend_b].into_iter(), &db)
+            .await
+            .unwrap();
+        let friends = select_all_from_friends(&db).await.unwrap();
+        assert_eq!(friends[0].name, "b");
+        assert!(friends[0].pinned);
+        assert!(!friends[1].pinned);
+
+        assert_eq!(
+            delete_friend_override("https://b.example.com/", &db)
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(
+            select_all_from_friend_overrides(&db)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    // 测试只保留最新的文章，包括之前运行写入的文章
+    #[tokio::test]
+    async fn test_delete_excess_posts() {
+        let db = setup_test_db().await;
+        let previous = [
+            ("https://a.com/1", "2024-01-01"),
+            ("https://a.com/3", "2024-01-03"),
+        ];
+        bulk_insert_post_table(
+            previous
+                .iter()
+                .map(|(link, created)| post(base_post(link, link, created), "作者")),
+            &db,
+        )
+        .await
+        .unwrap();
+        let current = [
+            ("https://a.com/2", "2024-01-02"),
+            ("https://a.com/4", "2024-01-04"),
+        ];
+        bulk_insert_post_table(
+            current
+                .iter()
+                .map(|(link, created)| post(base_post(link, link, created), "作者")),
+            &db,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            delete_excess_posts(0, PostSortBy::Created, &db)
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            delete_excess_posts(2, PostSortBy::Created, &db)
+                .await
+                .unwrap(),
+            2
+        );
+        let posts = select_all_from_posts(&db, 0, 0, "created").await.unwrap();
+        let links: Vec<&str> = posts.iter().map(|post| post.meta.link.as_str()).collect();
+        assert_eq!(links, ["https://a.com/4", "https://a.com/3"]);
+    }
+
+    // 测试按作者或友链链接删除文章，其他友链的文章保留
+    #[tokio::test]
+    async fn test_delete_friend_posts() {
+        let db = setup_test_db().await;
+        let posts = [
+            ("https://a.example.com/1/", "旧名称"),
+            ("https://a.example.com/2/", "新名称"),
+            ("https://a.example.org/3/", "旧名称"),
+            ("https://b.example.com/4/", "b"),
+        ];
+        bulk_insert_post_table(
+            posts
+                .iter()
+                .map(|(link, author)| post(base_post(link, link, "2024-01-01"), author)),
+            &db,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            delete_friend_posts("https://a.example.com/", "旧名称", &db)
+                .await
+                .unwrap(),
+            3
+        );
+        let posts = select_all_from_posts(&db, 0, 0, "created").await.unwrap();
+        let links: Vec<&str> = posts.iter().map(|post| post.meta.link.as_str()).collect();
+        assert_eq!(links, ["https://b.example.com/4/"]);
+    }
 }
@@ -1,6 +1,7 @@
-use data_structures::metadata;
+use data_structures::{config::PostSortBy, metadata, query_params::PostFilter};
 use sqlx::{
     Error, MySql, QueryBuilder, Row, mysql::MySqlPool, mysql::MySqlPoolOptions, query, query_as,
+    query_scalar,
 };
 
 pub async fn connect_mysql_dbpool(url: &str) -> Result<MySqlPool, Error> {
@@ -10,10 +11,15 @@ pub async fn connect_mysql_dbpool(url: &str) -> Result<MySqlPool, Error> {
         .await
 }
 
+/// 创建连接池但不立即连接，数据库暂时不可用时api也能启动，由`/readyz`反映连接状态
+pub fn connect_mysql_dbpool_lazy(url: &str) -> Result<MySqlPool, Error> {
+    MySqlPoolOptions::new().max_connections(5).connect_lazy(url)
+}
+
 pub async fn insert_post_table(post: &metadata::Posts, pool: &MySqlPool) -> Result<(), Error> {
     let sql = "INSERT INTO posts
-    (title, author, link, avatar ,rule,created,updated,createdAt)
-     VALUES (?, ?, ?,?, ?,?, ?, ?)";
+    (title, author, link, avatar ,rule,created,updated,createdAt,summary,cover,tags,first_seen)
+     VALUES (?, ?, ?,?, ?,?, ?, ?, ?, ?, ?, ?)";
     let q = query(sql)
         .bind(&post.meta.title)
         .bind(&post.author)
@@ -22,7 +28,11 @@ pub async fn insert_post_table(post: &metadata::Posts, pool: &MySqlPool) -> Resu
         .bind(&post.meta.rule)
         .bind(&post.meta.created)
         .bind(&post.meta.updated)
-        .bind(&post.created_at);
+        .bind(&post.created_at)
+        .bind(&post.meta.summary)
+        .bind(&post.meta.cover)
+        .bind(post.meta.tags.to_db_string())
+        .bind(&post.first_seen);
     // println!("sql: {},{:?}",q.sql(),q.take_arguments());
     q.execute(pool).await?;
     Ok(())
@@ -32,13 +42,17 @@ pub async fn insert_friend_table(
     friends: &metadata::Friends,
     pool: &MySqlPool,
 ) -> Result<(), Error> {
-    let sql = "INSERT INTO friends (name, link, avatar, error,createdAt) VALUES (?, ?, ?, ?, ?)";
+    let sql = "INSERT INTO friends (name, link, avatar, error, createdAt, error_kind, invalid_avatar, latest_post, pinned) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)";
     let q = query(sql)
         .bind(&friends.name)
         .bind(&friends.link)
         .bind(&friends.avatar)
         .bind(friends.error)
-        .bind(&friends.created_at);
+        .bind(&friends.created_at)
+        .bind(friends.error_kind)
+        .bind(friends.invalid_avatar)
+        .bind(&friends.latest_post)
+        .bind(friends.pinned);
     // println!("sql: {},{:?}",q.sql(),q.take_arguments());
     q.execute(pool).await?;
     Ok(())
@@ -52,7 +66,7 @@ pub async fn bulk_insert_post_table(
         // Note the trailing space; most calls to `QueryBuilder` don't automatically insert
         // spaces as that might interfere with identifiers or quoted strings where exact
         // values may matter.
-        "INSERT INTO posts (title, author, link, avatar ,rule,created,updated,createdAt) ",
+        "INSERT INTO posts (title, author, link, avatar ,rule,created,updated,createdAt,summary,cover,tags,first_seen) ",
     );
 
     query_builder.push_values(tuples, |mut b, post| {
@@ -66,7 +80,11 @@ pub async fn bulk_insert_post_table(
             .push_bind(post.meta.rule)
             .push_bind(post.meta.created)
             .push_bind(post.meta.updated)
-            .push_bind(post.created_at);
+            .push_bind(post.created_at)
+            .push_bind(post.meta.summary)
+            .push_bind(post.meta.cover)
+            .push_bind(post.meta.tags.to_db_string())
+            .push_bind(post.first_seen);
     });
     let query = query_builder.build();
     query.execute(pool).await?;
@@ -81,7 +99,7 @@ pub async fn bulk_insert_friend_table(
         // Note the trailing space; most calls to `QueryBuilder` don't automatically insert
         // spaces as that might interfere with identifiers or quoted strings where exact
         // values may matter.
-        "INSERT INTO friends (name, link, avatar, error,createdAt) ",
+        "INSERT INTO friends (name, link, avatar, error, createdAt, error_kind, invalid_avatar, latest_post, pinned) ",
     );
 
     query_builder.push_values(tuples, |mut b, friends| {
@@ -92,7 +110,11 @@ pub async fn bulk_insert_friend_table(
             .push_bind(friends.link)
             .push_bind(friends.avatar)
             .push_bind(friends.error)
-            .push_bind(friends.created_at);
+            .push_bind(friends.created_at)
+            .push_bind(friends.error_kind)
+            .push_bind(friends.invalid_avatar)
+            .push_bind(friends.latest_post)
+            .push_bind(friends.pinned);
     });
     let query = query_builder.build();
     query.execute(pool).await?;
@@ -114,6 +136,22 @@ pub async fn delete_post_table(
     Ok(())
 }
 
+/// 删除作者为`author`或链接以友链`link`开头的文章，返回删除的数量
+pub async fn delete_friend_posts(
+    link: &str,
+    author: &str,
+    pool: &MySqlPool,
+) -> Result<usize, Error> {
+    let sql = "DELETE FROM posts WHERE author = ? OR LEFT(link, CHAR_LENGTH(?)) = ?";
+    let affected_rows = query(sql)
+        .bind(author)
+        .bind(link)
+        .bind(link)
+        .execute(pool)
+        .await?;
+    Ok(affected_rows.rows_affected() as usize)
+}
+
 pub async fn truncate_friend_table(pool: &MySqlPool) -> Result<(), Error> {
     let sql = "TRUNCATE table friends";
     query(sql).execute(pool).await?;
@@ -126,29 +164,78 @@ pub async fn truncate_friend_table(pool: &MySqlPool) -> Result<(), Error> {
 ///
 /// 如果`start`和`end`同时为0，则查询全部；
 ///
-/// 否则只查询`start-end`条数据，如果`start>end`，会报错
+/// 如果`end`为0，则查询`start`之后的全部数据；
+///
+/// 否则只查询`start-end`条数据，如果`start>end`，返回空
 pub async fn select_all_from_posts(
     pool: &MySqlPool,
     start: usize,
     end: usize,
     sort_rule: &str,
 ) -> Result<Vec<metadata::Posts>, Error> {
-    let sql = if start == 0 && end == 0 {
-        format!("SELECT * FROM posts ORDER BY {sort_rule} DESC")
+    select_all_from_posts_with_filter(pool, &PostFilter::default(), start, end, sort_rule).await
+}
+
+/// 将过滤条件拼接为`WHERE`子句，没有条件时不拼接
+fn push_post_filter(query_builder: &mut QueryBuilder<MySql>, filter: &PostFilter) {
+    let conditions = [
+        ("author = ", filter.author.clone()),
+        (
+            "title LIKE ",
+            filter
+                .keyword
+                .as_ref()
+                .map(|k| format!("%{}%", crate::escape_like(k))),
+        ),
+        ("created >= ", filter.created_since.clone()),
+        ("created <= ", filter.created_until.clone()),
+        ("updated >= ", filter.updated_since.clone()),
+        ("updated <= ", filter.updated_until.clone()),
+        ("first_seen > ", filter.since.clone()),
+    ];
+    let mut first = true;
+    for (condition, value) in conditions {
+        let Some(value) = value else {
+            continue;
+        };
+        query_builder.push(if first { " WHERE " } else { " AND " });
+        query_builder.push(condition).push_bind(value);
+        if condition.ends_with("LIKE ") {
+            query_builder.push(" ESCAPE '\\\\'");
+        }
+        first = false;
+    }
+}
+
+/// 按照过滤条件查询`posts`表，分页规则与[`select_all_from_posts`]相同
+pub async fn select_all_from_posts_with_filter(
+    pool: &MySqlPool,
+    filter: &PostFilter,
+    start: usize,
+    end: usize,
+    sort_rule: &str,
+) -> Result<Vec<metadata::Posts>, Error> {
+    let mut query_builder: QueryBuilder<MySql> = QueryBuilder::new("SELECT * FROM posts");
+    push_post_filter(&mut query_builder, filter);
+    query_builder.push(format!(" ORDER BY {sort_rule} DESC"));
+    if end == 0 {
+        if start != 0 {
+            query_builder.push(format!(" LIMIT 18446744073709551615 OFFSET {start}"));
+        }
     } else {
-        format!(
-            "
-        SELECT * FROM posts
-        ORDER BY {sort_rule} DESC
-        LIMIT {limit} OFFSET {start}
-        ",
-            limit = end - start
-        )
-    };
-    // println!("{}",sql);
-    let posts = query_as::<_, metadata::Posts>(&sql).fetch_all(pool).await?;
+        query_builder.push(format!(
+            " LIMIT {limit} OFFSET {start}",
+            limit = end.saturating_sub(start)
+        ));
+    }
+    // println!("{}",query_builder.sql());
+    let posts = query_builder
+        .build_query_as::<metadata::Posts>()
+        .fetch_all(pool)
+        .await?;
     Ok(posts)
 }
+
 /// 查询`posts`表中`link`包含`domain_str`的数据
 ///
 /// 当num<0时，返回所有数据
@@ -170,6 +257,105 @@ pub async fn select_all_from_posts_with_linklike(
     Ok(posts)
 }
 
+/// 按标题和摘要全文检索`posts`表，结果按相关度排序，最多返回`num`条
+///
+/// 使用ngram分词的`FULLTEXT`索引，检索词少于`ngram_token_size`时无结果
+pub async fn search_posts(
+    pool: &MySqlPool,
+    keyword: &str,
+    num: usize,
+) -> Result<Vec<metadata::Posts>, Error> {
+    let sql = "SELECT * FROM posts
+    WHERE MATCH(title, summary) AGAINST(? IN NATURAL LANGUAGE MODE)
+    ORDER BY MATCH(title, summary) AGAINST(? IN NATURAL LANGUAGE MODE) DESC LIMIT ?";
+    let posts = query_as::<_, metadata::Posts>(sql)
+        .bind(keyword)
+        .bind(keyword)
+        .bind(num as i64)
+        .fetch_all(pool)
+        .await?;
+    Ok(posts)
+}
+
+/// 统计`posts`表中每个标签的文章数，标签区分大小写
+pub async fn select_tag_counts_from_posts(pool: &MySqlPool) -> Result<Vec<(String, usize)>, Error> {
+    // 保存的标签已去除空白和重复，按`,`拆分即可；默认排序规则忽略大小写，分组时使用utf8mb4_bin
+    let sql = "WITH RECURSIVE split (tag, rest) AS (
+        SELECT SUBSTRING_INDEX(tags, ',', 1),
+            SUBSTRING(tags, CHAR_LENGTH(SUBSTRING_INDEX(tags, ',', 1)) + 2)
+        FROM posts WHERE tags != ''
+        UNION ALL
+        SELECT SUBSTRING_INDEX(rest, ',', 1),
+            SUBSTRING(rest, CHAR_LENGTH(SUBSTRING_INDEX(rest, ',', 1)) + 2)
+        FROM split WHERE rest != ''
+    )
+    SELECT tag COLLATE utf8mb4_bin AS name, COUNT(*) AS count
+    FROM split WHERE tag != '' GROUP BY name";
+    let rows = query(sql).fetch_all(pool).await?;
+    Ok(rows
+        .iter()
+        .map(|row| (row.get("name"), row.get::<i64, _>("count") as usize))
+        .collect())
+}
+
+/// 查询`posts`表中含有`tags`任意一个标签的数据，标签忽略大小写
+///
+/// 当num<0时，返回所有数据；`sort_rule`只能为`created`或`updated`，其他值按`created`排序
+pub async fn select_all_from_posts_with_tags(
+    pool: &MySqlPool,
+    tags: &[String],
+    num: i32,
+    sort_rule: &str,
+) -> Result<Vec<metadata::Posts>, Error> {
+    if tags.is_empty() {
+        return Ok(Vec::new());
+    }
+    let sort_rule = match sort_rule {
+        "updated" => "updated",
+        _ => "created",
+    };
+    let mut query_builder: QueryBuilder<MySql> = QueryBuilder::new("SELECT * FROM posts WHERE ");
+    for (i, tag) in tags.iter().enumerate() {
+        if i > 0 {
+            query_builder.push(" OR ");
+        }
+        query_builder
+            .push("CONCAT(',', tags, ',') LIKE ")
+            .push_bind(format!("%,{},%", crate::escape_like(tag)))
+            .push(" ESCAPE '\\\\'");
+    }
+    query_builder.push(format!(" ORDER BY {sort_rule} DESC"));
+    if num >= 0 {
+        query_builder.push(format!(" LIMIT {num}"));
+    }
+    let posts = query_builder
+        .build_query_as::<metadata::Posts>()
+        .fetch_all(pool)
+        .await?;
+    Ok(posts)
+}
+
+/// 查询`posts`表中`first_seen`不早于`since`的数据，按`first_seen`倒序
+///
+/// 当num<0时，返回所有数据
+pub async fn select_new_posts(
+    pool: &MySqlPool,
+    since: &str,
+    num: i32,
+) -> Result<Vec<metadata::Posts>, Error> {
+    let mut sql = String::from(
+        "SELECT * FROM posts WHERE first_seen >= ? ORDER BY first_seen DESC, updated DESC",
+    );
+    if num >= 0 {
+        sql.push_str(&format!(" LIMIT {num}"));
+    }
+    let posts = query_as::<_, metadata::Posts>(&sql)
+        .bind(since)
+        .fetch_all(pool)
+        .await?;
+    Ok(posts)
+}
+
 /// 查询`friends`表中`link`包含`domain_str`的一条数据
 pub async fn select_one_from_friends_with_linklike(
     pool: &MySqlPool,
@@ -186,21 +372,217 @@ pub async fn select_one_from_friends_with_linklike(
 
 /// 获取`posts`表中最近一次更新（`createdAt`最新）的时间
 pub async fn select_latest_time_from_posts(pool: &MySqlPool) -> Result<String, Error> {
-    let sql = "SELECT createdAt from posts ORDER BY createdAt DESC";
+    let sql = "SELECT createdAt from posts ORDER BY createdAt DESC LIMIT 1";
     let result = query(sql).fetch_one(pool).await?;
     let created_at: String = result.get("createdAt");
     Ok(created_at)
 }
 
-/// 查询`friends`表的所有数据
+/// 检查数据库是否可以连接
+pub async fn ping(pool: &MySqlPool) -> Result<(), Error> {
+    query("SELECT 1").execute(pool).await?;
+    Ok(())
+}
+
+/// 返回`db/schema/mysql`中尚未成功执行的迁移版本
+pub async fn select_pending_migrations(pool: &MySqlPool) -> Result<Vec<i64>, Error> {
+    let sql = "SELECT version FROM _sqlx_migrations WHERE success = 1";
+    let applied: Vec<i64> = query_scalar(sql).fetch_all(pool).await?;
+    let pending = sqlx::migrate!("../db/schema/mysql")
+        .iter()
+        .map(|migration| migration.version)
+        .filter(|version| !applied.contains(version))
+        .collect();
+    Ok(pending)
+}
+
+/// 统计`posts`表的数据总数
+pub async fn select_count_from_posts(pool: &MySqlPool) -> Result<usize, Error> {
+    select_count_from_posts_with_filter(pool, &PostFilter::default()).await
+}
+
+/// 统计`posts`表中符合过滤条件的数据总数
+pub async fn select_count_from_posts_with_filter(
+    pool: &MySqlPool,
+    filter: &PostFilter,
+) -> Result<usize, Error> {
+    let mut query_builder: QueryBuilder<MySql> =
+        QueryBuilder::new("SELECT COUNT(*) AS num FROM posts");
+    push_post_filter(&mut query_builder, filter);
+    let result = query_builder.build().fetch_one(pool).await?;
+    let num: i64 = result.get("num");
+    Ok(num as usize)
+}
+
+/// 统计`friends`表的数据，返回`(总数, 失联数)`
+pub async fn select_count_from_friends(pool: &MySqlPool) -> Result<(usize, usize), Error> {
+    let sql =
+        "SELECT COUNT(*) AS total, COUNT(CASE WHEN error THEN 1 END) AS error_num FROM friends";
+    let result = query(sql).fetch_one(pool).await?;
+    let total: i64 = result.get("total");
+    let error_num: i64 = result.get("error_num");
+    Ok((total as usize, error_num as usize))
+}
+
+/// 统计可以访问但最新文章早于`before`的友链数
+pub async fn select_dormant_count_from_friends(
+    pool: &MySqlPool,
+    before: &str,
+) -> Result<usize, Error> {
+    let sql = "SELECT COUNT(*) AS dormant_num FROM friends WHERE NOT error AND latest_post < ?";
+    let result = query(sql).bind(before).fetch_one(pool).await?;
+    let dormant_num: i64 = result.get("dormant_num");
+    Ok(dormant_num as usize)
+}
+
+/// 随机查询`posts`表中的`num`条数据
+pub async fn select_random_posts(
+    pool: &MySqlPool,
+    num: usize,
+) -> Result<Vec<metadata::Posts>, Error> {
+    let sql = "SELECT * FROM posts ORDER BY RAND() LIMIT ?";
+    let posts = query_as::<_, metadata::Posts>(sql)
+        .bind(num as i64)
+        .fetch_all(pool)
+        .await?;
+    Ok(posts)
+}
+
+/// 查询`friends`表的所有数据，置顶的友链在前
 pub async fn select_all_from_friends(pool: &MySqlPool) -> Result<Vec<metadata::Friends>, Error> {
-    let sql = String::from("SELECT * FROM friends");
+    let sql = String::from("SELECT * FROM friends ORDER BY pinned DESC, id");
     let friends = query_as::<_, metadata::Friends>(&sql)
         .fetch_all(pool)
         .await?;
     Ok(friends)
 }
 
+/// 插入一条手动添加的友链，`link`已存在时返回错误
+pub async fn insert_manual_friend(
+    friend: &metadata::ManualFriend,
+    pool: &MySqlPool,
+) -> Result<(), Error> {
+    let sql =
+        "INSERT INTO manual_friends (name, link, avatar, suffix, createdAt) VALUES (?, ?, ?, ?, ?)";
+    query(sql)
+        .bind(&friend.name)
+        .bind(&friend.link)
+        .bind(&friend.avatar)
+        .bind(&friend.suffix)
+        .bind(&friend.created_at)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// 按`link`修改手动添加的友链，返回修改的行数
+pub async fn update_manual_friend(
+    friend: &metadata::ManualFriend,
+    pool: &MySqlPool,
+) -> Result<usize, Error> {
+    let sql = "UPDATE manual_friends SET name = ?, avatar = ?, suffix = ? WHERE link = ?";
+    let result = query(sql)
+        .bind(&friend.name)
+        .bind(&friend.avatar)
+        .bind(&friend.suffix)
+        .bind(&friend.link)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() as usize)
+}
+
+/// 按`link`删除手动添加的友链，返回删除的行数
+pub async fn delete_manual_friend(link: &str, pool: &MySqlPool) -> Result<usize, Error> {
+    let sql = "DELETE FROM manual_friends WHERE link = ?";
+    let result = query(sql).bind(link).execute(pool).await?;
+    Ok(result.rows_affected() as usize)
+}
+
+/// 查询`manual_friends`表的所有数据
+pub async fn select_all_from_manual_friends(
+    pool: &MySqlPool,
+) -> Result<Vec<metadata::ManualFriend>, Error> {
+    let sql = "SELECT name, link, avatar, suffix, createdAt FROM manual_friends ORDER BY id";
+    let friends = query_as::<_, metadata::ManualFriend>(sql)
+        .fetch_all(pool)
+        .await?;
+    Ok(friends)
+}
+
+/// 添加或修改友链的覆盖设置，`link`已存在时只修改，保留原来的`createdAt`
+pub async fn upsert_friend_override(
+    friend_override: &metadata::FriendOverride,
+    pool: &MySqlPool,
+) -> Result<(), Error> {
+    let sql = "INSERT INTO friend_overrides (link, name, feed, theme, hidden, pinned, max_posts, createdAt)
+    VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+    ON DUPLICATE KEY UPDATE name = VALUES(name), feed = VALUES(feed),
+    theme = VALUES(theme), hidden = VALUES(hidden), pinned = VALUES(pinned),
+    max_posts = VALUES(max_posts)";
+    query(sql)
+        .bind(&friend_override.link)
+        .bind(&friend_override.name)
+        .bind(&friend_override.feed)
+        .bind(&friend_override.theme)
+        .bind(friend_override.hidden)
+        .bind(friend_override.pinned)
+        .bind(friend_override.max_posts)
+        .bind(&friend_override.created_at)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// 按`link`删除友链的覆盖设置，返回删除的行数
+pub async fn delete_friend_override(link: &str, pool: &MySqlPool) -> Result<usize, Error> {
+    let sql = "DELETE FROM friend_overrides WHERE link = ?";
+    let result = query(sql).bind(link).execute(pool).await?;
+    Ok(result.rows_affected() as usize)
+}
+
+/// 查询`friend_overrides`表的所有数据
+pub async fn select_all_from_friend_overrides(
+    pool: &MySqlPool,
+) -> Result<Vec<metadata::FriendOverride>, Error> {
+    let sql = "SELECT link, name, feed, theme, hidden, pinned, max_posts, createdAt FROM friend_overrides ORDER BY id";
+    let overrides = query_as::<_, metadata::FriendOverride>(sql)
+        .fetch_all(pool)
+        .await?;
+    Ok(overrides)
+}
+
+/// 插入一条屏蔽站点，`pattern`已存在时返回错误
+pub async fn insert_blocked_site(
+    site: &metadata::BlockedSite,
+    pool: &MySqlPool,
+) -> Result<(), Error> {
+    let sql = "INSERT INTO blocked_sites (pattern, createdAt) VALUES (?, ?)";
+    query(sql)
+        .bind(&site.pattern)
+        .bind(&site.created_at)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// 删除屏蔽站点，返回删除的行数
+pub async fn delete_blocked_site(pattern: &str, pool: &MySqlPool) -> Result<usize, Error> {
+    let sql = "DELETE FROM blocked_sites WHERE pattern = ?";
+    let result = query(sql).bind(pattern).execute(pool).await?;
+    Ok(result.rows_affected() as usize)
+}
+
+/// 查询`blocked_sites`表的所有数据
+pub async fn select_all_from_blocked_sites(
+    pool: &MySqlPool,
+) -> Result<Vec<metadata::BlockedSite>, Error> {
+    let sql = "SELECT pattern, createdAt FROM blocked_sites ORDER BY id";
+    let sites = query_as::<_, metadata::BlockedSite>(sql)
+        .fetch_all(pool)
+        .await?;
+    Ok(sites)
+}
+
 pub async fn delete_outdated_posts(days: usize, dbpool: &MySqlPool) -> Result<usize, Error> {
     let sql = "DELETE FROM posts WHERE DATE(updated) < DATE_SUB(CURDATE(), INTERVAL ? DAY)";
     let affected_rows = query(sql).bind(days as i64).execute(dbpool).await?;
@@ -208,10 +590,35 @@ pub async fn delete_outdated_posts(days: usize, dbpool: &MySqlPool) -> Result<us
     Ok(affected_rows.rows_affected() as usize)
 }
 
+/// 只保留按`sort_by`从新到旧排序的前`max_total`篇文章，返回删除的数量，0表示不限制
+///
+/// 时间相同时保留先写入的
+pub async fn delete_excess_posts(
+    max_total: usize,
+    sort_by: PostSortBy,
+    dbpool: &MySqlPool,
+) -> Result<usize, Error> {
+    if max_total == 0 {
+        return Ok(0);
+    }
+    // MySQL不支持在IN子查询中使用LIMIT，也不能直接查询正在删除的表，需要再嵌套一层
+    let sql = format!(
+        "DELETE FROM posts WHERE id NOT IN \
+         (SELECT id FROM (SELECT id FROM posts ORDER BY {} DESC, id LIMIT ?) AS kept)",
+        crate::post_sort_column(sort_by)
+    );
+    let affected_rows = query(&sql).bind(max_total as i64).execute(dbpool).await?;
+
+    Ok(affected_rows.rows_affected() as usize)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use data_structures::metadata::{BasePosts, Friends, Posts};
+    use data_structures::metadata::{
+        BasePosts, BlockedSite, FailureKind, FriendOverride, Friends, ManualFriend, Posts, Tags,
+    };
+    use data_structures::testing::{base_post, friend, post};
     use std::time::SystemTime;
 
     // 辅助函数：获取测试数据库连接并设置测试环境
@@ -233,6 +640,9 @@ mod tests {
         // 清空表以确保测试环境干净
         truncate_table(&dbpool, "friends").await.unwrap();
         truncate_table(&dbpool, "posts").await.unwrap();
+        truncate_table(&dbpool, "manual_friends").await.unwrap();
+        truncate_table(&dbpool, "blocked_sites").await.unwrap();
+        truncate_table(&dbpool, "friend_overrides").await.unwrap();
 
         dbpool
     }
@@ -270,6 +680,10 @@ mod tests {
             error: false,
             avatar: "https://example.com/avatar.jpg".to_string(),
             created_at: SystemTime::now().elapsed().unwrap().as_secs().to_string(),
+            error_kind: None,
+            invalid_avatar: false,
+            latest_post: None,
+            pinned: false,
         };
 
         // 插入数据
@@ -297,6 +711,7 @@ mod tests {
             updated: "2023-01-01".to_string(),
             link: "https://example.com/post".to_string(),
             rule: "test".to_string(),
+            ..Default::default()
         };
 
         let post = Posts {
@@ -304,6 +719,7 @@ mod tests {
             author: "测试作者".to_string(),
             avatar: "https://example.com/avatar.jpg".to_string(),
             created_at: SystemTime::now().elapsed().unwrap().as_secs().to_string(),
+            first_seen: String::new(),
         };
 
         // 插入数据
@@ -332,6 +748,10 @@ mod tests {
                 error: false,
                 avatar: "https://example1.com/avatar.jpg".to_string(),
                 created_at: SystemTime::now().elapsed().unwrap().as_secs().to_string(),
+                error_kind: None,
+                invalid_avatar: false,
+                latest_post: None,
+                pinned: false,
             },
             Friends {
                 name: "用户2".to_string(),
@@ -339,6 +759,10 @@ mod tests {
                 error: false,
                 avatar: "https://example2.com/avatar.jpg".to_string(),
                 created_at: SystemTime::now().elapsed().unwrap().as_secs().to_string(),
+                error_kind: None,
+                invalid_avatar: false,
+                latest_post: None,
+                pinned: false,
             },
         ];
 
@@ -370,10 +794,12 @@ mod tests {
                     updated: "2023-01-01".to_string(),
                     link: "https://example.com/post1".to_string(),
                     rule: "test".to_string(),
+                    ..Default::default()
                 },
                 author: "作者1".to_string(),
                 avatar: "https://example.com/avatar1.jpg".to_string(),
                 created_at: "2023-01-01".to_string(),
+                first_seen: String::new(),
             },
             Posts {
                 meta: BasePosts {
@@ -382,10 +808,12 @@ mod tests {
                     updated: "2023-01-02".to_string(),
                     link: "https://example.com/post2".to_string(),
                     rule: "test".to_string(),
+                    ..Default::default()
                 },
                 author: "作者2".to_string(),
                 avatar: "https://example.com/avatar2.jpg".to_string(),
                 created_at: "2023-01-02".to_string(),
+                first_seen: String::new(),
             },
         ];
 
@@ -417,10 +845,12 @@ mod tests {
                     updated: "2023-01-01".to_string(),
                     link: "https://example.com/post1".to_string(),
                     rule: "test".to_string(),
+                    ..Default::default()
                 },
                 author: "作者1".to_string(),
                 avatar: "https://example.com/avatar1.jpg".to_string(),
                 created_at: "2023-01-01".to_string(),
+                first_seen: String::new(),
             },
             Posts {
                 meta: BasePosts {
@@ -429,10 +859,12 @@ mod tests {
                     updated: "2023-01-02".to_string(),
                     link: "https://example.org/post2".to_string(),
                     rule: "test".to_string(),
+                    ..Default::default()
                 },
                 author: "作者2".to_string(),
                 avatar: "https://example.org/avatar2.jpg".to_string(),
                 created_at: "2023-01-02".to_string(),
+                first_seen: String::new(),
             },
         ];
 
@@ -469,6 +901,10 @@ mod tests {
                 error: false,
                 avatar: "https://example.com/avatar.jpg".to_string(),
                 created_at: "2023-01-01".to_string(),
+                error_kind: None,
+                invalid_avatar: false,
+                latest_post: None,
+                pinned: false,
             },
             Friends {
                 name: "用户2".to_string(),
@@ -476,6 +912,10 @@ mod tests {
                 error: false,
                 avatar: "https://example.org/avatar.jpg".to_string(),
                 created_at: "2023-01-02".to_string(),
+                error_kind: None,
+                invalid_avatar: false,
+                latest_post: None,
+                pinned: false,
             },
         ];
 
@@ -506,10 +946,12 @@ mod tests {
                     updated: "2023-01-01".to_string(),
                     link: "https://example.com/post1".to_string(),
                     rule: "test".to_string(),
+                    ..Default::default()
                 },
                 author: "作者1".to_string(),
                 avatar: "https://example.com/avatar1.jpg".to_string(),
                 created_at: "2023-01-01".to_string(),
+                first_seen: String::new(),
             },
             Posts {
                 meta: BasePosts {
@@ -518,10 +960,12 @@ mod tests {
                     updated: "2023-01-02".to_string(),
                     link: "https://example.com/post2".to_string(),
                     rule: "test".to_string(),
+                    ..Default::default()
                 },
                 author: "作者2".to_string(),
                 avatar: "https://example.com/avatar2.jpg".to_string(),
                 created_at: "2023-01-02".to_string(),
+                first_seen: String::new(),
             },
         ];
 
@@ -548,6 +992,10 @@ mod tests {
             error: false,
             avatar: "https://example.com/avatar.jpg".to_string(),
             created_at: "2023-01-01".to_string(),
+            error_kind: None,
+            invalid_avatar: false,
+            latest_post: None,
+            pinned: false,
         };
 
         insert_friend_table(&friend, &pool).await.unwrap();
@@ -576,6 +1024,10 @@ mod tests {
             error: false,
             avatar: "https://example.com/avatar.jpg".to_string(),
             created_at: "2023-01-01".to_string(),
+            error_kind: None,
+            invalid_avatar: false,
+            latest_post: None,
+            pinned: false,
         };
 
         insert_friend_table(&friend, &pool).await.unwrap();
@@ -767,4 +1219,605 @@ mod tests {
         let posts = select_all_from_posts(&pool, 0, 0, "updated").await.unwrap();
         assert_eq!(posts.len(), 0);
     }
+
+    // 测试分页查询帖子及统计数量
+    #[tokio::test]
+    async fn test_select_posts_pagination_and_count() {
+        let pool = setup_test_db().await;
+
+        // 创建测试数据 - 插入5篇文章
+        let mut posts = Vec::new();
+        for i in 1..=5 {
+            posts.push(post(
+                base_post(
+                    &format!("帖子{i}"),
+                    &format!("https://example.com/post{i}"),
+                    &format!("2023-01-0{i}"),
+                ),
+                &format!("作者{i}"),
+            ));
+        }
+        bulk_insert_post_table(posts.into_iter(), &pool)
+            .await
+            .unwrap();
+
+        assert_eq!(select_count_from_posts(&pool).await.unwrap(), 5);
+
+        // 第二页，每页2条
+        let page = select_all_from_posts(&pool, 2, 4, "updated").await.unwrap();
+        assert_eq!(page.len(), 2);
+        assert_eq!(page[0].meta.title, "帖子3");
+
+        // end为0时返回start之后的全部数据
+        let rest = select_all_from_posts(&pool, 3, 0, "updated").await.unwrap();
+        assert_eq!(rest.len(), 2);
+
+        // start>end时返回空
+        let empty = select_all_from_posts(&pool, 4, 2, "updated").await.unwrap();
+        assert!(empty.is_empty());
+    }
+
+    // 测试统计好友数量
+    #[tokio::test]
+    async fn test_select_count_from_friends() {
+        let pool = setup_test_db().await;
+
+        let friends = vec![
+            Friends {
+                invalid_avatar: true,
+                ..friend("用户1")
+            },
+            Friends {
+                error: true,
+                error_kind: Some(FailureKind::Nxdomain),
+                ..friend("用户2")
+            },
+        ];
+        bulk_insert_friend_table(friends.into_iter(), &pool)
+            .await
+            .unwrap();
+
+        let (total, error_num) = select_count_from_friends(&pool).await.unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(error_num, 1);
+
+        // 失败原因
+        let friends = select_all_from_friends(&pool).await.unwrap();
+        let error_friend = friends.iter().find(|f| f.error).unwrap();
+        assert_eq!(error_friend.error_kind, Some(FailureKind::Nxdomain));
+        // 头像无效的友链不算获取失败
+        let ok_friend = friends.iter().find(|f| !f.error).unwrap();
+        assert!(ok_friend.invalid_avatar);
+        assert_eq!(ok_friend.error_kind, None);
+    }
+
+    // 测试按条件过滤帖子
+    #[tokio::test]
+    async fn test_select_posts_with_filter() {
+        let pool = setup_test_db().await;
+
+        let mut posts = Vec::new();
+        for (i, title) in ["Rust入门", "Go入门", "rust进阶"].iter().enumerate() {
+            let i = i + 1;
+            let mut new_post = post(
+                base_post(
+                    title,
+                    &format!("https://example.com/post{i}"),
+                    &format!("2023-01-0{i}"),
+                ),
+                if i == 2 { "作者2" } else { "作者1" },
+            );
+            new_post.meta.updated = format!("2023-02-0{i}");
+            new_post.first_seen = format!("2023-03-0{i} 00:00:00");
+            posts.push(new_post);
+        }
+        bulk_insert_post_table(posts.into_iter(), &pool)
+            .await
+            .unwrap();
+
+        let filter = PostFilter {
+            author: Some("作者1".to_string()),
+            ..Default::default()
+        };
+        let result = select_all_from_posts_with_filter(&pool, &filter, 0, 0, "updated")
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(
+            select_count_from_posts_with_filter(&pool, &filter)
+                .await
+                .unwrap(),
+            2
+        );
+
+        let filter = PostFilter {
+            created_since: Some("2023-01-02".to_string()),
+            created_until: Some("2023-01-02".to_string()),
+            ..Default::default()
+        };
+        let result = select_all_from_posts_with_filter(&pool, &filter, 0, 0, "updated")
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].meta.title, "Go入门");
+
+        let filter = PostFilter {
+            keyword: Some("入门".to_string()),
+            since: Some("2023-03-01 00:00:00".to_string()),
+            ..Default::default()
+        };
+        let result = select_all_from_posts_with_filter(&pool, &filter, 0, 0, "updated")
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].meta.title, "Go入门");
+
+        // 通配符按字面匹配
+        for keyword in ["%", "_"] {
+            let filter = PostFilter {
+                keyword: Some(keyword.to_string()),
+                ..Default::default()
+            };
+            assert_eq!(
+                select_count_from_posts_with_filter(&pool, &filter)
+                    .await
+                    .unwrap(),
+                0
+            );
+        }
+    }
+
+    // 测试重新写入的已有文章不会被`since`当作新文章
+    #[tokio::test]
+    async fn test_select_posts_since_first_seen() {
+        let pool = setup_test_db().await;
+
+        let old_post = post(
+            base_post("旧文章", "https://example.com/old", "2023-03-01"),
+            "作者1",
+        );
+        insert_post_table(&old_post, &pool).await.unwrap();
+
+        // 再次抓取时删除后重新写入，createdAt更新，first_seen沿用上次的值
+        delete_post_table(std::iter::once(old_post.clone()), &pool)
+            .await
+            .unwrap();
+        let reinserted = Posts {
+            created_at: "2023-03-05 00:00:00".to_string(),
+            ..old_post
+        };
+        let new_post = post(
+            base_post("新文章", "https://example.com/new", "2023-03-05"),
+            "作者1",
+        );
+        bulk_insert_post_table([reinserted, new_post].into_iter(), &pool)
+            .await
+            .unwrap();
+
+        let filter = PostFilter {
+            since: Some("2023-03-02 00:00:00".to_string()),
+            ..Default::default()
+        };
+        let result = select_all_from_posts_with_filter(&pool, &filter, 0, 0, "updated")
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].meta.title, "新文章");
+    }
+
+    // 测试全文检索帖子
+    #[tokio::test]
+    async fn test_search_posts() {
+        let pool = setup_test_db().await;
+
+        let mut posts = Vec::new();
+        for (i, title) in ["Rust 异步编程", "Go 并发模型", "用 rust 写爬虫"]
+            .iter()
+            .enumerate()
+        {
+            posts.push(post(
+                base_post(title, &format!("https://example.com/post{i}"), "2023-01-01"),
+                "作者1",
+            ));
+        }
+        bulk_insert_post_table(posts.into_iter(), &pool)
+            .await
+            .unwrap();
+
+        let result = search_posts(&pool, "Rust", 10).await.unwrap();
+        assert_eq!(result.len(), 2);
+        assert!(
+            result
+                .iter()
+                .all(|p| p.meta.title.to_lowercase().contains("rust"))
+        );
+
+        let result = search_posts(&pool, "Rust", 1).await.unwrap();
+        assert_eq!(result.len(), 1);
+    }
+
+    // 测试保存摘要、封面和标签
+    #[tokio::test]
+    async fn test_insert_post_with_summary_cover_tags() {
+        let pool = setup_test_db().await;
+
+        let post = post(
+            BasePosts {
+                summary: Some("这是摘要".to_string()),
+                cover: Some("https://example.com/cover.png".to_string()),
+                tags: Tags::new(vec!["Rust".to_string(), "爬虫".to_string()]),
+                ..base_post("测试帖子", "https://example.com/post", "2023-01-01")
+            },
+            "测试作者",
+        );
+        insert_post_table(&post, &pool).await.unwrap();
+
+        let posts = select_all_from_posts(&pool, 0, 0, "updated").await.unwrap();
+        assert_eq!(posts.len(), 1);
+        assert_eq!(posts[0].meta, post.meta);
+        assert_eq!(posts[0].meta.tags.len(), 2);
+    }
+
+    // 测试按标签统计和查询帖子
+    #[tokio::test]
+    async fn test_select_posts_with_tags() {
+        let pool = setup_test_db().await;
+
+        let mut posts = Vec::new();
+        for (i, tags) in [vec!["Rust", "爬虫"], vec!["rust"], vec!["Go_lang"]]
+            .into_iter()
+            .enumerate()
+        {
+            let mut new_post = post(
+                base_post(
+                    &format!("帖子{i}"),
+                    &format!("https://example.com/post{i}"),
+                    &format!("2023-01-0{}", i + 1),
+                ),
+                "作者1",
+            );
+            new_post.meta.tags = Tags::new(tags.into_iter().map(String::from));
+            posts.push(new_post);
+        }
+        bulk_insert_post_table(posts.into_iter(), &pool)
+            .await
+            .unwrap();
+
+        let mut counts = select_tag_counts_from_posts(&pool).await.unwrap();
+        counts.sort();
+        assert_eq!(
+            counts,
+            vec![
+                ("Go_lang".to_string(), 1),
+                ("Rust".to_string(), 1),
+                ("rust".to_string(), 1),
+                ("爬虫".to_string(), 1),
+            ]
+        );
+
+        let result = select_all_from_posts_with_tags(&pool, &["RUST".to_string()], -1, "created")
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 2);
+
+        // `_`不作为通配符
+        let result = select_all_from_posts_with_tags(&pool, &["Go_".to_string()], -1, "created")
+            .await
+            .unwrap();
+        assert!(result.is_empty());
+
+        let result = select_all_from_posts_with_tags(
+            &pool,
+            &["爬虫".to_string(), "Go_lang".to_string()],
+            1,
+            "created",
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].meta.title, "帖子2");
+    }
+
+    // 测试统计不活跃友链
+    #[tokio::test]
+    async fn test_select_dormant_count_from_friends() {
+        let pool = setup_test_db().await;
+
+        let friends = [
+            ("活跃", false, Some("2024-06-01")),
+            ("不活跃", false, Some("2020-01-01")),
+            ("失联", true, Some("2019-01-01")),
+            ("未知", false, None),
+        ]
+        .into_iter()
+        .map(|(name, error, latest_post)| Friends {
+            error,
+            latest_post: latest_post.map(String::from),
+            ..friend(name)
+        });
+        bulk_insert_friend_table(friends, &pool).await.unwrap();
+
+        let dormant_num = select_dormant_count_from_friends(&pool, "2023-01-01")
+            .await
+            .unwrap();
+        assert_eq!(dormant_num, 1);
+    }
+
+    // 测试按第一次获取时间查询新文章
+    #[tokio::test]
+    async fn test_select_new_posts() {
+        let pool = setup_test_db().await;
+
+        let posts = [
+            ("旧文章", "2024-06-01 00:00:00"),
+            ("新文章1", "2024-06-02 00:00:00"),
+            ("新文章2", "2024-06-03 00:00:00"),
+        ]
+        .into_iter()
+        .map(|(title, first_seen)| Posts {
+            first_seen: first_seen.to_string(),
+            ..post(
+                base_post(
+                    title,
+                    &format!("https://example.com/{title}/"),
+                    "2024-06-01",
+                ),
+                "作者",
+            )
+        });
+        bulk_insert_post_table(posts, &pool).await.unwrap();
+
+        let result = select_new_posts(&pool, "2024-06-02 00:00:00", -1)
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].meta.title, "新文章2");
+        assert_eq!(result[1].first_seen, "2024-06-02 00:00:00");
+
+        let result = select_new_posts(&pool, "2024-06-02 00:00:00", 1)
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 1);
+    }
+
+    // 测试就绪检查用到的连接和迁移检查
+    #[tokio::test]
+    async fn test_select_pending_migrations() {
+        let pool = setup_test_db().await;
+
+        ping(&pool).await.unwrap();
+        let pending = select_pending_migrations(&pool).await.unwrap();
+        assert!(pending.is_empty());
+    }
+
+    // 测试随机查询文章
+    #[tokio::test]
+    async fn test_select_random_posts() {
+        let pool = setup_test_db().await;
+
+        let posts = (0..5).map(|i| {
+            post(
+                base_post(
+                    &format!("帖子{i}"),
+                    &format!("https://example.com/{i}/"),
+                    "2024-06-01",
+                ),
+                "作者",
+            )
+        });
+        bulk_insert_post_table(posts, &pool).await.unwrap();
+
+        assert_eq!(select_random_posts(&pool, 3).await.unwrap().len(), 3);
+        assert_eq!(select_random_posts(&pool, 10).await.unwrap().len(), 5);
+    }
+
+    // 测试手动添加友链的增删改查
+    #[tokio::test]
+    async fn test_manual_friends() {
+        let pool = setup_test_db().await;
+
+        let mut friend = ManualFriend {
+            name: "手动友链".to_string(),
+            link: "https://manual.example.com/".to_string(),
+            avatar: String::new(),
+            suffix: String::new(),
+            created_at: "2024-06-01 00:00:00".to_string(),
+        };
+        insert_manual_friend(&friend, &pool).await.unwrap();
+        // link重复
+        assert!(insert_manual_friend(&friend, &pool).await.is_err());
+
+        friend.name = "改名".to_string();
+        friend.suffix = "atom.xml".to_string();
+        assert_eq!(update_manual_friend(&friend, &pool).await.unwrap(), 1);
+        let friends = select_all_from_manual_friends(&pool).await.unwrap();
+        assert_eq!(friends.len(), 1);
+        assert_eq!(friends[0].name, "改名");
+        assert_eq!(friends[0].suffix, "atom.xml");
+
+        assert_eq!(
+            delete_manual_friend("https://manual.example.com/", &pool)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(update_manual_friend(&friend, &pool).await.unwrap(), 0);
+        assert!(
+            select_all_from_manual_friends(&pool)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    // 测试屏蔽站点的增删查
+    #[tokio::test]
+    async fn test_blocked_sites() {
+        let pool = setup_test_db().await;
+
+        let site = BlockedSite {
+            pattern: "spam\\.example\\.com".to_string(),
+            created_at: "2024-06-01 00:00:00".to_string(),
+        };
+        insert_blocked_site(&site, &pool).await.unwrap();
+        assert!(insert_blocked_site(&site, &pool).await.is_err());
+        assert_eq!(
+            select_all_from_blocked_sites(&pool).await.unwrap(),
+            vec![site]
+        );
+
+        assert_eq!(
+            delete_blocked_site("spam\\.example\\.com", &pool)
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(
+            select_all_from_blocked_sites(&pool)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    // 测试友链覆盖设置的添加、修改和删除，以及置顶友链的排序
+    #[tokio::test]
+    async fn test_friend_overrides() {
+        let pool = setup_test_db().await;
+
+        let mut friend_override = FriendOverride {
+            link: "https://b.example.com/".to_string(),
+            name: "新名称".to_string(),
+            feed: String::new(),
+            theme: String::new(),
+            hidden: false,
+            pinned: true,
+            max_posts: 0,
+            created_at: "2024-06-01 00:00:00".to_string(),
+        };
+        upsert_friend_override(&friend_override, &pool)
+            .await
+            .unwrap();
+        friend_override.feed = "https://b.example.com/feed.xml".to_string();
+        friend_override.max_posts = 10;
+        friend_override.created_at = "2024-06-02 00:00:00".to_string();
+        upsert_friend_override(&friend_override, &pool)
+            .await
+            .unwrap();
+        let overrides = select_all_from_friend_overrides(&pool).await.unwrap();
+        assert_eq!(overrides.len(), 1);
+        assert_eq!(overrides[0].feed, "https://b.example.com/feed.xml");
+        assert!(overrides[0].pinned);
+        assert_eq!(overrides[0].max_posts, 10);
+        // 修改时保留原来的添加时间
+        assert_eq!(overrides[0].created_at, "2024-06-01 00:00:00");
+
+        let mut friend_a = Friends::new(
+            "a".to_string(),
+            "https://a.example.com/".to_string(),
+            String::new(),
+            false,
+            "2024-06-01 00:00:00".to_string(),
+        );
+        let mut friend_b = friend_a.clone();
+        friend_b.name = "b".to_string();
+        friend_b.pinned = true;
+        friend_a.latest_post = Some("2024-06-01".to_string());
+        bulk_insert_friend_table(vec![friend_a, friend_b].into_iter(), &pool)
+            .await
+            .unwrap();
+        let friends = select_all_from_friends(&pool).await.unwrap();
+        assert_eq!(friends[0].name, "b");
+        assert!(friends[0].pinned);
+        assert!(!friends[1].pinned);
+
+        assert_eq!(
+            delete_friend_override("https://b.example.com/", &pool)
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(
+            select_all_from_friend_overrides(&pool)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    // 测试只保留最新的文章，包括之前运行写入的文章
+    #[tokio::test]
+    async fn test_delete_excess_posts() {
+        let pool = setup_test_db().await;
+        let previous = [
+            ("https://a.com/1", "2024-01-01"),
+            ("https://a.com/3", "2024-01-03"),
+        ];
+        bulk_insert_post_table(
+            previous
+                .iter()
+                .map(|(link, created)| post(base_post(link, link, created), "作者")),
+            &pool,
+        )
+        .await
+        .unwrap();
+        let current = [
+            ("https://a.com/2", "2024-01-02"),
+            ("https://a.com/4", "2024-01-04"),
+        ];
+        bulk_insert_post_table(
+            current
+                .iter()
+                .map(|(link, created)| post(base_post(link, link, created), "作者")),
+            &pool,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            delete_excess_posts(0, PostSortBy::Created, &pool)
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            delete_excess_posts(2, PostSortBy::Created, &pool)
+                .await
+                .unwrap(),
+            2
+        );
+        let posts = select_all_from_posts(&pool, 0, 0, "created").await.unwrap();
+        let links: Vec<&str> = posts.iter().map(|post| post.meta.link.as_str()).collect();
+        assert_eq!(links, ["https://a.com/4", "https://a.com/3"]);
+    }
+
+    // 测试按作者或友链链接删除文章，其他友链的文章保留
+    #[tokio::test]
+    async fn test_delete_friend_posts() {
+        let pool = setup_test_db().await;
+        let posts = [
+            ("https://a.example.com/1/", "旧名称"),
+            ("https://a.example.com/2/", "新名称"),
+            ("https://a.example.org/3/", "旧名称"),
+            ("https://b.example.com/4/", "b"),
+        ];
+        bulk_insert_post_table(
+            posts
+                .iter()
+                .map(|(link, author)| post(base_post(link, link, "2024-01-01"), author)),
+            &pool,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            delete_friend_posts("https://a.example.com/", "旧名称", &pool)
+                .await
+                .unwrap(),
+            3
+        );
+        let posts = select_all_from_posts(&pool, 0, 0, "created").await.unwrap();
+        let links: Vec<&str> = posts.iter().map(|post| post.meta.link.as_str()).collect();
+        assert_eq!(links, ["https://b.example.com/4/"]);
+    }
 }
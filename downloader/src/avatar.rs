@@ -0,0 +1,168 @@
+use data_structures::config::AvatarCacheSettings;
+use image::{ImageFormat, imageops::FilterType};
+use reqwest::header::CONTENT_TYPE;
+use reqwest_middleware::ClientWithMiddleware;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Cursor;
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+/// 头像文件大小上限
+const AVATAR_MAX_BYTES: usize = 5 * 1024 * 1024;
+
+/// 可以解码并缩放的头像格式
+const AVATAR_CONTENT_TYPES: [&str; 5] = [
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "image/x-icon",
+];
+
+/// 检查、缩放头像并转换为png，返回(原始内容的哈希, png数据)
+///
+/// 以原始内容的哈希命名，不同地址的同一张头像只保存一份
+pub(crate) fn process_avatar(
+    content_type: &str,
+    bytes: &[u8],
+    size: u32,
+) -> Result<(String, Vec<u8>), String> {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    if !AVATAR_CONTENT_TYPES.contains(&mime.as_str()) {
+        return Err(format!("不支持的头像类型`{content_type}`"));
+    }
+    if bytes.len() > AVATAR_MAX_BYTES {
+        return Err(format!("头像过大: {} bytes", bytes.len()));
+    }
+    let img = image::load_from_memory(bytes).map_err(|e| format!("头像解码失败: {e}"))?;
+    let img = if img.width() > size || img.height() > size {
+        img.resize(size, size, FilterType::Lanczos3)
+    } else {
+        img
+    };
+    let mut png = Vec::new();
+    img.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| format!("头像编码失败: {e}"))?;
+
+    let hash = Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+    Ok((hash, png))
+}
+
+/// 下载头像并保存到缓存目录，返回头像哈希
+pub async fn mirror_avatar(
+    client: &ClientWithMiddleware,
+    url: &str,
+    settings: &AvatarCacheSettings,
+) -> Result<String, String> {
+    let resp = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .error_for_status()
+        .map_err(|e| e.to_string())?;
+    let content_type = resp
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    if let Some(len) = resp.content_length()
+        && len as usize > AVATAR_MAX_BYTES
+    {
+        return Err(format!("头像过大: {len} bytes"));
+    }
+    let bytes = resp.bytes().await.map_err(|e| e.to_string())?;
+
+    let (hash, png) = process_avatar(&content_type, &bytes, settings.size)?;
+    // path_of只会因哈希格式不对返回None，这里的哈希一定合法
+    let path = settings.path_of(&hash).unwrap();
+    if !tokio::fs::try_exists(&path).await.unwrap_or(false) {
+        tokio::fs::create_dir_all(&settings.dir)
+            .await
+            .map_err(|e| format!("创建头像目录失败: {e}"))?;
+        tokio::fs::write(&path, png)
+            .await
+            .map_err(|e| format!("写入头像失败: {e}"))?;
+    }
+    Ok(hash)
+}
+
+/// 并发镜像所有头像，返回 原头像地址 -> 镜像后的地址，镜像失败的头像不在其中
+pub async fn mirror_avatars(
+    client: &ClientWithMiddleware,
+    urls: impl IntoIterator<Item = String>,
+    settings: &AvatarCacheSettings,
+) -> HashMap<String, String> {
+    let mut joinset = JoinSet::new();
+    let mut seen = Vec::new();
+    for url in urls {
+        // 已经镜像过的地址和data:内联图片不需要处理
+        if seen.contains(&url) || url.starts_with("data:") || url.starts_with(&settings.url_prefix)
+        {
+            continue;
+        }
+        seen.push(url.clone());
+        let client = client.clone();
+        let settings = settings.clone();
+        joinset.spawn(async move {
+            let res = mirror_avatar(&client, &url, &settings).await;
+            (url, res)
+        });
+    }
+
+    let mut mirrored = HashMap::new();
+    while let Some(res) = joinset.join_next().await {
+        match res {
+            Ok((url, Ok(hash))) => {
+                mirrored.insert(url, settings.url_of(&hash));
+            }
+            Ok((url, Err(e))) => warn!("头像 {} 镜像失败，保留原地址: {}", url, e),
+            Err(e) => warn!("头像镜像任务失败: {}", e),
+        }
+    }
+    info!("头像镜像完成，成功 {} / {}", mirrored.len(), seen.len());
+    mirrored
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, RgbImage};
+
+    fn png_bytes(width: u32, height: u32) -> Vec<u8> {
+        let img = DynamicImage::ImageRgb8(RgbImage::new(width, height));
+        let mut bytes = Vec::new();
+        img.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    // 测试头像缩放和类型检查
+    #[test]
+    fn test_process_avatar() {
+        let bytes = png_bytes(512, 256);
+        let (hash, png) = process_avatar("image/png", &bytes, 128).unwrap();
+        assert_eq!(hash.len(), 64);
+        let resized = image::load_from_memory(&png).unwrap();
+        assert_eq!((resized.width(), resized.height()), (128, 64));
+
+        // 内容相同则哈希相同，小图不放大
+        let (hash2, png2) = process_avatar("image/png; charset=binary", &bytes, 1024).unwrap();
+        assert_eq!(hash, hash2);
+        let original = image::load_from_memory(&png2).unwrap();
+        assert_eq!((original.width(), original.height()), (512, 256));
+
+        assert!(process_avatar("text/html", &bytes, 128).is_err());
+        assert!(process_avatar("image/png", b"<html></html>", 128).is_err());
+    }
+}
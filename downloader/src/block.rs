@@ -0,0 +1,156 @@
+//! 屏蔽站点
+//!
+//! `BLOCK_SITE`中的规则在读取配置后编译为一个[`RegexSet`]，检查url时不再重复编译，
+//! 规则写法见[`tools::block_site_regex`]
+
+use regex::{Regex, RegexSet};
+use std::fmt;
+
+/// `BLOCK_SITE`中无法编译的一条规则
+#[derive(Debug)]
+pub struct InvalidPattern {
+    /// 规则在列表中的位置，从0开始
+    pub index: usize,
+    pub pattern: String,
+    pub source: regex::Error,
+}
+
+/// `BLOCK_SITE`中的规则无法编译
+#[derive(Debug)]
+pub enum BlockSiteError {
+    /// 所有无效的规则，按在列表中的位置排列
+    Invalid(Vec<InvalidPattern>),
+    /// 每条规则都有效，但规则过多，超出[`RegexSet`]的大小限制
+    TooLarge(regex::Error),
+}
+
+impl fmt::Display for BlockSiteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlockSiteError::Invalid(invalid) => {
+                write!(f, "`BLOCK_SITE`中有{}条规则无效", invalid.len())?;
+                for InvalidPattern {
+                    index,
+                    pattern,
+                    source,
+                } in invalid
+                {
+                    write!(f, "\n第{}条规则`{}`: {}", index, pattern, source)?;
+                }
+                Ok(())
+            }
+            BlockSiteError::TooLarge(source) => {
+                write!(f, "`BLOCK_SITE`中的规则过多: {}", source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BlockSiteError {}
+
+#[derive(Debug, Clone)]
+pub struct BlockSites {
+    /// 转换后的正则，追加规则时重新编译
+    regexes: Vec<String>,
+    set: RegexSet,
+}
+
+impl BlockSites {
+    /// 编译所有规则，有无效的规则时全部返回
+    pub fn new(patterns: &[String]) -> Result<BlockSites, BlockSiteError> {
+        let regexes: Vec<String> = patterns
+            .iter()
+            .map(|pattern| tools::block_site_regex(pattern))
+            .collect();
+        let invalid: Vec<InvalidPattern> = regexes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, regex)| {
+                Regex::new(regex).err().map(|source| InvalidPattern {
+                    index,
+                    pattern: patterns[index].clone(),
+                    source,
+                })
+            })
+            .collect();
+        if !invalid.is_empty() {
+            return Err(BlockSiteError::Invalid(invalid));
+        }
+        // 每条规则都能单独编译时，只有规则过多超出大小限制才会失败
+        let set = RegexSet::new(&regexes).map_err(BlockSiteError::TooLarge)?;
+        Ok(BlockSites { regexes, set })
+    }
+
+    /// 逐条追加规则，跳过无效或会超出大小限制的规则，返回跳过的规则和原因
+    pub fn extend<'a>(&mut self, patterns: &'a [String]) -> Vec<(&'a str, regex::Error)> {
+        let mut skipped = Vec::new();
+        for pattern in patterns {
+            let regex = tools::block_site_regex(pattern);
+            if let Err(e) = Regex::new(&regex) {
+                skipped.push((pattern.as_str(), e));
+                continue;
+            }
+            self.regexes.push(regex);
+            match RegexSet::new(&self.regexes) {
+                Ok(set) => self.set = set,
+                Err(e) => {
+                    self.regexes.pop();
+                    skipped.push((pattern.as_str(), e));
+                }
+            }
+        }
+        skipped
+    }
+
+    /// 检查屏蔽url，匹配返回true，反之为false
+    pub fn is_blocked(&self, url: &str) -> bool {
+        self.set.is_match(url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 测试正则、通配符和主机名三种规则，以及返回所有无效规则的位置
+    #[test]
+    fn test_block_sites() {
+        let block_sites = BlockSites::new(&[
+            String::from(r"^http://"),
+            String::from("glob:*.blogspot.com"),
+            String::from("host:Example.com"),
+        ])
+        .unwrap();
+
+        assert!(block_sites.is_blocked("http://a.com/"));
+        assert!(block_sites.is_blocked("https://foo.blogspot.com/2024/"));
+        assert!(!block_sites.is_blocked("https://blogspot.com.evil.org/"));
+        assert!(block_sites.is_blocked("https://example.com"));
+        assert!(block_sites.is_blocked("https://EXAMPLE.com:8080/about/"));
+        assert!(!block_sites.is_blocked("https://www.example.com/"));
+        assert!(!block_sites.is_blocked("https://example.com.cn/"));
+        assert!(!BlockSites::new(&[]).unwrap().is_blocked("https://a.com/"));
+
+        let patterns = [String::from("("), String::from("a"), String::from("[")];
+        let BlockSiteError::Invalid(invalid) = BlockSites::new(&patterns).unwrap_err() else {
+            panic!("应当返回无效的规则");
+        };
+        let invalid: Vec<(usize, &str)> = invalid
+            .iter()
+            .map(|e| (e.index, e.pattern.as_str()))
+            .collect();
+        assert_eq!(invalid, [(0, "("), (2, "[")]);
+    }
+
+    // 测试追加规则时跳过无效的规则，保留其余规则
+    #[test]
+    fn test_extend_block_sites() {
+        let mut block_sites = BlockSites::new(&[String::from("host:a.com")]).unwrap();
+        let patterns = [String::from("("), String::from("host:b.com")];
+        let skipped = block_sites.extend(&patterns);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].0, "(");
+        assert!(block_sites.is_blocked("https://a.com/"));
+        assert!(block_sites.is_blocked("https://b.com/"));
+    }
+}
@@ -1,14 +1,186 @@
+use super::download::{SiteMove, detect_site_move};
 use chrono::{FixedOffset, Utc};
 use data_structures::metadata;
-use feed_rs::parser;
+use feed_rs::{model::Entry, parser};
 use reqwest_middleware::ClientWithMiddleware;
 use std::{collections::HashMap, vec};
-use tracing::warn;
+use tracing::{info, warn};
 use url::{ParseError, Url};
 // time zones
 // +08:00
 pub static BEIJING_OFFSET: Option<FixedOffset> = FixedOffset::east_opt(8 * 60 * 60);
 
+/// 摘要最多保留的字符数
+const SUMMARY_MAX_CHARS: usize = 200;
+
+/// 合并空白字符并截断为摘要，结果为空时返回`None`
+pub(crate) fn truncate_summary(text: &str) -> Option<String> {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        return None;
+    }
+    if text.chars().count() > SUMMARY_MAX_CHARS {
+        let truncated: String = text.chars().take(SUMMARY_MAX_CHARS).collect();
+        Some(format!("{truncated}…"))
+    } else {
+        Some(text)
+    }
+}
+
+/// 去除html标签，生成纯文本摘要
+fn html_to_summary(html: &str) -> Option<String> {
+    let document = nipper::Document::from(html);
+    truncate_summary(&document.select("body").text())
+}
+
+/// 获取html片段中第一张图片的地址
+fn first_image_in_html(html: &str) -> Option<String> {
+    let document = nipper::Document::from(html);
+    document
+        .select("img")
+        .attr("src")
+        .map(|src| src.to_string())
+}
+
+/// 拆分css规则解析出的标签文本，支持`,`、`，`、`#`和空白分隔
+pub(crate) fn split_tags(text: &str) -> metadata::Tags {
+    metadata::Tags::new(
+        text.split(|c: char| c == ',' || c == '，' || c == '#' || c.is_whitespace())
+            .map(String::from),
+    )
+}
+
+/// 将相对地址拼接为绝对地址，无法处理时返回`None`
+pub(crate) fn complete_url(base_url: &Url, link: &str) -> Option<String> {
+    match Url::parse(link) {
+        Ok(_) => Some(link.to_string()),
+        Err(ParseError::RelativeUrlWithoutBase) => match base_url.join(link) {
+            Ok(completion_url) => Some(completion_url.to_string()),
+            Err(e) => {
+                warn!("无法拼接相对地址：{},error:{}", link, e);
+                None
+            }
+        },
+        Err(_) => {
+            warn!("无法处理地址：{}", link);
+            None
+        }
+    }
+}
+
+/// 地址的最大长度，与数据库中`link`和`avatar`字段的长度一致
+pub(crate) const URL_MAX_CHARS: usize = 1024;
+
+/// 将友链页中解析出的地址补全为绝对地址，支持相对地址和`//`开头的协议相对地址
+///
+/// 只接受http(s)地址；`allow_data`为true时也接受`data:image/`开头的内联图片；
+/// 超过[`URL_MAX_CHARS`]的地址无法保存，视为无效
+pub(crate) fn resolve_linkpage_url(
+    base_url: &Url,
+    raw: &str,
+    allow_data: bool,
+) -> Result<String, String> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return Err(String::from("地址为空"));
+    }
+    let url = if allow_data && raw.starts_with("data:image/") {
+        raw.to_string()
+    } else {
+        let url = base_url
+            .join(raw)
+            .map_err(|e| format!("无法解析地址`{raw}`: {e}"))?;
+        match url.scheme() {
+            "http" | "https" if url.host_str().is_some() => url.to_string(),
+            _ => return Err(format!("不支持的地址`{raw}`")),
+        }
+    };
+    let len = url.chars().count();
+    if len > URL_MAX_CHARS {
+        return Err(format!("地址过长({len}个字符)"));
+    }
+    Ok(url)
+}
+
+/// 判断是否为跟踪参数，如`utm_source`
+fn is_tracking_param(key: &str) -> bool {
+    key.starts_with("utm_") || matches!(key, "fbclid" | "gclid" | "spm")
+}
+
+/// 规范化文章地址：去除跟踪参数和片段，去除末尾的`index.html`
+///
+/// host的大小写和默认端口由`Url`自动处理，无法解析的地址原样返回
+pub fn canonicalize_url(link: &str) -> String {
+    let Ok(mut url) = Url::parse(link) else {
+        return link.to_string();
+    };
+    url.set_fragment(None);
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| !is_tracking_param(key))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    if pairs.is_empty() {
+        url.set_query(None);
+    } else if url.query_pairs().count() != pairs.len() {
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    let path = url.path().to_string();
+    for index in ["index.html", "index.htm"] {
+        if let Some(stripped) = path.strip_suffix(index)
+            && stripped.ends_with('/')
+        {
+            url.set_path(stripped);
+        }
+    }
+    url.to_string()
+}
+
+/// 去重使用的键：在规范化的基础上忽略协议、`www.`前缀和末尾的`/`
+pub fn dedup_key(link: &str) -> String {
+    let canonical = canonicalize_url(link);
+    let Ok(url) = Url::parse(&canonical) else {
+        return canonical;
+    };
+    let host = url.host_str().unwrap_or_default();
+    let host = host.strip_prefix("www.").unwrap_or(host);
+    let port = url.port().map(|p| format!(":{p}")).unwrap_or_default();
+    let path = url.path().trim_end_matches('/');
+    let query = url.query().map(|q| format!("?{q}")).unwrap_or_default();
+    format!("{host}{port}{path}{query}")
+}
+
+/// 提取feed条目的封面：优先使用media扩展中的图片，否则使用正文或摘要中的第一张图片
+fn feed_entry_cover(entry: &Entry, base_url: &Url) -> Option<String> {
+    let media_image = entry.media.iter().find_map(|media| {
+        media
+            .thumbnails
+            .first()
+            .map(|thumbnail| thumbnail.image.uri.clone())
+            .or_else(|| {
+                media.content.iter().find_map(|content| {
+                    let is_image = content
+                        .content_type
+                        .as_ref()
+                        .is_some_and(|t| t.to_string().starts_with("image/"));
+                    if is_image {
+                        content.url.as_ref().map(|url| url.to_string())
+                    } else {
+                        None
+                    }
+                })
+            })
+    });
+    let cover = media_image.or_else(|| {
+        let content = entry.content.as_ref().and_then(|c| c.body.as_deref());
+        let summary = entry.summary.as_ref().map(|s| s.content.as_str());
+        content
+            .and_then(first_image_in_html)
+            .or_else(|| summary.and_then(first_image_in_html))
+    })?;
+    complete_url(base_url, cover.trim())
+}
+
 pub async fn crawl_link_page<'a>(
     url: &str,
     theme: &str,
@@ -20,10 +192,17 @@ pub async fn crawl_link_page<'a>(
             Some(s) => s,
             None => panic!("`{theme}` field not found in css_rule"),
         };
-        let html = client.get(url).send().await?.text().await?;
+        let resp = client.get(url).send().await?;
+        // 跟随重定向后的地址，用于补全相对地址
+        let final_url = resp.url().to_string();
+        if final_url != url {
+            info!("友链页 {} 重定向到 {}", url, final_url);
+        }
+        let html = resp.text().await?;
         let document = nipper::Document::from(&html);
         // 返回结果init
         let mut result: HashMap<&str, Vec<String>> = HashMap::new();
+        result.insert("url", vec![final_url]);
         for rule in ["author", "link", "avatar"] {
             let fields = theme_rule
                 .get(rule)
@@ -44,11 +223,27 @@ pub async fn crawl_link_page<'a>(
                     .ok_or(format!("`{theme}-{rule}-attr` 字段缺失"))?
                     .as_str()
                     .ok_or(format!("`{theme}-{rule}-attr` 字段格式错误"))?;
+                // 可选，懒加载属性，如`data-lazy-src`，存在时优先于`attr`
+                let lazy_attrs: Vec<&str> = match field.get("lazy_attrs") {
+                    Some(v) => v
+                        .as_sequence()
+                        .ok_or(format!("`{theme}-{rule}-lazy_attrs` 字段格式错误"))?
+                        .iter()
+                        .filter_map(|v| v.as_str())
+                        .collect(),
+                    None => Vec::new(),
+                };
 
                 for elem in document.select(match_rule).iter() {
-                    let parsed_field = match attr {
-                        "text" => elem.text().to_string(),
-                        _ => match elem.attr(attr).map(|r| r.to_string()) {
+                    let lazy_value = lazy_attrs
+                        .iter()
+                        .filter_map(|lazy_attr| elem.attr(lazy_attr))
+                        .map(|r| r.to_string())
+                        .find(|v| !v.trim().is_empty());
+                    let parsed_field = match (lazy_value, attr) {
+                        (Some(v), _) => v,
+                        (None, "text") => elem.text().to_string(),
+                        (None, _) => match elem.attr(attr).map(|r| r.to_string()) {
                             Some(v) => v,
                             None => continue,
                         },
@@ -78,37 +273,45 @@ pub async fn crawl_link_page<'a>(
     }
 }
 
+/// 主页规则中必须配置的字段
+const POST_PAGE_REQUIRED_FIELDS: [&str; 4] = ["title", "link", "created", "updated"];
+/// 主页规则中可以选择配置的字段
+const POST_PAGE_OPTIONAL_FIELDS: [&str; 3] = ["summary", "cover", "tags"];
+
+/// 解析主页，同时返回主页重定向到其他站点时的迁移信息
 pub async fn crawl_post_page<'a>(
     url: &str,
     css_rules: &serde_yaml::Mapping,
     client: &ClientWithMiddleware,
-) -> Result<HashMap<&'a str, Vec<String>>, Box<dyn std::error::Error>> {
+) -> Result<(HashMap<&'a str, Vec<String>>, Option<SiteMove>), Box<dyn std::error::Error>> {
     // let html = reqwest::get(url).await?.text().await?;
     // DEBUG:
     // debug!("{}", url);
-    let html = client
-        .get(url)
-        .send()
-        .await?
-        .error_for_status()?
-        .text()
-        .await?;
+    let resp = client.get(url).send().await?.error_for_status()?;
+    let site_move = detect_site_move(url, &resp);
+    let html = resp.text().await?;
     let document = nipper::Document::from(&html);
     // 返回结果init
     let mut result: HashMap<&str, Vec<String>> = HashMap::new();
     // 使用过的css规则
     let mut used_css_rules = vec![];
-    'outer: for css_rule in css_rules {
+    for css_rule in css_rules {
         let use_theme = css_rule
             .0
             .as_str()
             .ok_or("无法解析字段，需要一个字符串".to_string())?;
         used_css_rules.push(use_theme.to_string());
-        for current_field in ["title", "link", "created", "updated"] {
-            let fields = css_rule
-                .1
-                .get(current_field)
-                .ok_or(format!("`{use_theme}-{current_field}` 字段缺失"))?;
+        for current_field in POST_PAGE_REQUIRED_FIELDS
+            .iter()
+            .chain(POST_PAGE_OPTIONAL_FIELDS.iter())
+        {
+            let current_field = *current_field;
+            let fields = match css_rule.1.get(current_field) {
+                Some(fields) => fields,
+                // 可选字段允许缺失
+                None if POST_PAGE_OPTIONAL_FIELDS.contains(&current_field) => continue,
+                None => return Err(format!("`{use_theme}-{current_field}` 字段缺失").into()),
+            };
             let fields = fields
                 .as_sequence()
                 .ok_or(format!("`{use_theme}-{current_field}` 字段格式错误"))?;
@@ -144,10 +347,6 @@ pub async fn crawl_post_page<'a>(
                     if !result.contains_key(current_field) {
                         result.insert(current_field, res);
                     }
-                    // 全部字段解析完毕
-                    if result.len() == 4 {
-                        break 'outer;
-                    }
                 } else {
                     // DEBUG:
                     // debug!(
@@ -157,6 +356,13 @@ pub async fn crawl_post_page<'a>(
                 };
             }
         }
+        // 必需字段解析完毕
+        if POST_PAGE_REQUIRED_FIELDS
+            .iter()
+            .all(|field| result.contains_key(field))
+        {
+            break;
+        }
     }
     // DEBUG:
     // if result.len() < 4 {
@@ -166,23 +372,26 @@ pub async fn crawl_post_page<'a>(
     //     );
     // }
     result.insert("rules", used_css_rules);
-    Ok(result)
+    Ok((result, site_move))
 }
 
+/// 解析订阅，同时返回订阅重定向到其他站点时的迁移信息
+///
+/// 订阅迁移后，相对地址按迁移后的站点补全
 pub async fn crawl_post_page_feed(
     url: &str,
     base_url: &Url,
     client: &ClientWithMiddleware,
-) -> Result<Vec<metadata::BasePosts>, Box<dyn std::error::Error>> {
+) -> Result<(Vec<metadata::BasePosts>, Option<SiteMove>), Box<dyn std::error::Error>> {
     // DEBUG:
     // debug!("feed.....{}", url);
-    let html = client
-        .get(url)
-        .send()
-        .await?
-        .error_for_status()?
-        .bytes()
-        .await?;
+    let resp = client.get(url).send().await?.error_for_status()?;
+    let site_move = detect_site_move(url, &resp);
+    let base_url = &match &site_move {
+        Some(site_move) => Url::parse(&site_move.moved_link(base_url.as_str()))?,
+        None => base_url.clone(),
+    };
+    let html = resp.bytes().await?;
     // let html = reqwest::get(url).await?.bytes().await?;
     if let Ok(feed_from_xml) = parser::parse(html.as_ref()) {
         let entries = feed_from_xml.entries;
@@ -190,9 +399,12 @@ pub async fn crawl_post_page_feed(
         let mut format_base_posts = vec![];
         for entry in entries {
             // 标题
-            let title = entry.title.map_or(String::from("文章标题获取失败"), |t| {
-                t.content.to_string()
-            });
+            let title = entry
+                .title
+                .as_ref()
+                .map_or(String::from("文章标题获取失败"), |t| {
+                    t.content.to_string()
+                });
             // url链接
             let link = if !entry.links.is_empty() {
                 entry.links[0].href.clone()
@@ -201,22 +413,29 @@ pub async fn crawl_post_page_feed(
                 continue;
             };
             // 处理相对地址
-            let link = match Url::parse(&link) {
-                Ok(_) => link,
-                Err(parse_error) => match parse_error {
-                    ParseError::RelativeUrlWithoutBase => match base_url.join(&link) {
-                        Ok(completion_url) => completion_url.to_string(),
-                        Err(e) => {
-                            warn!("无法拼接相对地址：{},error:{}", link, e);
-                            continue;
-                        }
-                    },
-                    _ => {
-                        warn!("无法处理地址：{}", link);
-                        continue;
-                    }
-                },
+            let link = match complete_url(base_url, &link) {
+                Some(link) => canonicalize_url(&link),
+                None => continue,
             };
+            // 摘要，优先使用summary，否则使用正文
+            let summary = entry
+                .summary
+                .as_ref()
+                .and_then(|t| html_to_summary(&t.content))
+                .or_else(|| {
+                    entry
+                        .content
+                        .as_ref()
+                        .and_then(|c| c.body.as_deref())
+                        .and_then(html_to_summary)
+                });
+            let cover = feed_entry_cover(&entry, base_url);
+            let tags = metadata::Tags::new(
+                entry
+                    .categories
+                    .iter()
+                    .map(|c| c.label.clone().unwrap_or_else(|| c.term.clone())),
+            );
             // 时间
             let created = match entry.published {
                 Some(t) => tools::strptime_to_string_ymd(t.fixed_offset()),
@@ -231,12 +450,72 @@ pub async fn crawl_post_page_feed(
                 // 使用创建时间
                 None => created.clone(),
             };
-            let base_post =
+            let mut base_post =
                 metadata::BasePosts::new(title, created, updated, link, "feed".to_string());
+            base_post.summary = summary;
+            base_post.cover = cover;
+            base_post.tags = tags;
             format_base_posts.push(base_post);
         }
-        Ok(format_base_posts)
+        Ok((format_base_posts, site_move))
     } else {
-        Ok(Vec::new())
+        Ok((Vec::new(), site_move))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 测试地址规范化
+    #[test]
+    fn test_canonicalize_url() {
+        assert_eq!(
+            canonicalize_url("https://Example.com:443/a/index.html?utm_source=rss&id=1#top"),
+            "https://example.com/a/?id=1"
+        );
+        assert_eq!(
+            canonicalize_url("https://example.com/a?utm_medium=feed"),
+            "https://example.com/a"
+        );
+        assert_eq!(canonicalize_url("not a url"), "not a url");
+    }
+
+    // 测试友链页地址补全
+    #[test]
+    fn test_resolve_linkpage_url() {
+        let base = Url::parse("https://example.com/link/").unwrap();
+        assert_eq!(
+            resolve_linkpage_url(&base, "/img/a.png", false).unwrap(),
+            "https://example.com/img/a.png"
+        );
+        assert_eq!(
+            resolve_linkpage_url(&base, "//cdn.example.org/a.png", false).unwrap(),
+            "https://cdn.example.org/a.png"
+        );
+        assert_eq!(
+            resolve_linkpage_url(&base, "friend/", false).unwrap(),
+            "https://example.com/link/friend/"
+        );
+        assert!(resolve_linkpage_url(&base, "data:image/png;base64,AAAA", true).is_ok());
+        assert!(resolve_linkpage_url(&base, "data:image/png;base64,AAAA", false).is_err());
+        assert!(resolve_linkpage_url(&base, "javascript:void(0)", false).is_err());
+        assert!(resolve_linkpage_url(&base, "  ", false).is_err());
+        // 过长的内联图片无法保存
+        let data = format!("data:image/png;base64,{}", "A".repeat(URL_MAX_CHARS));
+        assert!(resolve_linkpage_url(&base, &data, true).is_err());
+    }
+
+    // 测试去重键
+    #[test]
+    fn test_dedup_key() {
+        assert_eq!(
+            dedup_key("http://www.example.com/a/"),
+            dedup_key("https://example.com/a/index.html?utm_source=rss")
+        );
+        assert_ne!(
+            dedup_key("https://example.com/a"),
+            dedup_key("https://example.com/b")
+        );
     }
 }
@@ -0,0 +1,155 @@
+use data_structures::metadata::FailureKind;
+use reqwest::StatusCode;
+use reqwest_middleware::ClientWithMiddleware;
+use std::collections::HashMap;
+use std::error::Error;
+use tokio::task::JoinSet;
+use tracing::debug;
+
+/// 域名停放页中常见的文字，匹配时忽略大小写
+const PARKED_MARKERS: [&str; 10] = [
+    "domain is for sale",
+    "domain may be for sale",
+    "buy this domain",
+    "this domain is parked",
+    "parkingcrew",
+    "sedoparking",
+    "bodis.com",
+    "hugedomains",
+    "该域名正在出售",
+    "域名出售",
+];
+
+/// 拼接错误及其所有来源的描述，reqwest的错误描述不包含底层原因
+fn error_chain(err: &dyn Error) -> String {
+    let mut messages = vec![err.to_string()];
+    let mut source = err.source();
+    while let Some(err) = source {
+        messages.push(err.to_string());
+        source = err.source();
+    }
+    messages.join(": ")
+}
+
+/// 根据错误描述判断请求失败的原因
+pub(crate) fn classify_error_message(message: &str) -> FailureKind {
+    let message = message.to_lowercase();
+    if message.contains("dns error")
+        || message.contains("failed to lookup address")
+        || message.contains("name or service not known")
+        || message.contains("no such host")
+        || message.contains("nodename nor servname")
+    {
+        FailureKind::Nxdomain
+    } else if message.contains("connection refused") {
+        FailureKind::ConnectionRefused
+    } else if message.contains("timed out") || message.contains("timeout") {
+        FailureKind::Timeout
+    } else if message.contains("tls")
+        || message.contains("certificate")
+        || message.contains("handshake")
+    {
+        FailureKind::Tls
+    } else {
+        FailureKind::Other
+    }
+}
+
+/// 根据主页内容判断是否为域名停放页
+pub(crate) fn is_parked_page(html: &str) -> bool {
+    let html = html.to_lowercase();
+    PARKED_MARKERS.iter().any(|marker| html.contains(marker))
+}
+
+/// 请求主页，判断友链没有获取到文章的原因
+pub async fn diagnose_failure(client: &ClientWithMiddleware, url: &str) -> FailureKind {
+    let resp = match client.get(url).send().await {
+        Ok(v) => v,
+        Err(reqwest_middleware::Error::Reqwest(e)) if e.is_timeout() => {
+            return FailureKind::Timeout;
+        }
+        Err(e) => return classify_error_message(&error_chain(&e)),
+    };
+    let status = resp.status();
+    if status.is_client_error() {
+        return FailureKind::HttpClientError;
+    }
+    if status.is_server_error() {
+        return FailureKind::HttpServerError;
+    }
+    match resp.text().await {
+        Ok(html) if status == StatusCode::OK && is_parked_page(&html) => FailureKind::Parked,
+        Ok(_) => FailureKind::NoPosts,
+        Err(e) => classify_error_message(&error_chain(&e)),
+    }
+}
+
+/// 并发诊断所有友链，返回 友链地址 -> 失败原因
+pub async fn diagnose_failures(
+    client: &ClientWithMiddleware,
+    urls: impl IntoIterator<Item = String>,
+) -> HashMap<String, FailureKind> {
+    let mut joinset = JoinSet::new();
+    for url in urls {
+        let client = client.clone();
+        joinset.spawn(async move {
+            let kind = diagnose_failure(&client, &url).await;
+            debug!("{} 获取失败原因: {}", url, kind);
+            (url, kind)
+        });
+    }
+    let mut kinds = HashMap::new();
+    while let Some(res) = joinset.join_next().await {
+        if let Ok((url, kind)) = res {
+            kinds.insert(url, kind);
+        }
+    }
+    kinds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 测试失败原因分类
+    #[test]
+    fn test_classify_error_message() {
+        assert_eq!(
+            classify_error_message(
+                "error sending request: client error (Connect): dns error: failed to lookup address information: Name or service not known"
+            ),
+            FailureKind::Nxdomain
+        );
+        assert_eq!(
+            classify_error_message(
+                "client error (Connect): tcp connect error: Connection refused (os error 111)"
+            ),
+            FailureKind::ConnectionRefused
+        );
+        assert_eq!(
+            classify_error_message(
+                "client error (Connect): received fatal alert: HandshakeFailure"
+            ),
+            FailureKind::Tls
+        );
+        assert_eq!(
+            classify_error_message("operation timed out"),
+            FailureKind::Timeout
+        );
+        assert_eq!(classify_error_message("unexpected eof"), FailureKind::Other);
+    }
+
+    // 测试域名停放页识别
+    #[test]
+    fn test_is_parked_page() {
+        assert!(is_parked_page(
+            "<html><body><h1>This Domain Is For Sale</h1></body></html>"
+        ));
+        assert!(is_parked_page(
+            "<script src=\"//www.parkingcrew.net/x.js\"></script>"
+        ));
+        assert!(!is_parked_page(
+            "<html><body><h1>我的博客</h1></body></html>"
+        ));
+    }
+}
@@ -1,12 +1,12 @@
+use super::block::BlockSites;
 use super::crawler;
 use chrono::Utc;
 use data_structures::{
     config::Settings,
-    metadata::{self, BasePosts},
+    metadata::{self, BasePosts, FailureKind},
 };
-use regex::Regex;
-use reqwest::{ClientBuilder as CL, Proxy};
-use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+use reqwest::{ClientBuilder as CL, Proxy, StatusCode, header::LOCATION, redirect};
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware, Middleware, Next};
 use reqwest_retry::{RetryTransientMiddleware, policies::ExponentialBackoff};
 use std::collections::HashMap;
 use std::time::Duration;
@@ -15,43 +15,199 @@ use tools;
 use tracing::{error, info, trace, warn};
 use url::{ParseError, Url};
 
+/// 获取到的文章及请求时检测到的站点迁移
+type CrawlResult = (Vec<BasePosts>, Option<SiteMove>);
+
 async fn get_joinset_result(
-    joinset: &mut JoinSet<Vec<BasePosts>>,
+    joinset: &mut JoinSet<CrawlResult>,
     base_url: &Url,
-) -> Result<Vec<BasePosts>, Box<dyn std::error::Error>> {
+) -> Result<CrawlResult, Box<dyn std::error::Error>> {
     while let Some(res) = joinset.join_next().await {
         if let Ok(success) = res
-            && !success.is_empty()
+            && !success.0.is_empty()
         {
-            info!("{} 解析成功! 共{}条", base_url, success.len());
+            info!("{} 解析成功! 共{}条", base_url, success.0.len());
             return Ok(success);
         }
     }
     Err("css request failed".into())
 }
 
-/// 构建请求客户端
-pub fn build_client() -> ClientWithMiddleware {
+fn base_client_builder() -> CL {
     let timeout = Duration::new(20, 0);
     let baseclient = CL::new()
         .timeout(timeout)
         .use_rustls_tls()
         .danger_accept_invalid_certs(true);
 
-    let baseclient = match tools::get_env_var("PROXY") {
+    match tools::get_env_var("PROXY") {
         Ok(proxy) => {
             info!("use proxy: {}", proxy);
             baseclient.proxy(Proxy::all(proxy).unwrap())
         }
         Err(_) => baseclient,
-    };
-    let baseclient = baseclient.build().unwrap();
+    }
+}
+
+/// 按状态码统计请求数，重试后的最终结果只计一次，没有响应时记为`error`
+struct StatusMetrics;
+
+#[async_trait::async_trait]
+impl Middleware for StatusMetrics {
+    async fn handle(
+        &self,
+        req: reqwest::Request,
+        extensions: &mut http::Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<reqwest::Response> {
+        let res = next.run(req, extensions).await;
+        let status = match &res {
+            Ok(resp) => resp.status().as_u16().to_string(),
+            Err(_) => String::from("error"),
+        };
+        metrics::counter!("fcircle_crawl_http_responses_total", "status" => status).increment(1);
+        res
+    }
+}
+
+/// 最多跟随的重定向次数
+const MAX_REDIRECTS: usize = 10;
+
+/// 经过重定向的响应，保存在最终响应的`extensions`中
+#[derive(Debug, Clone, Copy)]
+struct Redirected {
+    /// 重定向链中全部为301/308
+    permanent: bool,
+}
+
+/// 逐跳跟随重定向，记录重定向链是否全部为永久重定向，用于检测站点迁移
+///
+/// 客户端本身不跟随重定向，每一跳的请求都会经过内层的重试
+struct FollowRedirects;
+
+#[async_trait::async_trait]
+impl Middleware for FollowRedirects {
+    async fn handle(
+        &self,
+        req: reqwest::Request,
+        extensions: &mut http::Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<reqwest::Response> {
+        let mut req = req;
+        let mut redirected: Option<Redirected> = None;
+        let mut hops = 0;
+        loop {
+            let next_req = req.try_clone();
+            let mut resp = next.clone().run(req, extensions).await?;
+            let status = resp.status();
+            let location = resp
+                .headers()
+                .get(LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| resp.url().join(v).ok());
+            match (next_req, location) {
+                (Some(mut next_req), Some(location))
+                    if status.is_redirection() && hops < MAX_REDIRECTS =>
+                {
+                    hops += 1;
+                    let permanent = redirected.is_none_or(|redirected| redirected.permanent);
+                    redirected = Some(Redirected {
+                        permanent: permanent && is_permanent_redirect(status),
+                    });
+                    *next_req.url_mut() = location;
+                    req = next_req;
+                }
+                _ => {
+                    if let Some(redirected) = redirected {
+                        resp.extensions_mut().insert(redirected);
+                    }
+                    return Ok(resp);
+                }
+            }
+        }
+    }
+}
+
+/// 构建请求客户端
+pub fn build_client() -> ClientWithMiddleware {
+    let baseclient = base_client_builder()
+        .redirect(redirect::Policy::none())
+        .build()
+        .unwrap();
     let retry_policy = ExponentialBackoff::builder().build_with_max_retries(3);
     ClientBuilder::new(baseclient)
+        .with(StatusMetrics)
+        .with(FollowRedirects)
         .with(RetryTransientMiddleware::new_with_policy(retry_policy))
         .build()
 }
 
+/// 站点迁移，即主页或订阅重定向到了其他站点
+#[derive(Debug, Clone, PartialEq)]
+pub struct SiteMove {
+    pub from: String,
+    pub to: String,
+    /// 重定向链中全部为301/308
+    pub permanent: bool,
+}
+
+impl SiteMove {
+    /// 友链迁移后的地址：主页的重定向直接使用最终地址，订阅的重定向只替换站点
+    pub fn moved_link(&self, link: &str) -> String {
+        if link == self.from {
+            return self.to.clone();
+        }
+        let (Ok(mut url), Ok(to)) = (Url::parse(link), Url::parse(&self.to)) else {
+            return link.to_string();
+        };
+        if url.set_scheme(to.scheme()).is_err()
+            || url.set_host(to.host_str()).is_err()
+            || url.set_port(to.port()).is_err()
+        {
+            return link.to_string();
+        }
+        url.to_string()
+    }
+}
+
+/// 站点的比较键：去除`www.`前缀的host和非默认端口，忽略协议和路径
+fn site_key(url: &Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    let host = host.strip_prefix("www.").unwrap_or(host);
+    match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_string(),
+    }
+}
+
+fn is_permanent_redirect(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::MOVED_PERMANENTLY | StatusCode::PERMANENT_REDIRECT
+    )
+}
+
+/// 重定向的最终地址与原地址不是同一站点时返回`SiteMove`
+fn site_move(from: &str, to: &Url, permanent: bool) -> Option<SiteMove> {
+    let from_url = Url::parse(from).ok()?;
+    if site_key(&from_url) == site_key(to) {
+        return None;
+    }
+    Some(SiteMove {
+        from: from.to_string(),
+        to: to.to_string(),
+        permanent,
+    })
+}
+
+/// 请求`from`得到的响应经过重定向，且最终地址与原地址不是同一站点时返回`SiteMove`
+///
+/// 只比较host，协议、`www.`或路径不同的重定向（如`/`到`/index.html`）不算迁移
+pub fn detect_site_move(from: &str, resp: &reqwest::Response) -> Option<SiteMove> {
+    let redirected = resp.extensions().get::<Redirected>()?;
+    site_move(from, resp.url(), redirected.permanent)
+}
+
 /// 检查link页面解析结果的长度
 /// 如果字段`author`、`link`、`avatar`缺失，则返回0
 /// 否则，检查`author`和`link`的长度：
@@ -81,34 +237,22 @@ fn check_linkpage_res_length(download_res: &HashMap<&str, Vec<String>>) -> usize
     }
 }
 
-/// 检查屏蔽url，匹配返回true，反之为false
-fn check_block_site(block_sites: &Vec<String>, url: &str) -> bool {
-    for pattern in block_sites {
-        let re = Regex::new(pattern).unwrap();
-        if re.is_match(url) {
-            return true;
-        }
-    }
-    false
-}
-
 pub async fn start_crawl_postpages(
     base_postpage_url: String,
-    settings: &Settings,
+    block_sites: &BlockSites,
     extra_feed_suffix: String,
     css_rules: &tools::Value,
     client: &ClientWithMiddleware,
-) -> Result<Vec<metadata::BasePosts>, Box<dyn std::error::Error>> {
+) -> Result<CrawlResult, Box<dyn std::error::Error>> {
     // check block url
-    let block_sites = &settings.block_site;
-    if check_block_site(block_sites, &base_postpage_url) {
-        return Ok(Vec::new());
+    if block_sites.is_blocked(&base_postpage_url) {
+        return Ok((Vec::new(), None));
     };
     let base_url = match Url::parse(&base_postpage_url) {
         Ok(v) => v,
         Err(e) => {
             error!("postpage_url:{} 解析失败:{}", base_postpage_url, e);
-            return Ok(Vec::new());
+            return Ok((Vec::new(), None));
         }
     };
     let css_rules = css_rules.clone();
@@ -141,7 +285,7 @@ pub async fn start_crawl_postpages(
                     Ok(v) => v,
                     Err(e) => {
                         trace!("{}", e);
-                        Vec::new()
+                        (Vec::new(), None)
                     }
                 }
             });
@@ -153,14 +297,19 @@ pub async fn start_crawl_postpages(
         }
         joinset.spawn(async move {
             // 获取当前时间
-            let mut download_postpage_res =
+            let (mut download_postpage_res, site_move) =
                 match crawler::crawl_post_page(&base_postpage_url, &css_rules, &client_).await {
                     Ok(v) => v,
                     Err(e) => {
                         warn!("{}", e);
-                        return Vec::new();
+                        return (Vec::new(), None);
                     }
                 };
+            // 主页迁移后，相对地址按迁移后的地址补全
+            let base_url_ = match &site_move {
+                Some(site_move) => Url::parse(&site_move.to).unwrap_or(base_url_),
+                None => base_url_,
+            };
             let length;
             // 字段缺失检查
 
@@ -174,7 +323,7 @@ pub async fn start_crawl_postpages(
                         "url: {} 解析结果缺失`title`或`link`长度不等",
                         base_postpage_url
                     );
-                    return Vec::new();
+                    return (Vec::new(), site_move);
                 } else {
                     // 关键字段长度相等
                     length = download_postpage_res.get("title").unwrap().len()
@@ -188,7 +337,7 @@ pub async fn start_crawl_postpages(
             } else {
                 // 缺失link，无力回天
                 error!("url: {} 解析结果缺失`link`", base_postpage_url);
-                return Vec::new();
+                return (Vec::new(), site_move);
             }
 
             let mut format_base_posts = vec![];
@@ -219,6 +368,7 @@ pub async fn start_crawl_postpages(
                         }
                     },
                 };
+                let link = crawler::canonicalize_url(&link);
                 let created = match download_postpage_res.get("created") {
                     Some(v) => {
                         if i < v.len() {
@@ -249,19 +399,36 @@ pub async fn start_crawl_postpages(
                     // 否则使用created
                     None => created.clone(),
                 };
+                // 可选字段，按下标对应
+                let optional_field = |field: &str| {
+                    download_postpage_res
+                        .get(field)
+                        .and_then(|v| v.get(i))
+                        .map(|v| v.trim())
+                        .filter(|v| !v.is_empty())
+                };
+                let summary = optional_field("summary").and_then(crawler::truncate_summary);
+                let cover =
+                    optional_field("cover").and_then(|v| crawler::complete_url(&base_url_, v));
+                let tags = optional_field("tags")
+                    .map(crawler::split_tags)
+                    .unwrap_or_default();
                 let rules = download_postpage_res.get("rules").unwrap();
-                let base_post =
+                let mut base_post =
                     metadata::BasePosts::new(title, created, updated, link, rules.join(","));
+                base_post.summary = summary;
+                base_post.cover = cover;
+                base_post.tags = tags;
                 format_base_posts.push(base_post);
             }
-            format_base_posts
+            (format_base_posts, site_move)
         });
         if let Ok(posts) = get_joinset_result(&mut joinset, &base_url).await {
             info!("使用css规则解析成功:{}", base_url);
             Ok(posts)
         } else {
             info!("解析失败:{}", base_url);
-            Ok(Vec::new())
+            Ok((Vec::new(), None))
         }
     } else {
         error!("css_rule 格式错误");
@@ -271,6 +438,7 @@ pub async fn start_crawl_postpages(
 
 pub async fn start_crawl_linkpages(
     settings: &Settings,
+    block_sites: &BlockSites,
     css_rules: &tools::Value,
     client: &ClientWithMiddleware,
 ) -> Vec<metadata::Friends> {
@@ -278,8 +446,7 @@ pub async fn start_crawl_linkpages(
     let start_urls = &settings.link;
     for linkmeta in start_urls {
         // check block url
-        let block_sites = &settings.block_site;
-        if check_block_site(block_sites, &linkmeta.link) {
+        if block_sites.is_blocked(&linkmeta.link) {
             continue;
         };
         let download_linkpage_res = match crawler::crawl_link_page(
@@ -296,30 +463,178 @@ pub async fn start_crawl_linkpages(
                 continue;
             }
         };
+        // 友链页可能重定向，按最终地址补全相对地址
+        let linkpage_url = download_linkpage_res
+            .get("url")
+            .and_then(|v| v.first())
+            .map_or(linkmeta.link.as_str(), |v| v.as_str());
+        let linkpage_url = match Url::parse(linkpage_url) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("linkpage:{} 解析失败:{}", linkmeta.link, e);
+                continue;
+            }
+        };
         let length = check_linkpage_res_length(&download_linkpage_res);
         for i in 0..length {
             let author = download_linkpage_res.get("author").unwrap()[i]
                 .trim()
                 .to_string();
-            // TODO 链接拼接检查
-            let link = download_linkpage_res.get("link").unwrap()[i]
-                .trim()
-                .to_string();
-            // TODO 链接拼接检查
+            let tm = Utc::now().with_timezone(&crawler::BEIJING_OFFSET.unwrap());
+            let created_at = tools::strptime_to_string_ymdhms(tm);
+            // 补全相对地址，无效的链接无法抓取，按原地址记为获取失败
+            let raw_link = &download_linkpage_res.get("link").unwrap()[i];
+            let link = match crawler::resolve_linkpage_url(&linkpage_url, raw_link, false) {
+                Ok(v) => v,
+                Err(e) => {
+                    warn!(
+                        "linkpage:{} 友链`{}`的链接无效: {}",
+                        linkmeta.link, author, e
+                    );
+                    let raw_link: String = raw_link
+                        .trim()
+                        .chars()
+                        .take(crawler::URL_MAX_CHARS)
+                        .collect();
+                    let mut friend = metadata::Friends::new(
+                        author,
+                        raw_link,
+                        settings.default_avatar.clone(),
+                        true,
+                        created_at,
+                    );
+                    friend.error_kind = Some(FailureKind::InvalidLink);
+                    format_base_friends.push(friend);
+                    continue;
+                }
+            };
             let _avatar = download_linkpage_res.get("avatar").unwrap();
-            let avatar = if i < _avatar.len() {
-                download_linkpage_res.get("avatar").unwrap()[i]
-                    .trim()
-                    .to_string()
-            } else {
+            let mut invalid_avatar = false;
+            let avatar = match _avatar
+                .get(i)
+                .map(|avatar| crawler::resolve_linkpage_url(&linkpage_url, avatar, true))
+            {
+                Some(Ok(v)) => v,
+                Some(Err(e)) => {
+                    warn!(
+                        "linkpage:{} 友链`{}`的头像无效，使用默认头像: {}",
+                        linkmeta.link, author, e
+                    );
+                    invalid_avatar = true;
+                    settings.default_avatar.clone()
+                }
                 // 默认图片
-                String::from("https://sdn.geekzu.org/avatar/57d8260dfb55501c37dde588e7c3852c")
+                None => settings.default_avatar.clone(),
             };
-            let tm = Utc::now().with_timezone(&crawler::BEIJING_OFFSET.unwrap());
-            let created_at = tools::strptime_to_string_ymdhms(tm);
-            let base_post = metadata::Friends::new(author, link, avatar, false, created_at);
+            let mut base_post = metadata::Friends::new(author, link, avatar, false, created_at);
+            base_post.invalid_avatar = invalid_avatar;
             format_base_friends.push(base_post);
         }
     }
     format_base_friends
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 测试只有host变化的重定向才算站点迁移
+    #[test]
+    fn test_site_move() {
+        let to = Url::parse("https://new.example.org/blog/").unwrap();
+        assert_eq!(
+            site_move("https://old.example.com/", &to, true),
+            Some(SiteMove {
+                from: String::from("https://old.example.com/"),
+                to: String::from("https://new.example.org/blog/"),
+                permanent: true,
+            })
+        );
+
+        // 同一站点内的路径重定向
+        for (from, to) in [
+            ("https://a.example.com/", "https://a.example.com/index.html"),
+            ("https://a.example.com/blog", "https://a.example.com/blog/"),
+            ("http://a.example.com/", "https://www.a.example.com/"),
+        ] {
+            assert_eq!(site_move(from, &Url::parse(to).unwrap(), true), None);
+        }
+        let to = Url::parse("https://a.example.com:8443/").unwrap();
+        assert!(site_move("https://a.example.com/", &to, true).is_some());
+    }
+
+    // 测试主页迁移使用最终地址，订阅迁移只替换站点
+    #[test]
+    fn test_moved_link() {
+        let homepage = SiteMove {
+            from: String::from("https://old.example.com/"),
+            to: String::from("https://new.example.org/blog/"),
+            permanent: true,
+        };
+        assert_eq!(
+            homepage.moved_link("https://old.example.com/"),
+            "https://new.example.org/blog/"
+        );
+
+        let feed = SiteMove {
+            from: String::from("http://old.example.com/blog/atom.xml"),
+            to: String::from("https://new.example.org:8443/blog/atom.xml"),
+            permanent: true,
+        };
+        assert_eq!(
+            feed.moved_link("http://old.example.com/blog/"),
+            "https://new.example.org:8443/blog/"
+        );
+        assert_eq!(feed.moved_link("not a url"), "not a url");
+    }
+
+    /// 在本地端口上依次返回`responses`，返回本地地址
+    fn serve(responses: Vec<String>) -> String {
+        use std::io::{Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buf = [0; 1024];
+                let _ = stream.read(&mut buf);
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        addr
+    }
+
+    fn redirect(status: &str, location: &str) -> String {
+        format!(
+            "HTTP/1.1 {status}\r\nLocation: {location}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        )
+    }
+
+    // 测试跟随重定向后记录最终地址和是否永久迁移
+    #[tokio::test]
+    async fn test_follow_redirects() {
+        let to = serve(vec![String::from(
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+        )]);
+        let from = serve(vec![
+            redirect("301 Moved Permanently", "/atom.xml"),
+            redirect("302 Found", &format!("{to}/feed.xml")),
+        ]);
+        let from = format!("{from}/feed");
+        let resp = build_client().get(&from).send().await.unwrap();
+        assert_eq!(resp.url().as_str(), format!("{to}/feed.xml"));
+        let site_move = detect_site_move(&from, &resp).unwrap();
+        assert_eq!(site_move.to, format!("{to}/feed.xml"));
+        assert!(!site_move.permanent);
+        assert_eq!(resp.text().await.unwrap(), "ok");
+    }
+
+    // 测试只有301和308算永久重定向
+    #[test]
+    fn test_is_permanent_redirect() {
+        assert!(is_permanent_redirect(StatusCode::MOVED_PERMANENTLY));
+        assert!(is_permanent_redirect(StatusCode::PERMANENT_REDIRECT));
+        assert!(!is_permanent_redirect(StatusCode::FOUND));
+        assert!(!is_permanent_redirect(StatusCode::TEMPORARY_REDIRECT));
+    }
+}
@@ -1,4 +1,7 @@
+pub mod avatar;
+pub mod block;
 mod crawler;
+pub mod diagnose;
 pub mod download;
 
-pub use crawler::BEIJING_OFFSET;
+pub use crawler::{BEIJING_OFFSET, canonicalize_url, dedup_key};
@@ -127,3 +127,40 @@ pub fn get_env_var(var_name: &str) -> Result<String, Box<dyn std::error::Error>>
         )))),
     }
 }
+
+/// 字段值为`${变量名}`时从环境变量读取，否则原样返回
+pub fn resolve_secret(value: &str) -> Result<String, Box<dyn std::error::Error>> {
+    match value
+        .strip_prefix("${")
+        .and_then(|value| value.strip_suffix('}'))
+    {
+        Some(var_name) => get_env_var(var_name),
+        None => Ok(value.to_string()),
+    }
+}
+
+/// 将`BLOCK_SITE`中的规则转换为匹配完整url的正则
+///
+/// - `host:example.com`：主机名完全相同，不区分大小写
+/// - `glob:*.example.com`：主机名匹配通配符，`*`匹配任意个字符，`?`匹配一个字符，不区分大小写
+/// - 其它：正则表达式，原样使用
+pub fn block_site_regex(pattern: &str) -> String {
+    const HOST_PREFIX: &str = r"(?i)^[a-z][a-z0-9+.\-]*://(?:[^/?#@]*@)?";
+    const HOST_SUFFIX: &str = r"(?::\d+)?(?:[/?#]|$)";
+    if let Some(host) = pattern.strip_prefix("host:") {
+        format!("{HOST_PREFIX}{}{HOST_SUFFIX}", regex::escape(host.trim()))
+    } else if let Some(glob) = pattern.strip_prefix("glob:") {
+        let host: String = glob
+            .trim()
+            .chars()
+            .map(|c| match c {
+                '*' => String::from("[^/?#:@]*"),
+                '?' => String::from("[^/?#:@]"),
+                c => regex::escape(&c.to_string()),
+            })
+            .collect();
+        format!("{HOST_PREFIX}{host}{HOST_SUFFIX}")
+    } else {
+        pattern.to_string()
+    }
+}